@@ -3,6 +3,7 @@ MEMORY
   /* NOTE 1 K = 1 KiBi = 1024 bytes */
   /* TODO Adjust these memory regions to match your device memory layout */
   /* These values correspond to the LM3S6965, one of the few devices QEMU can emulate */
+/* bank 1 only, the last sector of bank 2 (0x081E0000) holds the settings */
 FLASH : ORIGIN = 0x08000000, LENGTH = 1024k
   RAM : ORIGIN = 0x24000000, LENGTH = 512K
 }
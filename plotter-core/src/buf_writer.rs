@@ -5,7 +5,7 @@ pub struct BufWriter<'a> {
 }
 
 impl<'a> BufWriter<'a> {
-    pub fn new(buf: &mut [u8]) -> BufWriter {
+    pub fn new(buf: &mut [u8]) -> BufWriter<'_> {
         BufWriter {
             data: buf,
             next_buf_idx: 0,
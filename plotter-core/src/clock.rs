@@ -0,0 +1,41 @@
+use core::cell::Cell;
+
+/// source of time for everything that measures it
+pub trait Clock {
+    /// us since some fixed point, never goes backwards
+    fn now(&self) -> u64;
+}
+
+impl<C: Clock> Clock for &C {
+    #[inline]
+    fn now(&self) -> u64 {
+        (*self).now()
+    }
+}
+
+/// clock that only moves when told to, for simulations and tests
+pub struct ManualClock {
+    now: Cell<u64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self { now: Cell::new(0) }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.set(now);
+    }
+
+    /// moves the clock `us` forward
+    pub fn advance(&self, us: u64) {
+        self.now.set(self.now.get() + us);
+    }
+}
+
+impl Clock for ManualClock {
+    #[inline]
+    fn now(&self) -> u64 {
+        self.now.get()
+    }
+}
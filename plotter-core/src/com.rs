@@ -0,0 +1,699 @@
+//! turns what arrives over the command transports into g-code, real-time
+//! commands and requests for the motion controller. datagram transports carry
+//! plain text or `protocol` frames, stream transports `\n` terminated lines.
+//! the motion controller sees the command handler as a `Host`, the tests
+//! script it.
+
+use core::fmt::Arguments;
+
+use heapless::consts::*;
+use heapless::Vec;
+
+use crate::clock::Clock;
+use crate::grbl_settings::{self, SettingsCommand};
+use crate::protocol::{self, Delivery, Frame, NackReason, Reply, SequenceTracker};
+use crate::transport::{self, CommandTransport, Framing, Peer};
+
+/// commands that skip the g-code queue, sent as single bytes in plain text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RealtimeCommand {
+    /// `!`, decelerate to a stop and lift the pen
+    FeedHold,
+    /// `~`, continue after a feed hold
+    Resume,
+    /// ctrl-x, stop right away and drop everything queued
+    Abort,
+    /// `?`, report the machine status
+    StatusQuery,
+}
+
+impl RealtimeCommand {
+    pub fn from_byte(byte: u8) -> Option<RealtimeCommand> {
+        match byte {
+            b'!' => Some(RealtimeCommand::FeedHold),
+            b'~' => Some(RealtimeCommand::Resume),
+            0x18 => Some(RealtimeCommand::Abort),
+            b'?' => Some(RealtimeCommand::StatusQuery),
+            _ => None,
+        }
+    }
+}
+
+/// `>telemetry <rate_hz>` subscribes the sender, `>telemetry off` stops it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TelemetryRequest {
+    Subscribe { peer: Peer, rate_hz: u32 },
+    Unsubscribe,
+}
+
+impl TelemetryRequest {
+    pub fn parse(args: &str, sender: Peer) -> Result<TelemetryRequest, ()> {
+        match args.trim() {
+            "off" => Ok(TelemetryRequest::Unsubscribe),
+            rate => match rate.parse::<u32>() {
+                Ok(rate_hz) if rate_hz > 0 => Ok(TelemetryRequest::Subscribe {
+                    peer: sender,
+                    rate_hz,
+                }),
+                _ => Err(()),
+            },
+        }
+    }
+}
+
+/// the command handler as the motion controller sees it
+pub trait Host {
+    /// g-code received and not consumed yet, oldest first
+    fn get_gcode_buffer(&self) -> &[gcode::GCode];
+
+    fn clear_gcode_buffer(&mut self);
+
+    /// drops the first `count` lines of the g-code buffer
+    fn consume_gcode(&mut self, count: usize);
+
+    /// number of g-code lines that can still be buffered
+    fn gcode_buffer_free(&self) -> usize;
+
+    /// real-time commands in the order they arrived, each at most once. they
+    /// have to be handled before anything in the g-code buffer
+    fn realtime_commands(&self) -> &[RealtimeCommand];
+
+    fn clear_realtime_commands(&mut self);
+
+    fn take_telemetry_request(&mut self) -> Option<TelemetryRequest>;
+
+    /// `$$`, `$n`, `$n=value` or `$RST`, already validated by itself
+    fn take_settings_command(&mut self) -> Option<SettingsCommand>;
+
+    /// true once after the host asked for a homing cycle
+    fn needs_calibration(&mut self) -> bool;
+
+    /// true once after the host sent `$X`
+    fn take_unlock_request(&mut self) -> bool;
+
+    /// while locked, g-code is turned away with an error
+    fn set_locked(&mut self, locked: bool);
+
+    /// true once after a stream client that sent something disconnected
+    fn take_client_dropped(&mut self) -> bool;
+
+    /// true once the host has been silent for longer than the heartbeat
+    /// timeout, then not again until the next packet arrives
+    fn host_timed_out(&mut self) -> bool;
+
+    /// sends a text reply to the host, what can't be sent is dropped
+    fn send(&mut self, args: Arguments);
+
+    /// sends binary `data` to `peer`, what can't be sent is dropped
+    fn send_to(&mut self, data: &[u8], peer: Peer);
+}
+
+/// `>heartbeat <seconds>` sets how long the host may stay silent during a job,
+/// `>heartbeat off` turns the check off
+fn parse_heartbeat(args: &str) -> Result<Option<u64>, ()> {
+    match args.trim() {
+        "off" => Ok(None),
+        secs => match secs.parse::<f32>() {
+            Ok(secs) if secs > 0.0 => Ok(Some((secs * 1_000_000.0) as u64)),
+            _ => Err(()),
+        },
+    }
+}
+
+/// like `Vec::truncate`, which indexes one past the end in heapless 0.6 and
+/// trips the checks of debug builds
+// the bound is only deprecated in favour of a generic-array heapless 0.6 can't use
+#[allow(deprecated)]
+pub(crate) fn truncate<T, N: heapless::ArrayLength<T>>(vec: &mut Vec<T, N>, len: usize) {
+    while vec.len() > len {
+        vec.pop();
+    }
+}
+
+/// longest line accepted over a stream
+const MAX_LINE_LEN: usize = 576;
+
+/// bytes of a stream that aren't a whole line yet, or a line that has to
+/// wait for room in the g-code buffer
+struct LineBuffer {
+    data: [u8; MAX_LINE_LEN],
+    len: usize,
+}
+
+impl LineBuffer {
+    const fn new() -> Self {
+        Self {
+            data: [0u8; MAX_LINE_LEN],
+            len: 0,
+        }
+    }
+
+    /// where the next read goes
+    #[inline]
+    fn spare(&mut self) -> &mut [u8] {
+        &mut self.data[self.len..]
+    }
+
+    /// the first whole line, without its newline
+    fn line(&self) -> Option<&[u8]> {
+        self.data[..self.len]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map(|end| &self.data[..end])
+    }
+
+    /// drops the first line, `line_len` long, and its newline
+    fn consume(&mut self, line_len: usize) {
+        self.data.copy_within(line_len + 1..self.len, 0);
+        self.len -= line_len + 1;
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    #[inline]
+    fn is_full(&self) -> bool {
+        self.len == MAX_LINE_LEN
+    }
+}
+
+struct Channel<T> {
+    transport: T,
+    /// only used by streams
+    lines: LineBuffer,
+    /// the stream client that sent something, it gets no buffer messages
+    /// since the stream already holds it back
+    streaming: Option<Peer>,
+}
+
+/// transports that can be added
+pub const MAX_TRANSPORTS: usize = 4;
+
+pub struct CommandHandler<T: CommandTransport, C: Clock> {
+    channels: Vec<Channel<T>, U4>,
+    clock: C,
+    /// channel the last message came from, replies go there
+    current: usize,
+
+    recv_buffer: [u8; 576],
+    gcode_buffer: Vec<gcode::GCode, U512>,
+    realtime_commands: Vec<RealtimeCommand, U8>,
+
+    page_count: usize,
+
+    buffer_empty_sent: bool,
+    buffer_full_sent: bool,
+
+    calibration_request: bool,
+    unlock_request: bool,
+    telemetry_request: Option<TelemetryRequest>,
+    settings_command: Option<SettingsCommand>,
+    /// the machine is in alarm, g-code is turned away until it's unlocked
+    locked: bool,
+    /// a stream client went away and it wasn't taken yet
+    client_dropped: bool,
+
+    /// us the host may stay silent, `None` if it may disappear any time
+    heartbeat_timeout: Option<u64>,
+    /// time the last packet arrived
+    last_packet: u64,
+    /// the timeout was reported and no packet came in since
+    host_lost: bool,
+
+    /// data frame expected next
+    tracker: SequenceTracker,
+}
+
+impl<T: CommandTransport, C: Clock> CommandHandler<T, C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            channels: Vec::new(),
+            clock,
+            current: 0,
+
+            recv_buffer: [0u8; 576],
+            gcode_buffer: Vec::new(),
+            realtime_commands: Vec::new(),
+
+            page_count: 0,
+
+            buffer_empty_sent: false,
+            buffer_full_sent: false,
+
+            calibration_request: false,
+            unlock_request: false,
+            telemetry_request: None,
+            settings_command: None,
+            locked: false,
+            client_dropped: false,
+
+            heartbeat_timeout: None,
+            last_packet: 0,
+            host_lost: false,
+
+            tracker: SequenceTracker::new(),
+        }
+    }
+
+    /// commands are taken from every added transport, `Err(())` once there
+    /// are `MAX_TRANSPORTS`. the first one gets the messages nobody asked for
+    /// until something arrives.
+    pub fn add_transport(&mut self, transport: T) -> Result<(), ()> {
+        self.channels
+            .push(Channel {
+                transport,
+                lines: LineBuffer::new(),
+                streaming: None,
+            })
+            .map_err(|_| ())
+    }
+
+    #[inline]
+    pub fn tick(&mut self) {
+        if !self.streaming() && !self.buffer_empty_sent && self.gcode_buffer.is_empty() {
+            let page_count = self.page_count;
+            self.send(format_args!("[{}] buffer empty\n\r", page_count));
+            self.buffer_empty_sent = true;
+            self.buffer_full_sent = false;
+        }
+
+        for idx in 0..self.channels.len() {
+            match self.channels[idx].transport.framing() {
+                Framing::Datagram => self.tick_datagram(idx),
+                Framing::Stream => self.tick_stream(idx),
+            }
+        }
+    }
+
+    /// handles one datagram of channel `idx`
+    fn tick_datagram(&mut self, idx: usize) {
+        let transport = &mut self.channels[idx].transport;
+        let (len, sender) = match transport.recv(&mut self.recv_buffer) {
+            Some((0, _)) => return,
+            Some(received) => received,
+            None => return,
+        };
+        self.current = idx;
+        self.last_packet = self.clock.now();
+        self.host_lost = false;
+
+        let len = if self.recv_buffer[0] != protocol::FRAME_MAGIC {
+            // real-time commands are picked out before anything gets queued
+            let data = &mut self.recv_buffer[..len];
+            match Self::take_realtime_commands(&mut self.realtime_commands, data) {
+                Some(len) => len,
+                None => return self.abort(),
+            }
+        } else {
+            len
+        };
+        let data = &self.recv_buffer[..len];
+
+        match protocol::parse_frame(data) {
+            // plain text, no delivery guarantees
+            None => {
+                if self.handle_text(len, sender) {
+                    return;
+                }
+            }
+            Some(Ok(Frame::Sync { seq })) => {
+                // acks always mean "everything up to here is in", so a sync is
+                // acked with the number before the one it expects next
+                self.tracker.sync(seq);
+                self.reply(Ok(seq.wrapping_sub(1)));
+            }
+            Some(Ok(Frame::Data { seq, .. })) if self.locked => {
+                // not accepted, the host has to resend it after the unlock
+                self.reply(Err((seq, NackReason::Alarm)));
+            }
+            Some(Ok(Frame::Data { seq, payload })) => match self.tracker.classify(seq) {
+                Delivery::New => {
+                    let result = Self::queue_frame(&mut self.gcode_buffer, payload);
+                    if let Ok(()) = result {
+                        self.tracker.accept();
+                    }
+                    self.reply(result.map(|_| seq).map_err(|e| (seq, e)));
+                }
+                Delivery::Duplicate => self.reply(Ok(seq)),
+                Delivery::Gap => {
+                    let expected = self.tracker.expected();
+                    self.reply(Err((expected, NackReason::OutOfOrder)));
+                }
+            },
+            Some(Err(())) => {
+                let expected = self.tracker.expected();
+                self.reply(Err((expected, NackReason::Malformed)));
+            }
+        }
+
+        if !self.streaming() && self.gcode_buffer.len() >= 511 {
+            let page_count = self.page_count;
+            self.send(format_args!("[{}] buffer full", page_count));
+            if !self.buffer_full_sent {
+                self.page_count += 1;
+            }
+            self.buffer_full_sent = true;
+            self.buffer_empty_sent = false;
+        }
+    }
+
+    /// reads more of the stream of channel `idx` and hands whole lines on
+    /// once the g-code buffer has room for them. nothing more is read while
+    /// the line buffer is full, so a busy plotter holds the client back.
+    fn tick_stream(&mut self, idx: usize) {
+        let channel = &mut self.channels[idx];
+        if channel.transport.take_disconnected() {
+            channel.lines.clear();
+            if channel.streaming.take().is_some() {
+                self.client_dropped = true;
+            }
+        }
+
+        let channel = &mut self.channels[idx];
+        if !channel.lines.is_full() {
+            if let Some((len, peer)) = channel.transport.recv(channel.lines.spare()) {
+                channel.streaming = Some(peer);
+                self.received(idx, len);
+            }
+        }
+
+        if let Some(peer) = self.channels[idx].streaming {
+            self.queue_lines(idx, peer);
+        }
+    }
+
+    /// takes the real-time commands out of the `len` bytes just read into
+    /// the line buffer of channel `idx`, they don't wait behind the g-code
+    fn received(&mut self, idx: usize, len: usize) {
+        let lines = &mut self.channels[idx].lines;
+        let data = &mut lines.data[lines.len..lines.len + len];
+        match Self::take_realtime_commands(&mut self.realtime_commands, data) {
+            Some(len) => lines.len += len,
+            None => self.abort(),
+        }
+
+        // a status query has to be answered here
+        self.current = idx;
+        self.last_packet = self.clock.now();
+        self.host_lost = false;
+    }
+
+    fn queue_lines(&mut self, idx: usize, sender: Peer) {
+        loop {
+            let lines = &mut self.channels[idx].lines;
+            let line = match lines.line() {
+                Some(line) => line,
+                None => break,
+            };
+            let needed = core::str::from_utf8(line)
+                .map(|line| gcode::parse(line).count())
+                .unwrap_or(0);
+            if needed > self.gcode_buffer.capacity() - self.gcode_buffer.len() {
+                return;
+            }
+
+            let len = line.len();
+            self.recv_buffer[..len].copy_from_slice(line);
+            lines.consume(len);
+            self.current = idx;
+            self.handle_text(len, sender);
+        }
+
+        if self.channels[idx].lines.is_full() {
+            self.channels[idx].lines.clear();
+            self.current = idx;
+            self.send(format_args!("error: line too long\n\r"));
+        }
+    }
+
+    /// a stream client is sending
+    #[inline]
+    fn streaming(&self) -> bool {
+        self.channels
+            .iter()
+            .any(|channel| channel.streaming.is_some())
+    }
+
+    /// handles the plain text in `self.recv_buffer[..len]`, returns true if it
+    /// was a command rather than g-code
+    fn handle_text(&mut self, len: usize, sender: Peer) -> bool {
+        let code_str = match core::str::from_utf8(&self.recv_buffer[..len]) {
+            Ok(code_str) => code_str,
+            Err(_) => return false,
+        };
+        let line = code_str.trim();
+        if line == ">calibrate" || line == ">home" {
+            self.calibration_request = true;
+            return true;
+        }
+        if line == "$X" || line == "$x" {
+            self.unlock_request = true;
+            return true;
+        }
+        if line.starts_with('$') {
+            match grbl_settings::parse_command(line) {
+                Ok(command) => self.settings_command = Some(command),
+                Err(e) => self.send(format_args!("error: {}\n\r", e)),
+            }
+            return true;
+        }
+        if let Some(args) = line.strip_prefix(">heartbeat") {
+            match parse_heartbeat(args) {
+                Ok(timeout) => {
+                    self.heartbeat_timeout = timeout;
+                    match timeout {
+                        Some(us) => {
+                            self.send(format_args!("heartbeat: {} s\n\r", us as f32 / 1_000_000.0))
+                        }
+                        None => self.send(format_args!("heartbeat: off\n\r")),
+                    }
+                }
+                Err(()) => self.send(format_args!(
+                    "invalid heartbeat command, expected >heartbeat <seconds> or >heartbeat off\n\r"
+                )),
+            }
+            return true;
+        }
+        if let Some(args) = line.strip_prefix(">telemetry") {
+            match TelemetryRequest::parse(args, sender) {
+                // the packets go out over udp, they need an address
+                Ok(TelemetryRequest::Subscribe { .. }) if sender == Peer::NONE => self.send(
+                    format_args!("error: telemetry is only sent over ethernet\n\r"),
+                ),
+                Ok(request) => self.telemetry_request = Some(request),
+                Err(()) => self.send(format_args!(
+                    "invalid telemetry command, expected >telemetry <rate_hz> or >telemetry off\n\r"
+                )),
+            }
+            return true;
+        }
+
+        if self.locked {
+            if !line.is_empty() {
+                self.send(format_args!("error: alarm, send $X to unlock\n\r"));
+            }
+            return true;
+        }
+
+        let mut full = false;
+        for code in gcode::parse(code_str) {
+            if self.gcode_buffer.push(code).is_err() {
+                full = true;
+                break;
+            }
+        }
+        if full {
+            self.send(format_args!("write failed, buffer full\n\r"));
+        }
+        false
+    }
+
+    /// moves the real-time command bytes out of `data` into `commands`,
+    /// returns the length of what's left. `None` if there was an abort, the
+    /// rest of the message goes with it
+    fn take_realtime_commands(
+        commands: &mut Vec<RealtimeCommand, U8>,
+        data: &mut [u8],
+    ) -> Option<usize> {
+        let mut len = 0;
+        let mut aborted = false;
+        for idx in 0..data.len() {
+            let byte = data[idx];
+            if let Some(command) = RealtimeCommand::from_byte(byte) {
+                aborted |= command == RealtimeCommand::Abort;
+                Self::queue_realtime_command(commands, command);
+            } else {
+                data[len] = byte;
+                len += 1;
+            }
+        }
+        if aborted {
+            None
+        } else {
+            Some(len)
+        }
+    }
+
+    /// adds `command` unless it's already waiting. a feed hold and a resume
+    /// cancel each other, only the later one stays. that way there is never
+    /// more than one of each, and an abort can't find the list full.
+    fn queue_realtime_command(commands: &mut Vec<RealtimeCommand, U8>, command: RealtimeCommand) {
+        let opposite = match command {
+            RealtimeCommand::FeedHold => Some(RealtimeCommand::Resume),
+            RealtimeCommand::Resume => Some(RealtimeCommand::FeedHold),
+            _ => None,
+        };
+        if let Some(idx) = commands.iter().position(|c| Some(*c) == opposite) {
+            commands[idx..].rotate_left(1);
+            commands.pop();
+        }
+        if !commands.contains(&command) {
+            let _ = commands.push(command);
+        }
+    }
+
+    /// drops the queued g-code and every line still waiting in a stream
+    fn abort(&mut self) {
+        truncate(&mut self.gcode_buffer, 0);
+        for channel in self.channels.iter_mut() {
+            channel.lines.clear();
+        }
+    }
+
+    /// queues every line of a data frame or none of them, so a frame that
+    /// doesn't fit can simply be sent again
+    fn queue_frame(
+        gcode_buffer: &mut Vec<gcode::GCode, U512>,
+        payload: &[u8],
+    ) -> Result<(), NackReason> {
+        let code_str = core::str::from_utf8(payload).map_err(|_| NackReason::Malformed)?;
+
+        let free = gcode_buffer.capacity() - gcode_buffer.len();
+        if gcode::parse(code_str).count() > free {
+            return Err(NackReason::BufferFull);
+        }
+
+        for code in gcode::parse(code_str) {
+            let _ = gcode_buffer.push(code);
+        }
+        Ok(())
+    }
+
+    /// acks `Ok(seq)`, nacks `Err((seq, reason))`
+    fn reply(&mut self, result: Result<u16, (u16, NackReason)>) {
+        let free = self.gcode_buffer_free() as u16;
+        let reply = match result {
+            Ok(seq) => Reply::Ack { seq, free },
+            Err((seq, reason)) => Reply::Nack { seq, free, reason },
+        };
+
+        let mut buf = [0u8; protocol::REPLY_LEN];
+        if let Some(channel) = self.channels.get_mut(self.current) {
+            let _ = channel.transport.send(reply.encode(&mut buf));
+        }
+    }
+}
+
+impl<T: CommandTransport, C: Clock> Host for CommandHandler<T, C> {
+    #[inline]
+    fn get_gcode_buffer(&self) -> &[gcode::GCode] {
+        &self.gcode_buffer
+    }
+
+    #[inline]
+    fn clear_gcode_buffer(&mut self) {
+        truncate(&mut self.gcode_buffer, 0);
+    }
+
+    #[inline]
+    fn consume_gcode(&mut self, count: usize) {
+        let count = count.min(self.gcode_buffer.len());
+        self.gcode_buffer.rotate_left(count);
+        let len = self.gcode_buffer.len() - count;
+        truncate(&mut self.gcode_buffer, len);
+    }
+
+    #[inline]
+    fn gcode_buffer_free(&self) -> usize {
+        self.gcode_buffer.capacity() - self.gcode_buffer.len()
+    }
+
+    #[inline]
+    fn realtime_commands(&self) -> &[RealtimeCommand] {
+        &self.realtime_commands
+    }
+
+    #[inline]
+    fn clear_realtime_commands(&mut self) {
+        truncate(&mut self.realtime_commands, 0);
+    }
+
+    #[inline]
+    fn take_telemetry_request(&mut self) -> Option<TelemetryRequest> {
+        self.telemetry_request.take()
+    }
+
+    #[inline]
+    fn take_settings_command(&mut self) -> Option<SettingsCommand> {
+        self.settings_command.take()
+    }
+
+    #[inline]
+    fn needs_calibration(&mut self) -> bool {
+        let calib_rqst = self.calibration_request;
+        self.calibration_request = false;
+        calib_rqst
+    }
+
+    #[inline]
+    fn take_unlock_request(&mut self) -> bool {
+        let unlock_rqst = self.unlock_request;
+        self.unlock_request = false;
+        unlock_rqst
+    }
+
+    /// plain g-code is answered with an error and data frames are nacked
+    #[inline]
+    fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    #[inline]
+    fn take_client_dropped(&mut self) -> bool {
+        let dropped = self.client_dropped;
+        self.client_dropped = false;
+        dropped
+    }
+
+    #[inline]
+    fn host_timed_out(&mut self) -> bool {
+        let timeout = match self.heartbeat_timeout {
+            Some(timeout) => timeout,
+            None => return false,
+        };
+        if self.host_lost || self.clock.now() - self.last_packet < timeout {
+            return false;
+        }
+
+        self.host_lost = true;
+        true
+    }
+
+    /// goes to the channel the last message came from
+    fn send(&mut self, args: Arguments) {
+        if let Some(channel) = self.channels.get_mut(self.current) {
+            let _ = transport::send_fmt(&mut channel.transport, args);
+        }
+    }
+
+    /// goes out over the first transport that can address `peer`
+    fn send_to(&mut self, data: &[u8], peer: Peer) {
+        for channel in self.channels.iter_mut() {
+            if channel.transport.send_to(data, peer).is_ok() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub trait Encoder {
+    fn pos(&self) -> i32;
+    fn dir(&self) -> bool;
+    fn calibrate(&self);
+}
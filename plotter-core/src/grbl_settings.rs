@@ -0,0 +1,582 @@
+//! grbl style `$` commands on the stored settings. `$$` lists them, `$n`
+//! shows one, `$n=value` changes one and `$RST` goes back to the defaults.
+//! the numbers follow grbl where there is an equivalent.
+
+use core::fmt;
+
+use crate::homing::HomingTrigger;
+use crate::settings::Settings;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingsCommand {
+    List,
+    Show(u16),
+    Set(u16, Value),
+    Reset,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Value {
+    Float(f32),
+    Int(u32),
+    Ip([u8; 4]),
+    Mac([u8; 6]),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Float(value) => write!(f, "{:.3}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Ip(ip) => write!(f, "{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]),
+            Value::Mac(mac) => write!(
+                f,
+                "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingError {
+    /// not a `$` command this firmware knows
+    Syntax,
+    Unknown(u16),
+    /// the value doesn't parse as what the setting holds
+    Invalid(u16, Kind),
+    OutOfRange(u16, Kind),
+    /// the value is fine by itself but doesn't go with the other settings
+    Conflict(&'static str),
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingError::Syntax => write!(f, "expected $$, $<n>, $<n>=<value> or $RST"),
+            SettingError::Unknown(id) => write!(f, "no setting ${}", id),
+            SettingError::Invalid(id, kind) => write!(f, "${} expects {}", id, kind),
+            SettingError::OutOfRange(id, kind) => write!(f, "${} has to be {}", id, kind),
+            SettingError::Conflict(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// what a setting holds, with the range numbers have to be in
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+    Float(f32, f32),
+    Int(u32, u32),
+    Ip,
+    Mac,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Float(min, max) => write!(f, "a number from {} to {}", min, max),
+            Kind::Int(min, max) => write!(f, "a whole number from {} to {}", min, max),
+            Kind::Ip => write!(f, "an address like 192.168.1.10"),
+            Kind::Mac => write!(f, "a mac address like 02:00:11:22:33:44, or 0"),
+        }
+    }
+}
+
+impl Kind {
+    fn parse(&self, text: &str) -> Option<Value> {
+        match self {
+            Kind::Float(..) => text.parse::<f32>().ok().map(Value::Float),
+            Kind::Int(..) => text.parse::<u32>().ok().map(Value::Int),
+            Kind::Ip => {
+                let mut ip = [0u8; 4];
+                parse_bytes(text, '.', 10, &mut ip)?;
+                Some(Value::Ip(ip))
+            }
+            // 0 is short for the all zero address, which means derived
+            Kind::Mac if text == "0" => Some(Value::Mac([0; 6])),
+            Kind::Mac => {
+                let mut mac = [0u8; 6];
+                parse_bytes(text, ':', 16, &mut mac)?;
+                Some(Value::Mac(mac))
+            }
+        }
+    }
+
+    fn contains(&self, value: Value) -> bool {
+        match (self, value) {
+            (Kind::Float(min, max), Value::Float(value)) => value >= *min && value <= *max,
+            (Kind::Int(min, max), Value::Int(value)) => value >= *min && value <= *max,
+            (Kind::Ip, Value::Ip(_)) => true,
+            // the lowest bit of the first byte marks multicast addresses
+            (Kind::Mac, Value::Mac(mac)) => mac[0] & 1 == 0,
+            _ => false,
+        }
+    }
+}
+
+/// exactly `out.len()` numbers separated by `separator`
+fn parse_bytes(text: &str, separator: char, radix: u32, out: &mut [u8]) -> Option<()> {
+    let mut parts = text.split(separator);
+    for byte in out.iter_mut() {
+        *byte = u8::from_str_radix(parts.next()?, radix).ok()?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(()),
+    }
+}
+
+pub struct SettingInfo {
+    pub id: u16,
+    pub description: &'static str,
+    pub kind: Kind,
+    get: fn(&Settings) -> Value,
+    set: fn(&mut Settings, Value),
+}
+
+impl SettingInfo {
+    #[inline]
+    pub fn get(&self, settings: &Settings) -> Value {
+        (self.get)(settings)
+    }
+}
+
+fn float(value: Value) -> f32 {
+    match value {
+        Value::Float(value) => value,
+        Value::Int(value) => value as f32,
+        _ => 0.0,
+    }
+}
+
+fn int(value: Value) -> u32 {
+    match value {
+        Value::Int(value) => value,
+        _ => 0,
+    }
+}
+
+/// 1 homes on the limit switch, 0 on a stall
+fn trigger(value: Value) -> HomingTrigger {
+    match value {
+        Value::Int(1) => HomingTrigger::LimitSwitch,
+        _ => HomingTrigger::Stall,
+    }
+}
+
+fn trigger_value(trigger: HomingTrigger) -> Value {
+    Value::Int((trigger == HomingTrigger::LimitSwitch) as u32)
+}
+
+const SPEED: Kind = Kind::Float(0.0, 1000.0);
+const PID_GAIN: Kind = Kind::Float(0.0, 100.0);
+const DUTY: Kind = Kind::Float(0.0, 100.0);
+const TRAVEL: Kind = Kind::Float(-1000.0, 1000.0);
+const DWELL: Kind = Kind::Int(0, 5000);
+const PWM_FREQ: Kind = Kind::Int(10, 20_000);
+const PORT: Kind = Kind::Int(1, 65535);
+const TRIGGER: Kind = Kind::Int(0, 1);
+const BACK_OFF: Kind = Kind::Float(0.5, 50.0);
+
+/// every setting, in the order `$$` lists them
+pub const SETTINGS: &[SettingInfo] = &[
+    SettingInfo {
+        id: 11,
+        description: "junction deviation, mm",
+        kind: Kind::Float(0.001, 1.0),
+        get: |s| Value::Float(s.junction_deviation),
+        set: |s, v| s.junction_deviation = float(v),
+    },
+    SettingInfo {
+        id: 30,
+        description: "pen up angle, deg",
+        kind: Kind::Int(0, 255),
+        get: |s| Value::Int(s.pen_up_angle as u32),
+        set: |s, v| s.pen_up_angle = int(v) as u8,
+    },
+    SettingInfo {
+        id: 31,
+        description: "pen up dwell, ms",
+        kind: DWELL,
+        get: |s| Value::Int(s.pen_up_dwell),
+        set: |s, v| s.pen_up_dwell = int(v),
+    },
+    SettingInfo {
+        id: 32,
+        description: "pen down dwell, ms",
+        kind: DWELL,
+        get: |s| Value::Int(s.pen_down_dwell),
+        set: |s, v| s.pen_down_dwell = int(v),
+    },
+    SettingInfo {
+        id: 100,
+        description: "x steps/mm",
+        kind: Kind::Float(1.0, 10_000.0),
+        get: |s| Value::Float(1.0 / s.unit_length.0),
+        set: |s, v| s.unit_length.0 = 1.0 / float(v),
+    },
+    SettingInfo {
+        id: 101,
+        description: "y steps/mm",
+        kind: Kind::Float(1.0, 10_000.0),
+        get: |s| Value::Float(1.0 / s.unit_length.1),
+        set: |s, v| s.unit_length.1 = 1.0 / float(v),
+    },
+    SettingInfo {
+        id: 110,
+        description: "max speed, mm/s",
+        kind: Kind::Float(1.0, 1000.0),
+        get: |s| Value::Float(s.max_speed),
+        set: |s, v| s.max_speed = float(v),
+    },
+    SettingInfo {
+        id: 111,
+        description: "min axis speed, mm/s",
+        kind: SPEED,
+        get: |s| Value::Float(s.min_speed),
+        set: |s, v| s.min_speed = float(v),
+    },
+    SettingInfo {
+        id: 120,
+        description: "acceleration, mm/s^2",
+        kind: Kind::Float(1.0, 100_000.0),
+        get: |s| Value::Float(s.acceleration),
+        set: |s, v| s.acceleration = float(v),
+    },
+    SettingInfo {
+        id: 121,
+        description: "jerk, mm/s^3",
+        kind: Kind::Float(1.0, 1_000_000.0),
+        get: |s| Value::Float(s.jerk),
+        set: |s, v| s.jerk = float(v),
+    },
+    SettingInfo {
+        id: 130,
+        description: "x soft limit min, mm, after homing",
+        kind: TRAVEL,
+        get: |s| Value::Float(s.soft_limits_min.0),
+        set: |s, v| s.soft_limits_min.0 = float(v),
+    },
+    SettingInfo {
+        id: 131,
+        description: "x soft limit max, mm, after homing",
+        kind: TRAVEL,
+        get: |s| Value::Float(s.soft_limits_max.0),
+        set: |s, v| s.soft_limits_max.0 = float(v),
+    },
+    SettingInfo {
+        id: 132,
+        description: "y soft limit min, mm, after homing",
+        kind: TRAVEL,
+        get: |s| Value::Float(s.soft_limits_min.1),
+        set: |s, v| s.soft_limits_min.1 = float(v),
+    },
+    SettingInfo {
+        id: 133,
+        description: "y soft limit max, mm, after homing",
+        kind: TRAVEL,
+        get: |s| Value::Float(s.soft_limits_max.1),
+        set: |s, v| s.soft_limits_max.1 = float(v),
+    },
+    SettingInfo {
+        id: 140,
+        description: "x home offset, mm, after homing",
+        kind: TRAVEL,
+        get: |s| Value::Float(s.home_offset.0),
+        set: |s, v| s.home_offset.0 = float(v),
+    },
+    SettingInfo {
+        id: 141,
+        description: "y home offset, mm, after homing",
+        kind: TRAVEL,
+        get: |s| Value::Float(s.home_offset.1),
+        set: |s, v| s.home_offset.1 = float(v),
+    },
+    SettingInfo {
+        id: 150,
+        description: "x homing trigger, 0 stall, 1 limit switch",
+        kind: TRIGGER,
+        get: |s| trigger_value(s.homing_trigger.0),
+        set: |s, v| s.homing_trigger.0 = trigger(v),
+    },
+    SettingInfo {
+        id: 151,
+        description: "x homing seek duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.homing_seek_duty.0),
+        set: |s, v| s.homing_seek_duty.0 = float(v),
+    },
+    SettingInfo {
+        id: 152,
+        description: "x homing locate duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.homing_locate_duty.0),
+        set: |s, v| s.homing_locate_duty.0 = float(v),
+    },
+    SettingInfo {
+        id: 153,
+        description: "x homing pull-off, mm",
+        kind: BACK_OFF,
+        get: |s| Value::Float(s.homing_back_off.0),
+        set: |s, v| s.homing_back_off.0 = float(v),
+    },
+    SettingInfo {
+        id: 160,
+        description: "y homing trigger, 0 stall, 1 limit switch",
+        kind: TRIGGER,
+        get: |s| trigger_value(s.homing_trigger.1),
+        set: |s, v| s.homing_trigger.1 = trigger(v),
+    },
+    SettingInfo {
+        id: 161,
+        description: "y homing seek duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.homing_seek_duty.1),
+        set: |s, v| s.homing_seek_duty.1 = float(v),
+    },
+    SettingInfo {
+        id: 162,
+        description: "y homing locate duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.homing_locate_duty.1),
+        set: |s, v| s.homing_locate_duty.1 = float(v),
+    },
+    SettingInfo {
+        id: 163,
+        description: "y homing pull-off, mm",
+        kind: BACK_OFF,
+        get: |s| Value::Float(s.homing_back_off.1),
+        set: |s, v| s.homing_back_off.1 = float(v),
+    },
+    SettingInfo {
+        id: 200,
+        description: "x velocity kp",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.x_pid.kp),
+        set: |s, v| s.x_pid.kp = float(v),
+    },
+    SettingInfo {
+        id: 201,
+        description: "x velocity ki",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.x_pid.ki),
+        set: |s, v| s.x_pid.ki = float(v),
+    },
+    SettingInfo {
+        id: 202,
+        description: "x velocity kd",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.x_pid.kd),
+        set: |s, v| s.x_pid.kd = float(v),
+    },
+    SettingInfo {
+        id: 203,
+        description: "x velocity feedforward, %/(mm/s)",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.x_pid.kff),
+        set: |s, v| s.x_pid.kff = float(v),
+    },
+    SettingInfo {
+        id: 204,
+        description: "x min duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.x_pid.min_duty),
+        set: |s, v| s.x_pid.min_duty = float(v),
+    },
+    SettingInfo {
+        id: 205,
+        description: "x max duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.x_pid.max_duty),
+        set: |s, v| s.x_pid.max_duty = float(v),
+    },
+    SettingInfo {
+        id: 210,
+        description: "y velocity kp",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.y_pid.kp),
+        set: |s, v| s.y_pid.kp = float(v),
+    },
+    SettingInfo {
+        id: 211,
+        description: "y velocity ki",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.y_pid.ki),
+        set: |s, v| s.y_pid.ki = float(v),
+    },
+    SettingInfo {
+        id: 212,
+        description: "y velocity kd",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.y_pid.kd),
+        set: |s, v| s.y_pid.kd = float(v),
+    },
+    SettingInfo {
+        id: 213,
+        description: "y velocity feedforward, %/(mm/s)",
+        kind: PID_GAIN,
+        get: |s| Value::Float(s.y_pid.kff),
+        set: |s, v| s.y_pid.kff = float(v),
+    },
+    SettingInfo {
+        id: 214,
+        description: "y min duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.y_pid.min_duty),
+        set: |s, v| s.y_pid.min_duty = float(v),
+    },
+    SettingInfo {
+        id: 215,
+        description: "y max duty, %",
+        kind: DUTY,
+        get: |s| Value::Float(s.y_pid.max_duty),
+        set: |s, v| s.y_pid.max_duty = float(v),
+    },
+    SettingInfo {
+        id: 220,
+        description: "x pwm frequency, Hz, after a reset",
+        kind: PWM_FREQ,
+        get: |s| Value::Int(s.pwm_freq.0),
+        set: |s, v| s.pwm_freq.0 = int(v),
+    },
+    SettingInfo {
+        id: 221,
+        description: "y pwm frequency, Hz, after a reset",
+        kind: PWM_FREQ,
+        get: |s| Value::Int(s.pwm_freq.1),
+        set: |s, v| s.pwm_freq.1 = int(v),
+    },
+    SettingInfo {
+        id: 300,
+        description: "static ip address, after a reset",
+        kind: Kind::Ip,
+        get: |s| Value::Ip(s.ip),
+        set: |s, v| {
+            if let Value::Ip(ip) = v {
+                s.ip = ip
+            }
+        },
+    },
+    SettingInfo {
+        id: 301,
+        description: "udp port, after a reset",
+        kind: PORT,
+        get: |s| Value::Int(s.port as u32),
+        set: |s, v| s.port = int(v) as u16,
+    },
+    SettingInfo {
+        id: 302,
+        description: "mac address, 0 for one from the device id, after a reset",
+        kind: Kind::Mac,
+        get: |s| Value::Mac(s.mac),
+        set: |s, v| {
+            if let Value::Mac(mac) = v {
+                s.mac = mac
+            }
+        },
+    },
+    SettingInfo {
+        id: 303,
+        description: "host ip address until a command arrives, after a reset",
+        kind: Kind::Ip,
+        get: |s| Value::Ip(s.remote_ip),
+        set: |s, v| {
+            if let Value::Ip(ip) = v {
+                s.remote_ip = ip
+            }
+        },
+    },
+    SettingInfo {
+        id: 304,
+        description: "host udp port, after a reset",
+        kind: PORT,
+        get: |s| Value::Int(s.remote_port as u32),
+        set: |s, v| s.remote_port = int(v) as u16,
+    },
+    SettingInfo {
+        id: 305,
+        description: "dhcp, 0 off 1 on, after a reset",
+        kind: Kind::Int(0, 1),
+        get: |s| Value::Int(s.dhcp as u32),
+        set: |s, v| s.dhcp = int(v) != 0,
+    },
+];
+
+pub fn info(id: u16) -> Result<&'static SettingInfo, SettingError> {
+    SETTINGS
+        .iter()
+        .find(|info| info.id == id)
+        .ok_or(SettingError::Unknown(id))
+}
+
+/// `line` is a whole line starting with `$`. `$X` isn't a settings command and
+/// has to be picked out before.
+pub fn parse_command(line: &str) -> Result<SettingsCommand, SettingError> {
+    let line = line.trim();
+    let body = match line.strip_prefix('$') {
+        Some(body) => body,
+        None => return Err(SettingError::Syntax),
+    };
+
+    match body {
+        "$" => return Ok(SettingsCommand::List),
+        "RST" | "rst" => return Ok(SettingsCommand::Reset),
+        _ => (),
+    }
+
+    let (id, value) = match body.find('=') {
+        Some(at) => (&body[..at], Some(&body[at + 1..])),
+        None => (body, None),
+    };
+    let id = id.trim().parse::<u16>().map_err(|_| SettingError::Syntax)?;
+    let info = info(id)?;
+
+    match value {
+        None => Ok(SettingsCommand::Show(id)),
+        Some(text) => {
+            let value = info
+                .kind
+                .parse(text.trim())
+                .ok_or(SettingError::Invalid(id, info.kind))?;
+            if !info.kind.contains(value) {
+                return Err(SettingError::OutOfRange(id, info.kind));
+            }
+            Ok(SettingsCommand::Set(id, value))
+        }
+    }
+}
+
+impl Settings {
+    /// sets a single value from a `SettingsCommand::Set`, the settings stay
+    /// untouched if the result doesn't make sense as a whole
+    pub fn set(&mut self, id: u16, value: Value) -> Result<(), SettingError> {
+        let info = info(id)?;
+        if !info.kind.contains(value) {
+            return Err(SettingError::OutOfRange(id, info.kind));
+        }
+
+        let mut settings = *self;
+        (info.set)(&mut settings, value);
+        settings.check()?;
+        *self = settings;
+        Ok(())
+    }
+
+    /// the rules that span more than one setting
+    pub fn check(&self) -> Result<(), SettingError> {
+        if self.soft_limits_min.0 >= self.soft_limits_max.0
+            || self.soft_limits_min.1 >= self.soft_limits_max.1
+        {
+            return Err(SettingError::Conflict("soft limit min has to be below max"));
+        }
+        if self.x_pid.min_duty > self.x_pid.max_duty || self.y_pid.min_duty > self.y_pid.max_duty {
+            return Err(SettingError::Conflict("min duty has to be below max duty"));
+        }
+        if self.min_speed > self.max_speed {
+            return Err(SettingError::Conflict(
+                "min axis speed has to be below max speed",
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! homing of a single axis. the axis is driven towards its end of travel until
+//! a limit switch closes or the carriage stalls against the frame, backs off
+//! and approaches again slowly, which gives a repeatable trigger point
+//! independent of the seek speed.
+//!
+//! `HomingCycle` only decides how to drive the motor, reading the encoder and
+//! switch and applying the duty cycle is up to the caller.
+
+use core::fmt;
+
+/// what tells the cycle it has reached the end of travel
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HomingTrigger {
+    /// a switch that closes at the end of travel
+    LimitSwitch,
+    /// the encoder stops counting while the motor is driven
+    Stall,
+}
+
+#[derive(Clone, Copy)]
+pub struct HomingConfig {
+    pub trigger: HomingTrigger,
+    /// -1.0 to home towards negative encoder counts, 1.0 towards positive
+    pub dir: f32,
+    /// duty cycle (%) while looking for the trigger
+    pub seek_duty: f32,
+    /// duty cycle (%) while backing off and approaching again
+    pub locate_duty: f32,
+    /// encoder units to back off after the first trigger
+    pub back_off: i32,
+    /// encoder units the axis may travel without finding the trigger
+    pub max_travel: i32,
+    /// us without encoder movement after which the axis counts as stalled
+    pub stall_time: u64,
+}
+
+/// encoder units the position may jitter by without counting as movement
+const STALL_WINDOW: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HomingError {
+    /// travelled `max_travel` without a trigger
+    NotFound,
+    /// the axis didn't move while backing off
+    Stuck,
+    /// the limit switch didn't open while backing off
+    SwitchStuck,
+}
+
+impl fmt::Display for HomingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HomingError::NotFound => write!(f, "end of travel not found"),
+            HomingError::Stuck => write!(f, "axis stuck"),
+            HomingError::SwitchStuck => write!(f, "limit switch stuck"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HomingStep {
+    /// signed duty cycle (%) to drive the motor with
+    Drive(f32),
+    /// found the trigger point, the encoder position it was found at
+    Done(i32),
+    Failed(HomingError),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Seek,
+    BackOff,
+    Locate,
+    Finished(HomingStep),
+}
+
+pub struct HomingCycle {
+    config: HomingConfig,
+    phase: Phase,
+    /// position the current phase started at
+    phase_start: i32,
+
+    /// last position that counted as movement and when it was reached
+    still_pos: i32,
+    still_since: u64,
+}
+
+impl HomingCycle {
+    pub fn new(config: HomingConfig, now: u64, pos: i32) -> Self {
+        Self {
+            config,
+            phase: Phase::Seek,
+            phase_start: pos,
+            still_pos: pos,
+            still_since: now,
+        }
+    }
+
+    /// `now` in us, `pos` the encoder count and `limit` whether the limit
+    /// switch is closed, ignored when homing on stalls
+    pub fn tick(&mut self, now: u64, pos: i32, limit: bool) -> HomingStep {
+        if (pos - self.still_pos).abs() > STALL_WINDOW {
+            self.still_pos = pos;
+            self.still_since = now;
+        }
+        let stalled = now - self.still_since >= self.config.stall_time;
+        let triggered = match self.config.trigger {
+            HomingTrigger::LimitSwitch => limit,
+            HomingTrigger::Stall => stalled,
+        };
+        let travelled = (pos - self.phase_start).abs();
+        let c = self.config;
+
+        match self.phase {
+            Phase::Seek => {
+                if triggered {
+                    self.next_phase(Phase::BackOff, now, pos);
+                } else if travelled > c.max_travel {
+                    self.phase = Phase::Finished(HomingStep::Failed(HomingError::NotFound));
+                } else if stalled {
+                    // a stall before the switch closed, something is in the way
+                    self.phase = Phase::Finished(HomingStep::Failed(HomingError::Stuck));
+                }
+            }
+            Phase::BackOff => {
+                let released = c.trigger == HomingTrigger::Stall || !limit;
+                if travelled >= c.back_off && released {
+                    self.next_phase(Phase::Locate, now, pos);
+                } else if travelled > c.back_off + c.max_travel / 10 {
+                    self.phase = Phase::Finished(HomingStep::Failed(HomingError::SwitchStuck));
+                } else if stalled {
+                    self.phase = Phase::Finished(HomingStep::Failed(HomingError::Stuck));
+                }
+            }
+            Phase::Locate => {
+                if triggered {
+                    self.phase = Phase::Finished(HomingStep::Done(pos));
+                } else if travelled > 2 * c.back_off + c.max_travel / 10 {
+                    self.phase = Phase::Finished(HomingStep::Failed(HomingError::NotFound));
+                } else if stalled {
+                    self.phase = Phase::Finished(HomingStep::Failed(HomingError::Stuck));
+                }
+            }
+            Phase::Finished(_) => (),
+        }
+
+        match self.phase {
+            Phase::Seek => HomingStep::Drive(c.dir * c.seek_duty),
+            Phase::BackOff => HomingStep::Drive(-c.dir * c.locate_duty),
+            Phase::Locate => HomingStep::Drive(c.dir * c.locate_duty),
+            Phase::Finished(step) => step,
+        }
+    }
+
+    fn next_phase(&mut self, phase: Phase, now: u64, pos: i32) {
+        self.phase = phase;
+        self.phase_start = pos;
+        self.still_pos = pos;
+        self.still_since = now;
+    }
+}
@@ -0,0 +1,300 @@
+use core::f32::consts::{FRAC_PI_2, TAU};
+use micromath::F32Ext;
+
+use crate::math::sqrt;
+
+use core::marker::Copy;
+#[derive(Clone, Copy)]
+pub struct Interpolator {
+    start: (f32, f32),
+    end: (f32, f32),
+    interpolation_len: f32,
+
+    diff_1: (f32, f32),
+
+    radius: f32,
+    start_angle: f32,
+    central_angle: f32,
+    circle_origin: (f32, f32),
+
+    /// y encoder units per x encoder unit of travel, circles are
+    /// interpolated in x units and stretched back by this on the y axis
+    axis_ratio: f32,
+
+    method: Interpolation,
+}
+
+#[derive(Clone, Copy)]
+pub enum Interpolation {
+    Linear,
+    Circular(f32, f32, CircularInterpolationDir),
+    NoInterpolation,
+}
+
+#[derive(Clone, Copy)]
+pub enum CircularInterpolationDir {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Interpolator {
+    pub fn new(start: (i32, i32), end: (i32, i32), method: Interpolation, axis_ratio: f32) -> Self {
+        match method {
+            Interpolation::Linear => Self::setup_linear_interpolation(start, end, method),
+            Interpolation::Circular(_, _, _) => {
+                Self::setup_circular_interpolaion(start, end, method, axis_ratio)
+            }
+            Interpolation::NoInterpolation => Self::setup_no_interpolation(start, end),
+        }
+    }
+
+    #[inline]
+    pub fn get_interpolation_len(&self) -> u32 {
+        self.interpolation_len as u32
+    }
+
+    #[inline]
+    pub fn get_interpolation_at(&self, idx: u32) -> (i32, i32) {
+        match self.method {
+            Interpolation::Linear => self.calc_interpolation_linear(idx),
+            Interpolation::Circular(_, _, dir) => self.calc_interpolation_circular(idx, dir),
+            Interpolation::NoInterpolation => self.calc_interpolation_none(),
+        }
+    }
+
+    /// length of the path in mm
+    pub fn path_len(&self, unit_length_x: f32, unit_length_y: f32) -> f32 {
+        match self.method {
+            Interpolation::Linear | Interpolation::NoInterpolation => {
+                let dx = (self.end.0 - self.start.0) * unit_length_x;
+                let dy = (self.end.1 - self.start.1) * unit_length_y;
+                sqrt(dx * dx + dy * dy)
+            }
+            // radius is in x units, see `setup_circular_interpolaion`
+            Interpolation::Circular(_, _, _) => self.radius * self.central_angle * unit_length_x,
+        }
+    }
+
+    /// direction of travel at `idx` in encoder units, not normalized
+    #[inline]
+    pub fn tangent_at(&self, idx: u32) -> (f32, f32) {
+        match self.method {
+            Interpolation::Linear | Interpolation::NoInterpolation => {
+                (self.end.0 - self.start.0, self.end.1 - self.start.1)
+            }
+            Interpolation::Circular(_, _, dir) => {
+                let angle = self.circular_angle_at(idx, dir);
+                let (sin, cos) = (angle.sin(), angle.cos());
+                match dir {
+                    CircularInterpolationDir::Clockwise => (sin, -cos * self.axis_ratio),
+                    CircularInterpolationDir::CounterClockwise => (-sin, cos * self.axis_ratio),
+                }
+            }
+        }
+    }
+
+    /// smallest box (encoder units) holding the whole path as `(min, max)`
+    pub fn bounds(&self) -> ((i32, i32), (i32, i32)) {
+        let (start, end) = (
+            (self.start.0 as i32, self.start.1 as i32),
+            (self.end.0 as i32, self.end.1 as i32),
+        );
+        let mut min = (start.0.min(end.0), start.1.min(end.1));
+        let mut max = (start.0.max(end.0), start.1.max(end.1));
+
+        if let Interpolation::Circular(_, _, dir) = self.method {
+            // an arc bulges out furthest where it crosses one of the circle's axes
+            for quadrant in 0..4 {
+                let angle = quadrant as f32 * FRAC_PI_2;
+                let mut offset = match dir {
+                    CircularInterpolationDir::Clockwise => self.start_angle - angle,
+                    CircularInterpolationDir::CounterClockwise => angle - self.start_angle,
+                };
+                while offset < 0.0 {
+                    offset += TAU;
+                }
+                while offset >= TAU {
+                    offset -= TAU;
+                }
+                if offset > self.central_angle {
+                    continue;
+                }
+
+                let x = self.circle_origin.0 + (self.radius * angle.cos());
+                let y = (self.circle_origin.1 + (self.radius * angle.sin())) * self.axis_ratio;
+                let (x, y) = (x.round() as i32, y.round() as i32);
+                min = (min.0.min(x), min.1.min(y));
+                max = (max.0.max(x), max.1.max(y));
+            }
+        }
+
+        (min, max)
+    }
+
+    #[inline]
+    fn circular_angle_at(&self, idx: u32, dir: CircularInterpolationDir) -> f32 {
+        let fraction = if self.interpolation_len == 0.0 {
+            0.0
+        } else {
+            idx as f32 / self.interpolation_len
+        };
+        let offset_angle = fraction * self.central_angle;
+
+        match dir {
+            CircularInterpolationDir::Clockwise => self.start_angle - offset_angle,
+            CircularInterpolationDir::CounterClockwise => self.start_angle + offset_angle,
+        }
+    }
+
+    #[inline]
+    fn calc_interpolation_none(&self) -> (i32, i32) {
+        (self.end.0 as i32, self.end.1 as i32)
+    }
+
+    #[inline]
+    fn calc_interpolation_linear(&self, idx: u32) -> (i32, i32) {
+        if self.interpolation_len == 0.0 {
+            return (self.end.0 as i32, self.end.1 as i32);
+        }
+
+        let fraction = (idx as f32) / self.interpolation_len;
+
+        let x = self.start.0 + (self.diff_1.0 * fraction);
+        let y = self.start.1 + (self.diff_1.1 * fraction);
+
+        (x.floor() as i32, y.floor() as i32)
+    }
+
+    #[inline]
+    fn calc_interpolation_circular(&self, idx: u32, dir: CircularInterpolationDir) -> (i32, i32) {
+        if self.interpolation_len == 0.0 {
+            return (self.end.0 as i32, self.end.1 as i32);
+        }
+
+        let angle = self.circular_angle_at(idx, dir);
+
+        let x = self.circle_origin.0 + (self.radius * angle.cos());
+        let y = (self.circle_origin.1 + (self.radius * angle.sin())) * self.axis_ratio;
+
+        (x.round() as i32, y.round() as i32)
+    }
+
+    fn setup_linear_interpolation(
+        start: (i32, i32),
+        end: (i32, i32),
+        method: Interpolation,
+    ) -> Self {
+        let interpolation_len = if (start.0 - end.0).abs() > ((start.1 - end.1).abs()) {
+            (start.0 - end.0).abs() as f32
+        } else {
+            (start.1 - end.1).abs() as f32
+        };
+
+        let start = (start.0 as f32, start.1 as f32);
+        let end = (end.0 as f32, end.1 as f32);
+
+        let x_diff = end.0 - start.0;
+        let y_diff = end.1 - start.1;
+
+        let diff_1 = (x_diff, y_diff);
+
+        Self {
+            start,
+            end,
+            interpolation_len,
+            diff_1,
+            method,
+
+            start_angle: 0.0,
+            central_angle: 0.0,
+            circle_origin: (0.0, 0.0),
+            radius: 0.0,
+            axis_ratio: 1.0,
+        }
+    }
+
+    fn setup_circular_interpolaion(
+        start: (i32, i32),
+        end: (i32, i32),
+        method: Interpolation,
+        axis_ratio: f32,
+    ) -> Self {
+        let (i, j, dir) = match method {
+            Interpolation::Circular(i, j, dir) => (i, j, dir),
+            _ => panic!("Called circular interpolation setup without circular interpolation enum."),
+        };
+
+        let start = (start.0 as f32, start.1 as f32);
+        let end = (end.0 as f32, end.1 as f32);
+
+        // the encoders don't share a unit length, so a circle in mm is an ellipse
+        // in encoder units. everything below works with y scaled down to x units.
+        let start_scaled = (start.0, start.1 / axis_ratio);
+        let end_scaled = (end.0, end.1 / axis_ratio);
+
+        let circle_origin = (start_scaled.0 + i, start_scaled.1 + j / axis_ratio);
+
+        let start_diff = (
+            start_scaled.0 - circle_origin.0,
+            start_scaled.1 - circle_origin.1,
+        );
+        let end_diff = (
+            end_scaled.0 - circle_origin.0,
+            end_scaled.1 - circle_origin.1,
+        );
+
+        //FIXME: calculate radius without involving squares as this might overflow
+        let radius = sqrt((start_diff.0 * start_diff.0) + (start_diff.1 * start_diff.1));
+
+        let start_angle = start_diff.1.atan2(start_diff.0);
+        let end_angle = end_diff.1.atan2(end_diff.0);
+
+        // angle swept going from start to end in the requested direction,
+        // an arc ending where it started is a full circle
+        let mut central_angle = match dir {
+            CircularInterpolationDir::Clockwise => start_angle - end_angle,
+            CircularInterpolationDir::CounterClockwise => end_angle - start_angle,
+        };
+        if central_angle <= 0.0 {
+            central_angle += TAU;
+        }
+
+        // one interpolation step per encoder unit on the finer of the two axes
+        let arc_len = radius * central_angle * axis_ratio.max(1.0);
+        let interpolation_len = arc_len.ceil();
+
+        Self {
+            start,
+            end,
+            interpolation_len,
+            diff_1: start_diff,
+            radius,
+            start_angle,
+            central_angle,
+            circle_origin,
+            axis_ratio,
+            method,
+        }
+    }
+
+    fn setup_no_interpolation(start: (i32, i32), end: (i32, i32)) -> Self {
+        let start = (start.0 as f32, start.1 as f32);
+        let end = (end.0 as f32, end.1 as f32);
+        Self {
+            start,
+            end,
+            interpolation_len: 1.0,
+            diff_1: (0.0, 0.0),
+            radius: 0.0,
+            start_angle: 0.0,
+            central_angle: 0.0,
+            circle_origin: (0.0, 0.0),
+            axis_ratio: 1.0,
+            method: Interpolation::NoInterpolation,
+        }
+    }
+
+    pub fn interpolation_method(&self) -> Interpolation {
+        self.method
+    }
+}
@@ -0,0 +1,42 @@
+//! motion planning, interpolation and control logic of the plotter. nothing
+//! in here touches a peripheral or a global clock, so it builds and is tested
+//! on the host as well as on the mcu.
+
+#![no_std]
+// `Result<_, ()>` errors and argument heavy constructors are how the firmware
+// has always been written
+#![allow(
+    clippy::result_unit_err,
+    clippy::new_without_default,
+    clippy::too_many_arguments
+)]
+// a test build links std, whose float methods shadow micromath's
+#![cfg_attr(test, allow(unused_imports))]
+
+pub mod buf_writer;
+pub mod clock;
+pub mod com;
+pub mod encoder;
+pub mod grbl_settings;
+pub mod homing;
+pub mod interpolator;
+pub mod math;
+pub mod motion_controller;
+pub mod motor_pwm;
+pub mod pen;
+pub mod planner;
+pub mod plant_sim;
+pub mod protocol;
+pub mod pwm_duty;
+pub mod ring_buffer;
+pub mod s_curve;
+pub mod sequence;
+pub mod sequence_wrapper;
+pub mod settings;
+pub mod soft_limits;
+pub mod speed_calc;
+pub mod speed_profile;
+pub mod switches;
+pub mod telemetry;
+pub mod transport;
+pub mod velocity_controller;
@@ -0,0 +1,16 @@
+//! float helpers that need more precision than micromath gives
+
+use micromath::F32Ext;
+
+/// micromath's sqrt is only good to a few percent, enough to draw arcs with the
+/// wrong radius or plan speeds the acceleration limit can't reach. two newton
+/// steps on top of it bring it to full precision.
+#[inline]
+pub fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut root = x.sqrt();
+    root = 0.5 * (root + x / root);
+    0.5 * (root + x / root)
+}
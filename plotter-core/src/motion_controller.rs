@@ -0,0 +1,1176 @@
+//! the plotter's main state machine: runs the queued g-code through the
+//! planner and interpolator, closes the velocity loops of both axes around
+//! the encoders and handles feed holds, homing, alarms and settings. the
+//! hardware is behind the `PwmPin`, `Encoder`, `Pen`, `Switches` and
+//! `SettingsStorage` traits and the command handler behind `Host`, the
+//! firmware passes its peripherals, the tests `plant_sim`.
+
+use core::fmt;
+
+use crate::clock::Clock;
+use crate::com::{Host, RealtimeCommand, TelemetryRequest};
+use crate::encoder::Encoder;
+use crate::grbl_settings::{self, SettingsCommand};
+use crate::homing::{HomingConfig, HomingCycle, HomingStep};
+use crate::interpolator::CircularInterpolationDir;
+use crate::math::sqrt;
+use crate::motor_pwm::{MotorPwm, PwmPin};
+use crate::pen::{Pen, PenPosition};
+use crate::s_curve::SCurveProfile;
+use crate::sequence::SequenceVector;
+use crate::sequence_wrapper::{PositioningMode, SequenceWrapper, Units};
+use crate::settings::{Settings, SettingsStorage, DEFAULTS};
+use crate::soft_limits::SoftLimits;
+use crate::speed_calc::PulseContedSpeedCalc;
+use crate::speed_profile::{MotionProfile, ProfileKind, SpeedProfile};
+use crate::switches::Switches;
+use crate::telemetry::{self, AxisSample, Telemetry};
+use crate::velocity_controller::VelocityController;
+
+/// us between two updates of the velocity loops
+const CORRECTION_INTERVAL: u64 = 10_000;
+
+/// encoder units x may travel without finding its trigger, ~420 mm
+const X_HOMING_TRAVEL: i32 = 10_000;
+/// y has the finer encoder, ~315 mm
+const Y_HOMING_TRAVEL: i32 = 30_000;
+/// us without encoder movement after which a homing axis counts as stalled
+const HOMING_STALL_TIME: u64 = 100_000;
+
+/// mm an axis may overshoot the soft limits by before motion is halted
+const SOFT_LIMIT_TOLERANCE: f32 = 1.0;
+
+/// encoder units an axis that isn't meant to move may be off by, it coasts
+/// and has backlash, so it hardly ever stops right on its point
+const HOLD_WINDOW: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MachineState {
+    /// nothing left to run
+    Idle,
+    /// executing a segment
+    Run,
+    /// stopped or stopping on a feed hold, waiting for resume
+    Hold,
+    /// stopped because of a fault, needs to be unlocked
+    Alarm,
+    /// looking for the machine origin
+    Homing,
+}
+
+impl fmt::Display for MachineState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MachineState::Idle => write!(f, "Idle"),
+            MachineState::Run => write!(f, "Run"),
+            MachineState::Hold => write!(f, "Hold"),
+            MachineState::Alarm => write!(f, "Alarm"),
+            MachineState::Homing => write!(f, "Homing"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Axis {
+    X,
+    Y,
+}
+
+impl fmt::Display for Axis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Axis::X => write!(f, "x"),
+            Axis::Y => write!(f, "y"),
+        }
+    }
+}
+
+/// x moves right and y up on the positive side of their motors and encoders
+pub struct MotionController<
+    PX: PwmPin,
+    PY: PwmPin,
+    EX: Encoder,
+    EY: Encoder,
+    P: Pen,
+    L: Switches,
+    S: SettingsStorage,
+    C: Clock + Copy,
+> {
+    x_motor: MotorPwm<PX>,
+    y_motor: MotorPwm<PY>,
+
+    x_opto: PulseContedSpeedCalc<EX, C>,
+    y_opto: PulseContedSpeedCalc<EY, C>,
+
+    pen: P,
+    switches: L,
+    clock: C,
+
+    sequence: SequenceWrapper,
+    /// time (us) motion may go on after the pen moved
+    dwell_until: Option<u64>,
+
+    int_idx: u32,
+    /// velocity profile of the segment being executed
+    profile: MotionProfile,
+    /// kind of profile the next segments are planned with
+    profile_kind: ProfileKind,
+    /// distance (mm) into the segment at which `profile` starts, not 0 after a
+    /// resume
+    profile_offset: f32,
+
+    state: MachineState,
+    /// interpolated point each axis is currently heading for
+    target: (i32, i32),
+    telemetry: Telemetry,
+    /// time (us) and path speed (mm/s) a feed hold started decelerating from,
+    /// `None` once the tool has stopped
+    hold_decel: Option<(u64, f32)>,
+    /// axis being homed and its cycle, x is homed before y
+    homing: Option<(Axis, HomingCycle)>,
+    settings: Settings,
+    settings_store: S,
+    /// applied to the sequence once the machine is homed
+    soft_limits: SoftLimits,
+
+    x_pwm: f32,
+    y_pwm: f32,
+
+    x_velocity: VelocityController,
+    y_velocity: VelocityController,
+
+    last_correction_time_x: u64,
+    last_correction_time_y: u64,
+}
+
+impl<PX, PY, EX, EY, P, L, S, C> MotionController<PX, PY, EX, EY, P, L, S, C>
+where
+    PX: PwmPin,
+    PY: PwmPin,
+    EX: Encoder,
+    EY: Encoder,
+    P: Pen,
+    L: Switches,
+    S: SettingsStorage,
+    C: Clock + Copy,
+{
+    pub fn new(
+        x_motor: MotorPwm<PX>,
+        y_motor: MotorPwm<PY>,
+        encoder_x: EX,
+        encoder_y: EY,
+        pen: P,
+        switches: L,
+        settings: Settings,
+        settings_store: S,
+        clock: C,
+    ) -> Self {
+        let mut controller = Self {
+            x_motor,
+            y_motor,
+            x_opto: PulseContedSpeedCalc::new(encoder_x, clock),
+            y_opto: PulseContedSpeedCalc::new(encoder_y, clock),
+            pen,
+            switches,
+            clock,
+            sequence: SequenceWrapper::new(),
+            dwell_until: None,
+            int_idx: 1,
+            profile: MotionProfile::standstill(),
+            profile_kind: ProfileKind::Trapezoidal,
+            profile_offset: 0.0,
+
+            state: MachineState::Idle,
+            target: (0, 0),
+            telemetry: Telemetry::new(),
+            hold_decel: None,
+            homing: None,
+            settings,
+            settings_store,
+            soft_limits: SoftLimits::new((0, 0), (0, 0)),
+
+            x_pwm: 0.0,
+            y_pwm: 0.0,
+
+            x_velocity: VelocityController::new(settings.x_pid),
+            y_velocity: VelocityController::new(settings.y_pid),
+
+            last_correction_time_x: clock.now(),
+            last_correction_time_y: clock.now(),
+        };
+        controller.apply_settings();
+        controller
+    }
+
+    #[inline]
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// stores `settings` in flash and applies them once they're saved, the
+    /// old ones stay if saving fails. only from idle, a flash erase stalls the
+    /// main loop for seconds. the pwm frequencies and the network settings
+    /// take effect on the next boot.
+    pub fn update_settings<H: Host>(&mut self, settings: Settings, cmd: &mut H) -> Result<(), ()> {
+        let finished = self.sequence.sequence.sequence_len() < 2;
+        if self.state != MachineState::Idle || !finished {
+            cmd.send(format_args!(
+                "error: can't change settings while {}\n\r",
+                self.state
+            ));
+            return Err(());
+        }
+
+        if self.settings_store.save(&settings).is_err() {
+            cmd.send(format_args!("error: saving settings failed\n\r"));
+            return Err(());
+        }
+
+        let units_changed = settings.unit_length != self.settings.unit_length;
+        self.settings = settings;
+        self.apply_settings();
+        if units_changed && self.sequence.sequence.soft_limits().is_some() {
+            // the encoder positions no longer mean what they did when homed
+            self.sequence.sequence.set_soft_limits(None);
+            cmd.send(format_args!("soft limits off until homed\n\r"));
+        }
+        Ok(())
+    }
+
+    /// everything in `settings` that can change without a reboot
+    fn apply_settings(&mut self) {
+        let settings = self.settings;
+        let (unit_length_x, unit_length_y) = settings.unit_length;
+        self.sequence.set_unit_lengths(unit_length_x, unit_length_y);
+        self.sequence.set_max_feedrate(settings.max_speed * 60.0);
+        self.sequence
+            .sequence
+            .set_planner_limits(settings.acceleration, settings.junction_deviation);
+        if self.profile_kind == ProfileKind::SCurve {
+            self.sequence.sequence.set_jerk(settings.jerk);
+        }
+        self.x_velocity.set_config(settings.x_pid);
+        self.y_velocity.set_config(settings.y_pid);
+        self.pen.set_up_angle(settings.pen_up_angle);
+        self.soft_limits = SoftLimits::from_mm(
+            settings.soft_limits_min,
+            settings.soft_limits_max,
+            settings.unit_length,
+        );
+    }
+
+    #[inline]
+    pub fn start_sequence(&mut self) {
+        self.sequence.start();
+    }
+
+    #[inline]
+    pub fn stop_sequence(&mut self) {
+        self.sequence.stop();
+    }
+
+    #[inline]
+    fn interpret_gcode<H: Host>(&mut self, code: &gcode::GCode, cmd: &mut H) {
+        match code.mnemonic() {
+            gcode::Mnemonic::General => (),
+            gcode::Mnemonic::Miscellaneous => return self.interpret_mcode(code, cmd),
+            _ => return,
+        }
+
+        if let Some(feedrate) = code.value_for('F') {
+            self.sequence.set_feedrate(feedrate);
+        }
+
+        let queued = match code.major_number() {
+            0 => {
+                //G00 rapid move
+                if let Some(pen_pos) = code.value_for('Z') {
+                    self.sequence.pen_pos(pen_pos)
+                }
+                match (code.value_for('X'), code.value_for('Y')) {
+                    (Some(x), Some(y)) => self.sequence.pos_rapid(x, y),
+                    (Some(x), None) => self.sequence.pos_x_rapid(x),
+                    (None, Some(y)) => self.sequence.pos_y_rapid(y),
+                    (None, None) => Ok(()),
+                }
+            }
+            1 => {
+                //G01 linear interpolation
+                match (code.value_for('X'), code.value_for('Y')) {
+                    (Some(x), Some(y)) => self.sequence.pos(x, y),
+                    (Some(x), None) => self.sequence.pos_x(x),
+                    (None, Some(y)) => self.sequence.pos_y(y),
+                    (None, None) => Ok(()),
+                }
+            }
+            2 | 3 => {
+                //G02/G03 circular interpolation
+                let dir = if code.major_number() == 2 {
+                    CircularInterpolationDir::Clockwise
+                } else {
+                    CircularInterpolationDir::CounterClockwise
+                };
+
+                let (same_x, same_y) = self.sequence.unchanged_pos();
+                let x = code.value_for('X').unwrap_or(same_x);
+                let y = code.value_for('Y').unwrap_or(same_y);
+
+                if let Some(r) = code.value_for('R') {
+                    self.sequence.arc_radius(x, y, r, dir)
+                } else {
+                    let i = code.value_for('I').unwrap_or(0.0);
+                    let j = code.value_for('J').unwrap_or(0.0);
+                    self.sequence.arc_relative_center(x, y, i, j, dir)
+                }
+            }
+            20 => {
+                self.set_units(Units::Inches, cmd);
+                Ok(())
+            }
+            21 => {
+                self.set_units(Units::Millimetres, cmd);
+                Ok(())
+            }
+            90 => {
+                self.sequence.set_positioning(PositioningMode::Absolute);
+                Ok(())
+            }
+            91 => {
+                self.sequence.set_positioning(PositioningMode::Relative);
+                Ok(())
+            }
+            92 => {
+                //G92 set position without moving
+                self.sequence
+                    .set_pos(code.value_for('X'), code.value_for('Y'));
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(error) = queued {
+            cmd.send(format_args!("error: {}, move dropped\n\r", error));
+        }
+    }
+
+    #[inline]
+    fn interpret_mcode<H: Host>(&mut self, code: &gcode::GCode, cmd: &mut H) {
+        if code.major_number() == 710 {
+            //M710 S0 trapezoidal, M710 S1 jerk limited velocity profiles
+            if let Some(s) = code.value_for('S') {
+                let kind = if s == 0.0 {
+                    ProfileKind::Trapezoidal
+                } else {
+                    ProfileKind::SCurve
+                };
+                self.set_profile_kind(kind, cmd);
+            }
+        }
+    }
+
+    /// moves the pen and, if it actually moves, holds motion for the dwell
+    /// time of that direction
+    fn move_pen(&mut self, pen_pos: PenPosition) {
+        let moved = self.pen.pos() != pen_pos;
+        self.pen.move_pen(pen_pos);
+        if !moved {
+            return;
+        }
+
+        let dwell = match pen_pos {
+            PenPosition::Default => self.settings.pen_up_dwell,
+            PenPosition::Angle(_) => self.settings.pen_down_dwell,
+        };
+        if dwell > 0 {
+            self.dwell_until = Some(self.clock.now() + dwell as u64 * 1000);
+        }
+    }
+
+    /// takes effect from the next segment on
+    pub fn set_profile_kind<H: Host>(&mut self, kind: ProfileKind, cmd: &mut H) {
+        if self.profile_kind == kind {
+            return;
+        }
+
+        self.profile_kind = kind;
+        let jerk = match kind {
+            ProfileKind::Trapezoidal => 0.0,
+            ProfileKind::SCurve => self.settings.jerk,
+        };
+        self.sequence.sequence.set_jerk(jerk);
+        cmd.send(format_args!("profile: {}\n\r", kind));
+    }
+
+    fn set_units<H: Host>(&mut self, units: Units, cmd: &mut H) {
+        if self.sequence.units() != units {
+            self.sequence.set_units(units);
+            cmd.send(format_args!("units: {}\n\r", units));
+        }
+    }
+
+    #[inline]
+    pub fn tick<H: Host>(&mut self, cmd: &mut H) {
+        self.x_opto.tick(self.x_pwm);
+        self.y_opto.tick(self.y_pwm);
+
+        if self.switches.take_estop_triggered() {
+            self.emergency_stop(cmd);
+        }
+
+        for idx in 0..cmd.realtime_commands().len() {
+            let command = cmd.realtime_commands()[idx];
+            self.realtime_command(command, cmd);
+        }
+        cmd.clear_realtime_commands();
+
+        if cmd.take_unlock_request() {
+            self.unlock(cmd);
+        }
+
+        // the rest of the job may never arrive, stop where it can be resumed
+        if self.state == MachineState::Run && cmd.host_timed_out() {
+            cmd.send(format_args!("error: host lost, feed hold\n\r"));
+            self.feed_hold();
+        }
+        if cmd.take_client_dropped() && self.state == MachineState::Run {
+            cmd.send(format_args!("error: client disconnected, feed hold\n\r"));
+            self.feed_hold();
+        }
+
+        if let Some(request) = cmd.take_telemetry_request() {
+            self.telemetry_request(request, cmd);
+        }
+        if let Some(command) = cmd.take_settings_command() {
+            self.settings_command(command, cmd);
+        }
+        self.sample_telemetry(cmd);
+
+        if self.state != MachineState::Homing {
+            self.check_soft_limits(cmd);
+        }
+
+        if cmd.needs_calibration() {
+            self.start_homing(cmd);
+        }
+        cmd.set_locked(self.state == MachineState::Alarm);
+        if self.state == MachineState::Homing {
+            // queued lines wait until the machine is homed
+            self.homing_tick(cmd);
+            return;
+        }
+        if self.state == MachineState::Alarm {
+            // anything that got in before the lock is dropped
+            if !cmd.get_gcode_buffer().is_empty() {
+                cmd.send(format_args!("error: alarm, send $X to unlock\n\r"));
+                cmd.clear_gcode_buffer();
+            }
+            return;
+        }
+
+        if self.sequence.sequence.has_free_space() {
+            let mut consumed = 0;
+            for idx in 0..cmd.get_gcode_buffer().len() {
+                let code = cmd.get_gcode_buffer()[idx].clone();
+                if code.mnemonic() == gcode::Mnemonic::General && code.major_number() == 28 {
+                    // G28 drops the queue, so everything before it has to run first
+                    let finished = self.sequence.sequence.sequence_len() < 2;
+                    if self.state == MachineState::Idle && finished {
+                        self.start_homing(cmd);
+                        consumed += 1;
+                    }
+                    break;
+                }
+
+                self.interpret_gcode(&code, cmd);
+                if !self.sequence.is_running() {
+                    self.start_sequence();
+                }
+                consumed += 1;
+            }
+            cmd.consume_gcode(consumed);
+        }
+
+        if let Some(until) = self.dwell_until {
+            if self.clock.now() < until {
+                // the pen is still settling, the segment starts from rest anyway
+                self.x_stop();
+                self.y_stop();
+                self.x_velocity.reset();
+                self.y_velocity.reset();
+                return;
+            }
+            self.dwell_until = None;
+        }
+
+        let held = self.state == MachineState::Hold && self.hold_decel.is_none();
+
+        if !self.sequence.is_running() || held {
+            self.x_stop();
+            self.y_stop();
+            self.x_velocity.reset();
+            self.y_velocity.reset();
+            self.profile = MotionProfile::standstill();
+            return;
+        }
+
+        let sqv = self.sequence.curr_pos();
+        let interpolator = sqv.interpolator;
+
+        if self.int_idx > interpolator.get_interpolation_len() {
+            // the finished segment stays current until the next one streams in
+            if self.sequence.advance().is_none() {
+                self.x_stop();
+                self.y_stop();
+                self.x_velocity.reset();
+                self.y_velocity.reset();
+                self.profile = MotionProfile::standstill();
+                if self.state == MachineState::Run {
+                    self.state = MachineState::Idle;
+                }
+                // ran out of segments before a feed hold finished decelerating
+                if self.hold_decel.take().is_some() {
+                    self.pen.move_up();
+                }
+                return;
+            }
+            let pen_pos = self.sequence.curr_pos().pen();
+            self.move_pen(pen_pos);
+            self.int_idx = 1;
+            self.plan_segment();
+            if self.state == MachineState::Idle {
+                self.state = MachineState::Run;
+            }
+            return;
+        }
+
+        if let Some(speed) = self.hold_speed() {
+            if speed <= 0.0 {
+                // feed hold came to a stop
+                self.hold_decel = None;
+                self.x_stop();
+                self.y_stop();
+                self.pen.move_up();
+                return;
+            }
+        }
+
+        // follow the interpolated path point by point, the previous point tells
+        // which way the path is heading on each axis
+        let (tx, ty) = interpolator.get_interpolation_at(self.int_idx);
+        self.target = (tx, ty);
+        let (px, py) = if self.int_idx <= 1 {
+            sqv.start()
+        } else {
+            interpolator.get_interpolation_at(self.int_idx - 1)
+        };
+        let (cx, cy) = self.curr_pos();
+
+        let dir_x = Self::step_dir(px, tx, cx);
+        let dir_y = Self::step_dir(py, ty, cy);
+
+        let x_reached = (dir_x > 0.0 && cx >= tx) || (dir_x < 0.0 && cx <= tx) || dir_x == 0.0;
+        let y_reached = (dir_y > 0.0 && cy >= ty) || (dir_y < 0.0 && cy <= ty) || dir_y == 0.0;
+
+        let (speed_x, speed_y) = self.axis_speeds(&sqv);
+
+        match (x_reached, y_reached) {
+            (true, true) => {
+                self.int_idx += 1;
+            }
+            (true, false) => {
+                self.x_stop();
+                self.drive_y(dir_y, speed_y);
+            }
+            (false, true) => {
+                self.y_stop();
+                self.drive_x(dir_x, speed_x);
+            }
+            (false, false) => {
+                self.drive_x(dir_x, speed_x);
+                self.drive_y(dir_y, speed_y);
+            }
+        }
+    }
+
+    #[inline]
+    fn realtime_command<H: Host>(&mut self, command: RealtimeCommand, cmd: &mut H) {
+        match command {
+            RealtimeCommand::FeedHold => self.feed_hold(),
+            RealtimeCommand::Resume => self.resume(),
+            RealtimeCommand::Abort => self.abort(),
+            RealtimeCommand::StatusQuery => self.report_status(cmd),
+        }
+    }
+
+    /// sends a single line status report:
+    /// `<state|MPos:x,y|UPos:x,y|Units:u|Pen:p|Speed:x,y|Seg:n|Buf:segments,lines>`
+    ///
+    /// MPos is in mm relative to home, UPos in encoder units, Units the active
+    /// G20/G21 mode (`mm` or `inch`), Speed is the measured speed of each axis
+    /// in mm/s, Seg the index of the current segment and Buf the free space in
+    /// the sequence and the g-code buffer
+    pub fn report_status<H: Host>(&self, cmd: &mut H) {
+        let pos = self.curr_pos();
+        let (x_mm, y_mm) = self.sequence.pos_to_mm(pos);
+        let speed_x = self.sequence.unit_to_mm_x(self.x_opto.speed());
+        let speed_y = self.sequence.unit_to_mm_y(self.y_opto.speed());
+
+        cmd.send(format_args!(
+            "<{}|MPos:{:.3},{:.3}|UPos:{},{}|Units:{}|Pen:{}|Speed:{:.2},{:.2}|Seg:{}|Buf:{},{}>\n\r",
+            self.state,
+            x_mm,
+            y_mm,
+            pos.0,
+            pos.1,
+            self.sequence.units(),
+            self.pen.pos(),
+            speed_x,
+            speed_y,
+            self.sequence.sequence.curr_idx(),
+            self.sequence.sequence.free_space(),
+            cmd.gcode_buffer_free(),
+        ));
+    }
+
+    /// decelerates along the path and lifts the pen once stopped
+    pub fn feed_hold(&mut self) {
+        match self.state {
+            MachineState::Run => {
+                let sqv = self.sequence.curr_pos();
+                let speed = self
+                    .profile
+                    .velocity_at(self.segment_distance(&sqv) - self.profile_offset);
+                self.hold_decel = Some((self.clock.now(), speed));
+            }
+            MachineState::Idle => self.pen.move_up(),
+            MachineState::Hold | MachineState::Alarm | MachineState::Homing => return,
+        }
+        self.state = MachineState::Hold;
+    }
+
+    /// puts the pen back down and continues the current segment from where the
+    /// feed hold stopped it
+    pub fn resume(&mut self) {
+        if self.state != MachineState::Hold {
+            return;
+        }
+
+        let sqv = self.sequence.curr_pos();
+        let entry_vel = self.hold_speed().unwrap_or(0.0).max(0.0);
+        self.hold_decel = None;
+        self.move_pen(sqv.pen());
+
+        if self.int_idx > sqv.interpolator.get_interpolation_len() {
+            self.state = MachineState::Idle;
+        } else {
+            self.plan_profile(entry_vel, self.segment_distance(&sqv));
+            self.state = MachineState::Run;
+        }
+    }
+
+    /// stops right away, lifts the pen and drops every queued segment. an
+    /// alarm stays latched
+    pub fn abort(&mut self) {
+        self.dwell_until = None;
+        self.x_stop();
+        self.y_stop();
+        self.x_velocity.reset();
+        self.y_velocity.reset();
+        self.pen.move_up();
+
+        let curr_pos = self.curr_pos();
+        self.sequence.clear(curr_pos);
+        self.int_idx = 1;
+        self.profile = MotionProfile::standstill();
+        self.profile_offset = 0.0;
+        self.hold_decel = None;
+        self.homing = None;
+        if self.state != MachineState::Alarm {
+            self.state = MachineState::Idle;
+        }
+    }
+
+    /// cuts both h-bridges
+    fn disable_motors(&mut self) {
+        self.x_motor.disable_pwm();
+        self.y_motor.disable_pwm();
+        self.x_pwm = 0.0;
+        self.y_pwm = 0.0;
+    }
+
+    fn emergency_stop<H: Host>(&mut self, cmd: &mut H) {
+        self.disable_motors();
+        self.abort();
+        self.state = MachineState::Alarm;
+        cmd.send(format_args!("error: emergency stop\n\r"));
+    }
+
+    /// stops and locks the machine until it's unlocked, for faults the
+    /// controller can't see by itself
+    pub fn alarm<H: Host>(&mut self, reason: fmt::Arguments, cmd: &mut H) {
+        self.abort();
+        self.state = MachineState::Alarm;
+        cmd.send(format_args!("error: {}\n\r", reason));
+    }
+
+    /// leaves an alarm once the e-stop is released. the position may be off
+    /// after whatever caused the alarm, so soft limits the machine is outside
+    /// of are dropped until it's homed again
+    pub fn unlock<H: Host>(&mut self, cmd: &mut H) {
+        if self.state != MachineState::Alarm {
+            return;
+        }
+        if self.switches.estop_engaged() {
+            cmd.send(format_args!("error: emergency stop still engaged\n\r"));
+            return;
+        }
+
+        self.x_motor.enable_pwm();
+        self.y_motor.enable_pwm();
+        if let Some(limits) = self.sequence.sequence.soft_limits() {
+            if !limits
+                .grow(self.soft_limit_margin())
+                .contains(self.curr_pos())
+            {
+                self.sequence.sequence.set_soft_limits(None);
+                cmd.send(format_args!("soft limits off until homed\n\r"));
+            }
+        }
+        self.state = MachineState::Idle;
+        cmd.send(format_args!("unlocked\n\r"));
+    }
+
+    /// homes x and then y, drops everything queued. only starts from idle
+    pub fn start_homing<H: Host>(&mut self, cmd: &mut H) {
+        if self.state != MachineState::Idle {
+            cmd.send(format_args!("error: can't home while {}\n\r", self.state));
+            return;
+        }
+
+        self.abort();
+        // the encoders are about to be zeroed somewhere else
+        self.sequence.sequence.set_soft_limits(None);
+        let cycle = HomingCycle::new(
+            self.homing_config(Axis::X),
+            self.clock.now(),
+            self.x_opto.pos(),
+        );
+        self.homing = Some((Axis::X, cycle));
+        self.state = MachineState::Homing;
+        cmd.send(format_args!("homing\n\r"));
+    }
+
+    /// mm from the homing trigger points to the program origin, takes effect
+    /// with the next homing cycle
+    pub fn set_home_offset(&mut self, x: f32, y: f32) {
+        self.settings.home_offset = (x, y);
+    }
+
+    /// envelope in encoder units from the homing trigger points, takes effect
+    /// with the next homing cycle
+    pub fn set_soft_limits(&mut self, soft_limits: SoftLimits) {
+        self.soft_limits = soft_limits;
+    }
+
+    /// `set_soft_limits` in mm
+    pub fn set_soft_limits_mm(&mut self, min: (f32, f32), max: (f32, f32)) {
+        self.settings.soft_limits_min = min;
+        self.settings.soft_limits_max = max;
+        let unit_lengths = self.sequence.unit_lengths();
+        self.set_soft_limits(SoftLimits::from_mm(min, max, unit_lengths));
+    }
+
+    /// moves are checked against the soft limits before they're queued, this
+    /// catches an axis that ends up outside anyway and halts everything
+    #[inline]
+    fn check_soft_limits<H: Host>(&mut self, cmd: &mut H) {
+        let limits = match self.sequence.sequence.soft_limits() {
+            Some(limits) => limits,
+            None => return,
+        };
+        let pos = self.curr_pos();
+        let inside = limits.grow(self.soft_limit_margin()).contains(pos);
+        if self.state == MachineState::Alarm || inside {
+            return;
+        }
+
+        self.abort();
+        self.state = MachineState::Alarm;
+        let (x, y) = self.sequence.pos_to_mm(pos);
+        cmd.send(format_args!(
+            "error: soft limit, halted at {:.3},{:.3}\n\r",
+            x, y
+        ));
+    }
+
+    /// `SOFT_LIMIT_TOLERANCE` in encoder units
+    #[inline]
+    fn soft_limit_margin(&self) -> (i32, i32) {
+        (
+            self.sequence.mm_to_unit_x(SOFT_LIMIT_TOLERANCE) as i32,
+            self.sequence.mm_to_unit_y(SOFT_LIMIT_TOLERANCE) as i32,
+        )
+    }
+
+    /// both axes home towards negative counts, x left against the frame and
+    /// y down. the rest comes from the settings
+    fn homing_config(&self, axis: Axis) -> HomingConfig {
+        let s = &self.settings;
+        let (trigger, seek_duty, locate_duty, back_off, max_travel) = match axis {
+            Axis::X => (
+                s.homing_trigger.0,
+                s.homing_seek_duty.0,
+                s.homing_locate_duty.0,
+                self.sequence.mm_to_unit_x(s.homing_back_off.0),
+                X_HOMING_TRAVEL,
+            ),
+            Axis::Y => (
+                s.homing_trigger.1,
+                s.homing_seek_duty.1,
+                s.homing_locate_duty.1,
+                self.sequence.mm_to_unit_y(s.homing_back_off.1),
+                Y_HOMING_TRAVEL,
+            ),
+        };
+        HomingConfig {
+            trigger,
+            dir: -1.0,
+            seek_duty,
+            locate_duty,
+            back_off: back_off as i32,
+            max_travel,
+            stall_time: HOMING_STALL_TIME,
+        }
+    }
+
+    fn homing_tick<H: Host>(&mut self, cmd: &mut H) {
+        let now = self.clock.now();
+        let (axis, step) = match self.homing.as_mut() {
+            Some((Axis::X, cycle)) => (
+                Axis::X,
+                cycle.tick(now, self.x_opto.pos(), self.switches.x_limit()),
+            ),
+            Some((Axis::Y, cycle)) => (
+                Axis::Y,
+                cycle.tick(now, self.y_opto.pos(), self.switches.y_limit()),
+            ),
+            None => return,
+        };
+
+        match (axis, step) {
+            (Axis::X, HomingStep::Drive(duty)) => self.move_x(duty, duty.abs()),
+            (Axis::Y, HomingStep::Drive(duty)) => self.move_y(duty, duty.abs()),
+            (Axis::X, HomingStep::Done(_)) => {
+                self.x_stop();
+                self.x_opto.calibrate();
+                let cycle = HomingCycle::new(self.homing_config(Axis::Y), now, self.y_opto.pos());
+                self.homing = Some((Axis::Y, cycle));
+            }
+            (Axis::Y, HomingStep::Done(_)) => {
+                self.y_stop();
+                self.y_opto.calibrate();
+                self.finish_homing(cmd);
+            }
+            (_, HomingStep::Failed(error)) => {
+                self.x_stop();
+                self.y_stop();
+                self.homing = None;
+                self.state = MachineState::Alarm;
+                cmd.send(format_args!("error: homing {} failed, {}\n\r", axis, error));
+            }
+        }
+    }
+
+    /// both encoders are zeroed at their trigger points
+    fn finish_homing<H: Host>(&mut self, cmd: &mut H) {
+        self.homing = None;
+        self.sequence
+            .set_home(self.settings.home_offset.0, self.settings.home_offset.1);
+        self.sequence
+            .sequence
+            .set_soft_limits(Some(self.soft_limits));
+        let curr_pos = self.curr_pos();
+        self.sequence.clear(curr_pos);
+        self.target = curr_pos;
+        self.state = MachineState::Idle;
+        cmd.send(format_args!("homed\n\r"));
+    }
+
+    fn settings_command<H: Host>(&mut self, command: SettingsCommand, cmd: &mut H) {
+        match command {
+            SettingsCommand::List => {
+                for info in grbl_settings::SETTINGS {
+                    let value = info.get(&self.settings);
+                    cmd.send(format_args!(
+                        "${}={} ({})\n\r",
+                        info.id, value, info.description
+                    ));
+                }
+            }
+            SettingsCommand::Show(id) => {
+                if let Ok(info) = grbl_settings::info(id) {
+                    let value = info.get(&self.settings);
+                    cmd.send(format_args!(
+                        "${}={} ({})\n\r",
+                        info.id, value, info.description
+                    ));
+                }
+            }
+            SettingsCommand::Set(id, value) => {
+                let mut settings = self.settings;
+                if let Err(e) = settings.set(id, value) {
+                    cmd.send(format_args!("error: {}\n\r", e));
+                } else if self.update_settings(settings, cmd).is_ok() {
+                    cmd.send(format_args!("${}={}\n\r", id, value));
+                }
+            }
+            SettingsCommand::Reset => {
+                if self.update_settings(DEFAULTS, cmd).is_ok() {
+                    cmd.send(format_args!("settings restored to defaults\n\r"));
+                }
+            }
+        }
+    }
+
+    fn telemetry_request<H: Host>(&mut self, request: TelemetryRequest, cmd: &mut H) {
+        match request {
+            TelemetryRequest::Subscribe { peer, rate_hz } => {
+                self.telemetry.subscribe(peer, rate_hz);
+                cmd.send(format_args!(
+                    "telemetry: {} Hz to {}\n\r",
+                    rate_hz.min(telemetry::MAX_RATE_HZ),
+                    peer
+                ));
+            }
+            TelemetryRequest::Unsubscribe => {
+                self.telemetry.unsubscribe();
+                cmd.send(format_args!("telemetry: off\n\r"));
+            }
+        }
+    }
+
+    #[inline]
+    fn sample_telemetry<H: Host>(&mut self, cmd: &mut H) {
+        let now = self.clock.now();
+        if !self.telemetry.is_due(now) {
+            return;
+        }
+
+        let x = AxisSample {
+            target_pos: self.target.0,
+            actual_pos: self.x_opto.pos(),
+            pwm: self.x_pwm,
+            speed: self.x_opto.speed(),
+        };
+        let y = AxisSample {
+            target_pos: self.target.1,
+            actual_pos: self.y_opto.pos(),
+            pwm: self.y_pwm,
+            speed: self.y_opto.speed(),
+        };
+
+        if let Some((packet, peer)) = self.telemetry.record(now, x, y) {
+            cmd.send_to(packet, peer);
+        }
+    }
+
+    #[inline]
+    pub fn state(&self) -> MachineState {
+        self.state
+    }
+
+    /// path speed (mm/s) a decelerating feed hold allows right now
+    #[inline]
+    fn hold_speed(&self) -> Option<f32> {
+        self.hold_decel.map(|(start, speed)| {
+            let elapsed = (self.clock.now() - start) as f32 / 1_000_000.0;
+            speed - self.settings.acceleration * elapsed
+        })
+    }
+
+    /// distance (mm) the tool has covered along the current segment
+    #[inline]
+    fn segment_distance(&self, sqv: &SequenceVector) -> f32 {
+        let interpolation_len = sqv.interpolator.get_interpolation_len().max(1);
+        let idx = self.int_idx.max(1).min(interpolation_len + 1) - 1;
+        sqv.length() * idx as f32 / interpolation_len as f32
+    }
+
+    /// sets up the velocity profile for the segment that just became current,
+    /// it starts where the previous one ended and ends at the junction speed
+    /// the look-ahead planned
+    fn plan_segment(&mut self) {
+        // start from wherever the previous segment actually ended up, the
+        // look-ahead may have raised its exit speed since then
+        let entry_vel = self.profile.velocity_at(self.profile.length());
+        self.plan_profile(entry_vel, 0.0);
+    }
+
+    /// plans the rest of the current segment starting `offset` mm into it
+    fn plan_profile(&mut self, entry_vel: f32, offset: f32) {
+        let sqv = self.sequence.curr_pos();
+        let max_vel = sqv.feedrate() / 60.0;
+        let exit_vel = self.sequence.sequence.exit_vel();
+        let accel = self.settings.acceleration;
+
+        let length = (sqv.length() - offset).max(0.0);
+        let profile = match self.profile_kind {
+            ProfileKind::Trapezoidal => {
+                SpeedProfile::new(length, entry_vel, max_vel, exit_vel, accel)
+                    .map(MotionProfile::Trapezoidal)
+            }
+            ProfileKind::SCurve => SCurveProfile::new(
+                length,
+                entry_vel,
+                max_vel,
+                exit_vel,
+                accel,
+                self.settings.jerk,
+            )
+            .map(MotionProfile::SCurve),
+        };
+
+        if let Ok(profile) = profile {
+            self.profile = profile;
+            self.profile_offset = offset;
+        }
+    }
+
+    /// speed each axis has to move at (mm/s) for the tool to follow the path
+    /// with the segment's velocity profile
+    #[inline]
+    fn axis_speeds(&self, sqv: &SequenceVector) -> (f32, f32) {
+        let (tx, ty) = sqv.interpolator.tangent_at(self.int_idx);
+        let (tx, ty) = (
+            self.sequence.unit_to_mm_x(tx),
+            self.sequence.unit_to_mm_y(ty),
+        );
+        let len = sqrt(tx * tx + ty * ty);
+
+        if len == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let distance = self.segment_distance(sqv) - self.profile_offset;
+        let mut speed = self.profile.velocity_at(distance);
+        if let Some(hold_speed) = self.hold_speed() {
+            speed = speed.min(hold_speed);
+        }
+
+        ((tx / len).abs() * speed, (ty / len).abs() * speed)
+    }
+
+    /// runs the x velocity loop towards `speed` (mm/s) in `dir` and applies its output
+    fn drive_x(&mut self, dir: f32, speed: f32) {
+        let now = self.clock.now();
+        // a loop that was idle continues as if it had just been updated, a
+        // stopped or reversing axis gets a fresh output right away
+        let dt = (now - self.last_correction_time_x).min(CORRECTION_INTERVAL);
+
+        if dt >= CORRECTION_INTERVAL || self.x_pwm * dir <= 0.0 {
+            self.last_correction_time_x = now;
+            let measured = self.sequence.unit_to_mm_x(self.x_opto.speed());
+            let target = dir * speed.max(self.settings.min_speed);
+            self.x_velocity
+                .update(target, measured, dt as f32 / 1_000_000.0);
+        }
+
+        let duty = self.x_velocity.output().abs();
+        if duty == 0.0 {
+            self.x_stop();
+        } else {
+            self.move_x(dir, duty);
+        }
+    }
+
+    /// runs the y velocity loop towards `speed` (mm/s) in `dir` and applies its output
+    fn drive_y(&mut self, dir: f32, speed: f32) {
+        let now = self.clock.now();
+        let dt = (now - self.last_correction_time_y).min(CORRECTION_INTERVAL);
+
+        if dt >= CORRECTION_INTERVAL || self.y_pwm * dir <= 0.0 {
+            self.last_correction_time_y = now;
+            let measured = self.sequence.unit_to_mm_y(self.y_opto.speed());
+            let target = dir * speed.max(self.settings.min_speed);
+            self.y_velocity
+                .update(target, measured, dt as f32 / 1_000_000.0);
+        }
+
+        let duty = self.y_velocity.output().abs();
+        if duty == 0.0 {
+            self.y_stop();
+        } else {
+            self.move_y(dir, duty);
+        }
+    }
+
+    /// direction to drive an axis in to get from `curr` to `target`, where `prev`
+    /// is the interpolation point before `target`. an axis that overshot the
+    /// path by more than `HOLD_WINDOW` gets driven back.
+    #[inline]
+    fn step_dir(prev: i32, target: i32, curr: i32) -> f32 {
+        let off = curr - target;
+        if target > prev || (target == prev && off < -HOLD_WINDOW) {
+            1.0
+        } else if target < prev || (target == prev && off > HOLD_WINDOW) {
+            -1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn move_x(&mut self, dir: f32, pwm: f32) {
+        if dir > 0.0 {
+            self.x_right(pwm);
+        } else if dir < 0.0 {
+            self.x_left(pwm);
+        } else {
+            self.x_stop();
+        }
+    }
+
+    fn move_y(&mut self, dir: f32, pwm: f32) {
+        if dir > 0.0 {
+            self.y_up(pwm);
+        } else if dir < 0.0 {
+            self.y_down(pwm);
+        } else {
+            self.y_stop();
+        }
+    }
+
+    fn x_left(&mut self, pwm: f32) {
+        self.x_motor.move_negative(pwm);
+        self.x_pwm = -pwm;
+    }
+
+    fn x_right(&mut self, pwm: f32) {
+        self.x_motor.move_positive(pwm);
+        self.x_pwm = pwm;
+    }
+
+    fn x_stop(&mut self) {
+        self.x_motor.active_stop();
+        self.x_pwm = 0.0;
+    }
+
+    fn y_down(&mut self, pwm: f32) {
+        self.y_motor.move_negative(pwm);
+        self.y_pwm = -pwm;
+    }
+
+    fn y_up(&mut self, pwm: f32) {
+        self.y_motor.move_positive(pwm);
+        self.y_pwm = pwm;
+    }
+
+    fn y_stop(&mut self) {
+        self.y_motor.active_stop();
+        self.y_pwm = 0.0;
+    }
+
+    #[inline]
+    pub fn curr_pos(&self) -> (i32, i32) {
+        (self.x_opto.pos(), self.y_opto.pos())
+    }
+}
@@ -0,0 +1,165 @@
+use crate::pwm_duty::PwmDutyCycle;
+
+pub trait PwmPin {
+    fn enable_a(&mut self);
+    fn enable_b(&mut self);
+
+    fn disable_a(&mut self);
+    fn disable_b(&mut self);
+
+    fn set_a_duty(&mut self, duty: u16);
+    fn set_b_duty(&mut self, duty: u16);
+
+    fn get_a_max_duty(&self) -> u16;
+    fn get_b_max_duty(&self) -> u16;
+
+    fn set_h_bridge_high(&mut self);
+    fn set_h_bridge_low(&mut self);
+}
+
+pub struct MotorPwm<T: PwmPin> {
+    pwm_pin: T,
+
+    pwm_a: PwmDutyCycle,
+    pwm_b: PwmDutyCycle,
+
+    motor_dir: MotorDir,
+}
+
+enum MotorDir {
+    Positive(f32),
+    Negative(f32),
+    Stopped(PWMState),
+}
+pub enum PWMState {
+    Disabled,
+    Enabled,
+}
+
+impl<T: PwmPin> MotorPwm<T> {
+    pub fn new(pwm_pin: T) -> MotorPwm<T> {
+        let pwm_a_max = pwm_pin.get_a_max_duty();
+        let pwm_b_max = pwm_pin.get_b_max_duty();
+
+        MotorPwm {
+            pwm_pin,
+
+            pwm_a: PwmDutyCycle::new(pwm_a_max),
+            pwm_b: PwmDutyCycle::new(pwm_b_max),
+
+            motor_dir: MotorDir::Stopped(PWMState::Disabled),
+        }
+    }
+
+    //enable & disable pwm (global)
+    #[inline]
+    pub fn enable_pwm(&mut self) {
+        match self.motor_dir {
+            MotorDir::Negative(_) => {}
+            MotorDir::Positive(_) => {}
+            MotorDir::Stopped(PWMState::Enabled) => {}
+
+            MotorDir::Stopped(PWMState::Disabled) => {
+                Self::enable_pwm_a(&mut self.pwm_a, &mut self.pwm_pin);
+                Self::enable_pwm_b(&mut self.pwm_b, &mut self.pwm_pin);
+                self.pwm_pin.set_h_bridge_high();
+                self.motor_dir = MotorDir::Stopped(PWMState::Enabled);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn disable_pwm(&mut self) {
+        match self.motor_dir {
+            MotorDir::Stopped(PWMState::Disabled) => {}
+            _ => {
+                Self::disable_pwm_a(&mut self.pwm_a, &mut self.pwm_pin);
+                Self::disable_pwm_b(&mut self.pwm_b, &mut self.pwm_pin);
+                self.pwm_pin.set_h_bridge_low();
+                self.motor_dir = MotorDir::Stopped(PWMState::Disabled);
+            }
+        }
+    }
+
+    //set_pwm_*_duty
+    fn set_pwm_a_duty(pwm_a: &mut PwmDutyCycle, pwm_pin: &mut T, duty: f32) {
+        pwm_a.set_duty_cycle(duty);
+        pwm_pin.set_a_duty(pwm_a.get_duty_cycle_val());
+    }
+
+    fn set_pwm_b_duty(pwm_b: &mut PwmDutyCycle, pwm_pin: &mut T, duty: f32) {
+        pwm_b.set_duty_cycle(duty);
+        pwm_pin.set_b_duty(pwm_b.get_duty_cycle_val());
+    }
+
+    //get_pwm_*_duty
+
+    pub fn get_pwm_a_duty(&self) -> f32 {
+        self.pwm_a.get_duty_cycle()
+    }
+
+    pub fn get_pwm_b_duty(&self) -> f32 {
+        self.pwm_b.get_duty_cycle()
+    }
+
+    //disable_pwm_*
+    fn disable_pwm_a(pwm_a: &mut PwmDutyCycle, pwm_pin: &mut T) {
+        Self::set_pwm_a_duty(pwm_a, pwm_pin, 0.0);
+        pwm_pin.disable_a();
+    }
+
+    fn disable_pwm_b(pwm_b: &mut PwmDutyCycle, pwm_pin: &mut T) {
+        Self::set_pwm_b_duty(pwm_b, pwm_pin, 0.0);
+        pwm_pin.disable_b();
+    }
+
+    //enable_pwm_*
+    fn enable_pwm_a(pwm_a: &mut PwmDutyCycle, pwm_pin: &mut T) {
+        Self::set_pwm_a_duty(pwm_a, pwm_pin, 0.0); //set pwm to zero before starting
+        pwm_pin.enable_a();
+    }
+
+    fn enable_pwm_b(pwm_b: &mut PwmDutyCycle, pwm_pin: &mut T) {
+        Self::set_pwm_b_duty(pwm_b, pwm_pin, 0.0); //set pwm to zero before starting
+        pwm_pin.enable_b();
+    }
+
+    //move_[dir]
+    #[inline]
+    pub fn move_negative(&mut self, duty_cycle: f32) {
+        match self.motor_dir {
+            MotorDir::Negative(duty) if duty == duty_cycle => {}
+            _ => {
+                Self::enable_pwm_a(&mut self.pwm_a, &mut self.pwm_pin);
+                Self::set_pwm_a_duty(&mut self.pwm_a, &mut self.pwm_pin, duty_cycle);
+                Self::disable_pwm_b(&mut self.pwm_b, &mut self.pwm_pin);
+                self.motor_dir = MotorDir::Negative(duty_cycle);
+            }
+        }
+    }
+    #[inline]
+    pub fn move_positive(&mut self, duty_cycle: f32) {
+        match self.motor_dir {
+            MotorDir::Positive(duty) if duty == duty_cycle => {}
+            _ => {
+                Self::enable_pwm_b(&mut self.pwm_b, &mut self.pwm_pin);
+                Self::set_pwm_b_duty(&mut self.pwm_b, &mut self.pwm_pin, duty_cycle);
+                Self::disable_pwm_a(&mut self.pwm_a, &mut self.pwm_pin);
+                self.motor_dir = MotorDir::Positive(duty_cycle);
+            }
+        }
+    }
+
+    /// brakes the motor, a disabled motor stays disabled
+    #[inline]
+    pub fn active_stop(&mut self) {
+        match self.motor_dir {
+            MotorDir::Stopped(_) => (),
+            _ => {
+                Self::set_pwm_a_duty(&mut self.pwm_a, &mut self.pwm_pin, 0.0);
+                Self::set_pwm_b_duty(&mut self.pwm_b, &mut self.pwm_pin, 0.0);
+                self.motor_dir = MotorDir::Stopped(PWMState::Enabled);
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use core::fmt;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum PenPosition {
+    Default,
+    Angle(u8),
+}
+
+impl fmt::Display for PenPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PenPosition::Default => write!(f, "up"),
+            PenPosition::Angle(a) => write!(f, "{}", a),
+        }
+    }
+}
+
+/// the pen lift as the motion controller drives it
+pub trait Pen {
+    /// last position the pen was sent to
+    fn pos(&self) -> PenPosition;
+
+    fn move_pen(&mut self, pen_pos: PenPosition);
+
+    fn move_up(&mut self);
+
+    /// angle the pen is lifted to, takes effect with the next lift
+    fn set_up_angle(&mut self, angle: u8);
+}
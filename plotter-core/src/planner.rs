@@ -0,0 +1,46 @@
+//! look-ahead helpers, speeds the tool can keep through the junction between
+//! two segments. all distances are in mm, velocities in mm/s.
+
+use crate::math::sqrt;
+
+/// max speed through the corner between a segment ending in direction
+/// `prev_dir` and one starting in direction `next_dir`. uses the junction
+/// deviation model: the corner is treated as a circular arc that stays within
+/// `deviation` of the sharp corner and the speed is limited so the centripetal
+/// acceleration on that arc doesn't exceed `accel`.
+///
+/// returns `f32::INFINITY` for a straight continuation, 0 for a reversal or if
+/// either direction has no length
+pub fn junction_velocity(
+    prev_dir: (f32, f32),
+    next_dir: (f32, f32),
+    accel: f32,
+    deviation: f32,
+) -> f32 {
+    let prev_len = sqrt(prev_dir.0 * prev_dir.0 + prev_dir.1 * prev_dir.1);
+    let next_len = sqrt(next_dir.0 * next_dir.0 + next_dir.1 * next_dir.1);
+
+    if prev_len == 0.0 || next_len == 0.0 {
+        return 0.0;
+    }
+
+    // cosine of the angle inside the corner, -1 when the path goes straight on
+    let cos_theta = -(prev_dir.0 * next_dir.0 + prev_dir.1 * next_dir.1) / (prev_len * next_len);
+
+    if cos_theta < -0.999_999 {
+        return f32::INFINITY;
+    }
+    if cos_theta > 0.999_999 {
+        return 0.0;
+    }
+
+    let sin_half_theta = sqrt((1.0 - cos_theta) * 0.5);
+    sqrt(accel * deviation * sin_half_theta / (1.0 - sin_half_theta))
+}
+
+/// highest speed at the start of a segment `length` long from which `exit_vel`
+/// can still be reached at its end
+#[inline]
+pub fn max_entry_velocity(exit_vel: f32, length: f32, accel: f32) -> f32 {
+    sqrt(exit_vel * exit_vel + 2.0 * accel * length)
+}
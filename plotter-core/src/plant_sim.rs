@@ -0,0 +1,329 @@
+//! simulated axis for running the motion code off the plotter: a brushed dc
+//! motor driven through the h-bridge, a belt with backlash and the encoder
+//! on the carriage. `SimPwmPin` and `SimEncoder` put it behind the same
+//! `PwmPin` and `Encoder` traits the real timers implement.
+//!
+//! everything is in encoder units and seconds. the plant doesn't move on its
+//! own, call `step` to advance its clock.
+
+use core::cell::RefCell;
+
+use crate::encoder::Encoder;
+use crate::motor_pwm::PwmPin;
+
+/// integration step, finer than any control loop so the loops see a smooth plant
+const MAX_STEP: f32 = 50e-6;
+
+#[derive(Clone, Copy)]
+pub struct PlantConfig {
+    /// units/s^2 per % duty cycle above the deadband
+    pub torque_gain: f32,
+    /// 1/s, back emf and viscous friction together
+    pub damping: f32,
+    /// units/s^2 of deceleration from sliding friction
+    pub coulomb_friction: f32,
+    /// units/s^2 the drive has to overcome to get a standing motor going
+    pub static_friction: f32,
+    /// duty cycle (%) below which the h-bridge doesn't drive the motor at all
+    pub deadband: f32,
+    /// encoder units of play between the motor and the carriage
+    pub backlash: f32,
+    /// 1/s of extra damping while the bridge is enabled and not driving,
+    /// both low side switches short the motor
+    pub brake_damping: f32,
+    /// value `get_*_max_duty` reports
+    pub max_duty: u16,
+}
+
+impl PlantConfig {
+    /// roughly the x axis: ~4000 units/s at full duty, starts at ~15 %
+    pub fn x_axis() -> Self {
+        Self {
+            torque_gain: 1200.0,
+            damping: 25.0,
+            coulomb_friction: 8000.0,
+            static_friction: 12000.0,
+            deadband: 5.0,
+            backlash: 3.0,
+            brake_damping: 40.0,
+            max_duty: 12_000,
+        }
+    }
+
+    /// roughly the y axis, finer encoder and a heavier carriage
+    pub fn y_axis() -> Self {
+        Self {
+            torque_gain: 3000.0,
+            damping: 18.0,
+            coulomb_friction: 12000.0,
+            static_friction: 20000.0,
+            deadband: 4.0,
+            backlash: 8.0,
+            brake_damping: 30.0,
+            max_duty: 12_000,
+        }
+    }
+}
+
+pub struct MotorPlant {
+    config: PlantConfig,
+
+    /// motor side position and speed
+    motor_pos: f32,
+    motor_vel: f32,
+    /// carriage position, stays put while the motor crosses the backlash gap
+    load_pos: f32,
+    zero: i32,
+    /// ends of the carriage's travel, `None` for an endless axis
+    travel: Option<(f32, f32)>,
+
+    bridge_enabled: bool,
+    a_enabled: bool,
+    b_enabled: bool,
+    a_duty: u16,
+    b_duty: u16,
+
+    /// us
+    time: u64,
+}
+
+impl MotorPlant {
+    pub fn new(config: PlantConfig) -> Self {
+        Self {
+            config,
+
+            motor_pos: 0.0,
+            motor_vel: 0.0,
+            load_pos: 0.0,
+            zero: 0,
+            travel: None,
+
+            bridge_enabled: false,
+            a_enabled: false,
+            b_enabled: false,
+            a_duty: 0,
+            b_duty: 0,
+
+            time: 0,
+        }
+    }
+
+    /// signed duty cycle (%) the bridge puts on the motor, b drives positive
+    pub fn duty(&self) -> f32 {
+        if !self.bridge_enabled {
+            return 0.0;
+        }
+        let a = if self.a_enabled { self.a_duty } else { 0 };
+        let b = if self.b_enabled { self.b_duty } else { 0 };
+        (b as f32 - a as f32) / self.config.max_duty as f32 * 100.0
+    }
+
+    /// advances the simulation by `dt` seconds
+    pub fn step(&mut self, dt: f32) {
+        let mut left = dt;
+        while left > 0.0 {
+            let h = if left < MAX_STEP { left } else { MAX_STEP };
+            self.integrate(h);
+            left -= h;
+        }
+        self.time += (dt * 1_000_000.0) as u64;
+    }
+
+    fn integrate(&mut self, h: f32) {
+        let c = &self.config;
+
+        let duty = self.duty();
+        let drive = if duty > c.deadband {
+            (duty - c.deadband) * c.torque_gain
+        } else if duty < -c.deadband {
+            (duty + c.deadband) * c.torque_gain
+        } else {
+            0.0
+        };
+
+        let mut damping = c.damping;
+        if self.bridge_enabled && duty == 0.0 {
+            damping += c.brake_damping;
+        }
+
+        if self.motor_vel == 0.0 {
+            // stiction, nothing happens until the drive breaks the motor loose
+            if abs(drive) <= c.static_friction {
+                return;
+            }
+            self.motor_vel = (drive - signum(drive) * c.coulomb_friction) * h;
+        } else {
+            let accel =
+                drive - damping * self.motor_vel - signum(self.motor_vel) * c.coulomb_friction;
+            let vel = self.motor_vel + accel * h;
+            // friction stops the motor, it doesn't reverse it
+            self.motor_vel = if vel * self.motor_vel < 0.0 { 0.0 } else { vel };
+        }
+        self.motor_pos += self.motor_vel * h;
+
+        // the carriage is dragged along once the motor has taken up the play
+        let half_gap = c.backlash / 2.0;
+        if self.motor_pos - self.load_pos > half_gap {
+            self.load_pos = self.motor_pos - half_gap;
+        } else if self.load_pos - self.motor_pos > half_gap {
+            self.load_pos = self.motor_pos + half_gap;
+        }
+
+        // the frame stops the carriage dead, and with it the motor once the
+        // play is taken up
+        if let Some((min, max)) = self.travel {
+            if self.load_pos < min {
+                self.load_pos = min;
+                self.motor_pos = min - half_gap;
+                self.motor_vel = 0.0;
+            } else if self.load_pos > max {
+                self.load_pos = max;
+                self.motor_pos = max + half_gap;
+                self.motor_vel = 0.0;
+            }
+        }
+    }
+
+    /// puts hard stops at `min` and `max` carriage position, in encoder units
+    /// from where the plant started
+    pub fn set_travel(&mut self, min: f32, max: f32) {
+        self.travel = Some((min, max));
+    }
+
+    /// us since the plant was created
+    #[inline]
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// encoder count, relative to the last calibration
+    #[inline]
+    pub fn count(&self) -> i32 {
+        floor(self.load_pos) - self.zero
+    }
+
+    /// units/s of the motor side
+    #[inline]
+    pub fn motor_vel(&self) -> f32 {
+        self.motor_vel
+    }
+
+    /// exact carriage position in encoder units, relative to the last calibration
+    #[inline]
+    pub fn load_pos(&self) -> f32 {
+        self.load_pos - self.zero as f32
+    }
+
+    #[inline]
+    pub fn bridge_enabled(&self) -> bool {
+        self.bridge_enabled
+    }
+}
+
+#[inline]
+fn abs(val: f32) -> f32 {
+    if val < 0.0 {
+        -val
+    } else {
+        val
+    }
+}
+
+#[inline]
+fn signum(val: f32) -> f32 {
+    if val < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+#[inline]
+fn floor(val: f32) -> i32 {
+    let trunc = val as i32;
+    if (trunc as f32) > val {
+        trunc - 1
+    } else {
+        trunc
+    }
+}
+
+/// h-bridge side of a `MotorPlant`, hand it to `MotorPwm::new`
+pub struct SimPwmPin<'a> {
+    plant: &'a RefCell<MotorPlant>,
+}
+
+impl<'a> SimPwmPin<'a> {
+    pub fn new(plant: &'a RefCell<MotorPlant>) -> Self {
+        Self { plant }
+    }
+}
+
+impl<'a> PwmPin for SimPwmPin<'a> {
+    fn enable_a(&mut self) {
+        self.plant.borrow_mut().a_enabled = true;
+    }
+
+    fn enable_b(&mut self) {
+        self.plant.borrow_mut().b_enabled = true;
+    }
+
+    fn disable_a(&mut self) {
+        self.plant.borrow_mut().a_enabled = false;
+    }
+
+    fn disable_b(&mut self) {
+        self.plant.borrow_mut().b_enabled = false;
+    }
+
+    fn set_a_duty(&mut self, duty: u16) {
+        self.plant.borrow_mut().a_duty = duty;
+    }
+
+    fn set_b_duty(&mut self, duty: u16) {
+        self.plant.borrow_mut().b_duty = duty;
+    }
+
+    fn get_a_max_duty(&self) -> u16 {
+        self.plant.borrow().config.max_duty
+    }
+
+    fn get_b_max_duty(&self) -> u16 {
+        self.plant.borrow().config.max_duty
+    }
+
+    fn set_h_bridge_high(&mut self) {
+        self.plant.borrow_mut().bridge_enabled = true;
+    }
+
+    fn set_h_bridge_low(&mut self) {
+        self.plant.borrow_mut().bridge_enabled = false;
+    }
+}
+
+/// encoder side of a `MotorPlant`
+pub struct SimEncoder<'a> {
+    plant: &'a RefCell<MotorPlant>,
+}
+
+impl<'a> SimEncoder<'a> {
+    pub fn new(plant: &'a RefCell<MotorPlant>) -> Self {
+        Self { plant }
+    }
+}
+
+impl<'a> Encoder for SimEncoder<'a> {
+    fn pos(&self) -> i32 {
+        self.plant.borrow().count()
+    }
+
+    /// like the timer's dir bit, set while counting down
+    fn dir(&self) -> bool {
+        self.plant.borrow().motor_vel < 0.0
+    }
+
+    fn calibrate(&self) {
+        let mut plant = self.plant.borrow_mut();
+        plant.zero = floor(plant.load_pos);
+    }
+}
@@ -0,0 +1,142 @@
+//! framing of the command stream. a frame is
+//!
+//! `[FRAME_MAGIC] [kind] [seq lo] [seq hi] [payload..]`
+//!
+//! host -> plotter: `FRAME_DATA` carries g-code text, `FRAME_SYNC` (no payload)
+//! sets the sequence number the next data frame is expected with.
+//!
+//! plotter -> host: `FRAME_ACK` / `FRAME_NACK` with the payload
+//! `[free lo] [free hi] [reason]`, free being the number of g-code lines that
+//! can still be buffered. an ack means every frame up to and including `seq`
+//! has been queued, a nack asks the host to resend everything starting from
+//! `seq`.
+//!
+//! datagrams that don't start with `FRAME_MAGIC` aren't frames, they are plain
+//! g-code text without any delivery guarantees. real-time commands (`!`, `~`, `?`,
+//! ctrl-x) are only picked up from those, so they don't queue up behind
+//! frames waiting to be resent.
+
+pub const FRAME_MAGIC: u8 = 0xA5;
+
+pub const FRAME_DATA: u8 = 0x01;
+pub const FRAME_SYNC: u8 = 0x02;
+pub const FRAME_ACK: u8 = 0x81;
+pub const FRAME_NACK: u8 = 0x82;
+
+const HEADER_LEN: usize = 4;
+pub const REPLY_LEN: usize = HEADER_LEN + 3;
+
+pub enum Frame<'a> {
+    Data { seq: u16, payload: &'a [u8] },
+    Sync { seq: u16 },
+}
+
+/// returns `None` if `data` isn't a frame at all, `Some(Err(()))` if it looks
+/// like one but can't be decoded
+pub fn parse_frame(data: &[u8]) -> Option<Result<Frame<'_>, ()>> {
+    if data.first() != Some(&FRAME_MAGIC) {
+        return None;
+    }
+    if data.len() < HEADER_LEN {
+        return Some(Err(()));
+    }
+
+    let seq = u16::from_le_bytes([data[2], data[3]]);
+    let frame = match data[1] {
+        FRAME_DATA => Ok(Frame::Data {
+            seq,
+            payload: &data[HEADER_LEN..],
+        }),
+        FRAME_SYNC => Ok(Frame::Sync { seq }),
+        _ => Err(()),
+    };
+    Some(frame)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NackReason {
+    /// a frame got lost, resend from `seq`
+    OutOfOrder = 1,
+    /// not enough room for the frame's lines, resend it later
+    BufferFull = 2,
+    /// the frame couldn't be decoded
+    Malformed = 3,
+    /// the machine is in alarm and takes no g-code until it's unlocked
+    Alarm = 4,
+}
+
+#[derive(Clone, Copy)]
+pub enum Reply {
+    Ack {
+        seq: u16,
+        free: u16,
+    },
+    Nack {
+        seq: u16,
+        free: u16,
+        reason: NackReason,
+    },
+}
+
+impl Reply {
+    pub fn encode<'a>(&self, buf: &'a mut [u8; REPLY_LEN]) -> &'a [u8] {
+        let (kind, seq, free, reason) = match *self {
+            Reply::Ack { seq, free } => (FRAME_ACK, seq, free, 0),
+            Reply::Nack { seq, free, reason } => (FRAME_NACK, seq, free, reason as u8),
+        };
+
+        let seq = seq.to_le_bytes();
+        let free = free.to_le_bytes();
+        *buf = [FRAME_MAGIC, kind, seq[0], seq[1], free[0], free[1], reason];
+        &buf[..]
+    }
+}
+
+pub enum Delivery {
+    /// the expected frame, process it
+    New,
+    /// already processed, only acknowledge it again
+    Duplicate,
+    /// frames before this one are missing
+    Gap,
+}
+
+/// keeps track of which data frame comes next
+pub struct SequenceTracker {
+    expected: u16,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self { expected: 0 }
+    }
+
+    #[inline]
+    pub fn expected(&self) -> u16 {
+        self.expected
+    }
+
+    #[inline]
+    pub fn sync(&mut self, seq: u16) {
+        self.expected = seq;
+    }
+
+    /// sequence numbers wrap around, anything up to half the range behind the
+    /// expected one counts as already seen
+    pub fn classify(&self, seq: u16) -> Delivery {
+        let diff = seq.wrapping_sub(self.expected) as i16;
+        if diff == 0 {
+            Delivery::New
+        } else if diff < 0 {
+            Delivery::Duplicate
+        } else {
+            Delivery::Gap
+        }
+    }
+
+    /// call once the expected frame has been processed
+    #[inline]
+    pub fn accept(&mut self) {
+        self.expected = self.expected.wrapping_add(1);
+    }
+}
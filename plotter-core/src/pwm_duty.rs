@@ -14,7 +14,7 @@ impl PwmDutyCycle {
     /// duty cycle: 0-100
     #[inline]
     pub fn set_duty_cycle(&mut self, duty_cycle: f32) {
-        if duty_cycle > 100.0 || duty_cycle < 0.0 {
+        if !(0.0..=100.0).contains(&duty_cycle) {
             panic!("duty cycle out of range(0-100): {}", duty_cycle);
         }
         self.duty_cycle = duty_cycle / 100.0;
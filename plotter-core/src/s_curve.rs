@@ -0,0 +1,280 @@
+//! jerk limited (s-curve) velocity profile over a single segment. acceleration
+//! ramps up and down linearly instead of jumping, which keeps the pen from
+//! shaking on speed changes. distances are in mm, velocities in mm/s,
+//! accelerations in mm/s^2, jerk in mm/s^3 and times in s.
+
+use crate::math::sqrt;
+
+/// iterations used whenever a velocity or time has to be searched for
+const BISECTION_STEPS: u32 = 24;
+
+/// jerk limited change of velocity: jerk phase, constant acceleration phase,
+/// jerk phase
+#[derive(Clone, Copy)]
+struct Transition {
+    start_vel: f32,
+    /// 1 when speeding up, -1 when slowing down
+    sign: f32,
+    jerk: f32,
+    /// highest acceleration reached
+    peak_accel: f32,
+    /// duration of each of the jerk phases
+    jerk_time: f32,
+    /// duration of the constant acceleration phase between them
+    const_time: f32,
+}
+
+impl Transition {
+    fn new(start_vel: f32, end_vel: f32, accel: f32, jerk: f32) -> Transition {
+        let (dv, sign) = if end_vel >= start_vel {
+            (end_vel - start_vel, 1.0)
+        } else {
+            (start_vel - end_vel, -1.0)
+        };
+
+        // accel is only reached if the change is big enough, otherwise the
+        // two jerk phases meet in the middle
+        let (jerk_time, const_time) = if dv * jerk >= accel * accel {
+            (accel / jerk, dv / accel - accel / jerk)
+        } else {
+            (sqrt(dv / jerk), 0.0)
+        };
+
+        Transition {
+            start_vel,
+            sign,
+            jerk,
+            peak_accel: jerk * jerk_time,
+            jerk_time,
+            const_time,
+        }
+    }
+
+    #[inline]
+    fn duration(&self) -> f32 {
+        2.0 * self.jerk_time + self.const_time
+    }
+
+    #[inline]
+    fn end_vel(&self) -> f32 {
+        self.start_vel + self.sign * self.peak_accel * (self.jerk_time + self.const_time)
+    }
+
+    /// the velocity curve is point symmetric, so this is the mean velocity
+    /// times the duration
+    #[inline]
+    fn distance(&self) -> f32 {
+        (self.start_vel + self.end_vel()) * 0.5 * self.duration()
+    }
+
+    /// position and velocity `t` after the start of the transition
+    fn at(&self, t: f32) -> (f32, f32) {
+        let t = t.max(0.0).min(self.duration());
+        let (s, j, a) = (self.sign, self.jerk, self.peak_accel);
+
+        let t1 = t.min(self.jerk_time);
+        let mut pos = self.start_vel * t1 + s * j * t1 * t1 * t1 / 6.0;
+        let mut vel = self.start_vel + s * j * t1 * t1 / 2.0;
+
+        let t2 = (t - self.jerk_time).max(0.0).min(self.const_time);
+        pos += vel * t2 + s * a * t2 * t2 / 2.0;
+        vel += s * a * t2;
+
+        let t3 = (t - self.jerk_time - self.const_time).max(0.0);
+        pos += vel * t3 + s * (a * t3 * t3 / 2.0 - j * t3 * t3 * t3 / 6.0);
+        vel += s * (a * t3 - j * t3 * t3 / 2.0);
+
+        (pos, vel)
+    }
+}
+
+/// distance needed to go from `start_vel` to `end_vel`
+#[inline]
+pub fn transition_dist(start_vel: f32, end_vel: f32, accel: f32, jerk: f32) -> f32 {
+    Transition::new(start_vel, end_vel, accel, jerk).distance()
+}
+
+/// highest speed at the start of a segment `length` long from which `exit_vel`
+/// can still be reached at its end
+pub fn max_entry_velocity(exit_vel: f32, length: f32, accel: f32, jerk: f32) -> f32 {
+    // a jerk limited ramp always needs more room than a trapezoidal one
+    let mut low = exit_vel;
+    let mut high = sqrt(exit_vel * exit_vel + 2.0 * accel * length);
+
+    for _ in 0..BISECTION_STEPS {
+        let mid = (low + high) * 0.5;
+        if transition_dist(mid, exit_vel, accel, jerk) <= length {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// accelerate from the entry velocity, cruise, then decelerate to the exit
+/// velocity, with the acceleration limited by `jerk`
+#[derive(Clone, Copy)]
+pub struct SCurveProfile {
+    length: f32,
+
+    accel: Transition,
+    peak_vel: f32,
+    cruise_time: f32,
+    decel: Transition,
+}
+
+impl SCurveProfile {
+    /// `max_vel` is only reached if the segment is long enough, entry and exit
+    /// velocities above it are capped. if `exit_vel` can't be reached within
+    /// `length` the profile ends at the closest velocity it can reach.
+    pub fn new(
+        length: f32,
+        entry_vel: f32,
+        max_vel: f32,
+        exit_vel: f32,
+        accel: f32,
+        jerk: f32,
+    ) -> Result<SCurveProfile, ()> {
+        if length < 0.0
+            || max_vel <= 0.0
+            || accel <= 0.0
+            || jerk <= 0.0
+            || entry_vel < 0.0
+            || exit_vel < 0.0
+        {
+            return Err(());
+        }
+
+        let entry_vel = entry_vel.min(max_vel);
+        let mut exit_vel = exit_vel.min(max_vel);
+
+        let ramps_dist = |peak_vel: f32, exit_vel: f32| {
+            transition_dist(entry_vel, peak_vel, accel, jerk)
+                + transition_dist(peak_vel, exit_vel, accel, jerk)
+        };
+
+        let peak_vel = if ramps_dist(max_vel, exit_vel) <= length {
+            max_vel
+        } else if ramps_dist(entry_vel.max(exit_vel), exit_vel) <= length {
+            // too short to reach max_vel, look for the highest peak that fits
+            let mut low = entry_vel.max(exit_vel);
+            let mut high = max_vel;
+            for _ in 0..BISECTION_STEPS {
+                let mid = (low + high) * 0.5;
+                if ramps_dist(mid, exit_vel) <= length {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            low
+        } else if entry_vel > exit_vel {
+            // can't slow down to exit_vel in time, decelerate all the way and
+            // leave the segment faster than asked for
+            let mut low = exit_vel;
+            let mut high = entry_vel;
+            for _ in 0..BISECTION_STEPS {
+                let mid = (low + high) * 0.5;
+                if transition_dist(entry_vel, mid, accel, jerk) <= length {
+                    high = mid;
+                } else {
+                    low = mid;
+                }
+            }
+            exit_vel = high;
+            entry_vel
+        } else {
+            // can't speed up to exit_vel in time, accelerate all the way
+            let mut low = entry_vel;
+            let mut high = exit_vel;
+            for _ in 0..BISECTION_STEPS {
+                let mid = (low + high) * 0.5;
+                if transition_dist(entry_vel, mid, accel, jerk) <= length {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            exit_vel = low;
+            low
+        };
+
+        let cruise_dist = (length - ramps_dist(peak_vel, exit_vel)).max(0.0);
+        let cruise_time = if peak_vel > 0.0 {
+            cruise_dist / peak_vel
+        } else {
+            0.0
+        };
+
+        Ok(SCurveProfile {
+            length,
+            accel: Transition::new(entry_vel, peak_vel, accel, jerk),
+            peak_vel,
+            cruise_time,
+            decel: Transition::new(peak_vel, exit_vel, accel, jerk),
+        })
+    }
+
+    /// time it takes to run through the whole profile
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        self.accel.duration() + self.cruise_time + self.decel.duration()
+    }
+
+    /// position and velocity `t` after the start of the segment
+    pub fn setpoint_at(&self, t: f32) -> (f32, f32) {
+        let accel_time = self.accel.duration();
+        if t < accel_time {
+            return self.accel.at(t);
+        }
+
+        let accel_dist = self.accel.distance();
+        let t = t - accel_time;
+        if t < self.cruise_time {
+            return (accel_dist + self.peak_vel * t, self.peak_vel);
+        }
+
+        let cruise_dist = self.peak_vel * self.cruise_time;
+        let (pos, vel) = self.decel.at(t - self.cruise_time);
+        (accel_dist + cruise_dist + pos, vel)
+    }
+
+    /// velocity after travelling `distance` into the segment
+    pub fn velocity_at(&self, distance: f32) -> f32 {
+        let distance = distance.max(0.0).min(self.length);
+
+        // position only ever increases with time
+        let mut low = 0.0;
+        let mut high = self.duration();
+        for _ in 0..BISECTION_STEPS {
+            let mid = (low + high) * 0.5;
+            if self.setpoint_at(mid).0 < distance {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        self.setpoint_at(high).1
+    }
+
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    #[inline]
+    pub fn entry_vel(&self) -> f32 {
+        self.accel.start_vel
+    }
+
+    #[inline]
+    pub fn peak_vel(&self) -> f32 {
+        self.peak_vel
+    }
+
+    #[inline]
+    pub fn exit_vel(&self) -> f32 {
+        self.decel.end_vel()
+    }
+}
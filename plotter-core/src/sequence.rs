@@ -0,0 +1,426 @@
+use core::fmt;
+use core::marker::Copy;
+
+use crate::interpolator::{Interpolation, Interpolator};
+use crate::pen::PenPosition;
+use crate::planner::{junction_velocity, max_entry_velocity};
+use crate::ring_buffer::RingBuffer;
+use crate::s_curve;
+use crate::soft_limits::SoftLimits;
+
+/// number of queued segments whose entry speeds are replanned when a new one
+/// is added
+const LOOKAHEAD: usize = 32;
+/// max number of segments held at once, current one included
+pub const SEQUENCE_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SequenceError {
+    /// no room for another segment
+    Full,
+    /// the segment would leave the soft limits
+    OutOfBounds,
+    /// an arc that can't reach its end point, grbl's error 33
+    InvalidTarget,
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SequenceError::Full => write!(f, "sequence full"),
+            SequenceError::OutOfBounds => write!(f, "outside soft limits"),
+            SequenceError::InvalidTarget => write!(f, "invalid arc target"),
+        }
+    }
+}
+
+pub struct Sequence {
+    /// the front is the segment being executed, it's dropped once the next one
+    /// starts so new segments can be streamed in while the machine is moving
+    sequence_list: RingBuffer<SequenceVector, SEQUENCE_CAPACITY>,
+    /// number of segments started since the sequence was last cleared
+    curr_idx: u32,
+
+    sequence_is_running: bool,
+
+    unit_length_x: f32,
+    unit_length_y: f32,
+
+    /// mm/s^2, 0 disables look-ahead and every segment starts from standstill
+    accel: f32,
+    /// mm
+    junction_deviation: f32,
+    /// mm/s^3, 0 when segments run with trapezoidal profiles
+    jerk: f32,
+
+    /// segments leaving these are rejected, `None` until the machine is homed
+    soft_limits: Option<SoftLimits>,
+}
+
+impl Sequence {
+    pub fn new() -> Sequence {
+        let mut sequence_list = RingBuffer::new();
+        // can't fail, the buffer is empty
+        let _ = sequence_list.push_back(SequenceVector::new(
+            0,
+            0,
+            PenPosition::Default,
+            0,
+            0,
+            Interpolation::Linear,
+            0.0,
+            (1.0, 1.0),
+        ));
+
+        Sequence {
+            sequence_list,
+            curr_idx: 0,
+
+            sequence_is_running: false,
+
+            unit_length_x: 1.0,
+            unit_length_y: 1.0,
+
+            accel: 0.0,
+            junction_deviation: 0.0,
+            jerk: 0.0,
+
+            soft_limits: None,
+        }
+    }
+
+    /// mm per encoder unit on each axis, needed to keep arcs circular and to
+    /// measure segment lengths
+    pub fn set_unit_lengths(&mut self, unit_length_x: f32, unit_length_y: f32) {
+        self.unit_length_x = unit_length_x;
+        self.unit_length_y = unit_length_y;
+    }
+
+    /// acceleration (mm/s^2) and junction deviation (mm) used to plan the
+    /// speeds between segments
+    pub fn set_planner_limits(&mut self, accel: f32, junction_deviation: f32) {
+        self.accel = accel;
+        self.junction_deviation = junction_deviation;
+    }
+
+    /// jerk (mm/s^3) the segments are executed with, 0 for trapezoidal profiles.
+    /// jerk limited profiles need more room to change speed, so the queued
+    /// segments are replanned.
+    pub fn set_jerk(&mut self, jerk: f32) {
+        self.jerk = jerk;
+        self.plan();
+    }
+
+    /// envelope the segments added from now on have to stay in
+    pub fn set_soft_limits(&mut self, soft_limits: Option<SoftLimits>) {
+        self.soft_limits = soft_limits;
+    }
+
+    #[inline]
+    pub fn soft_limits(&self) -> Option<SoftLimits> {
+        self.soft_limits
+    }
+
+    /// highest entry speed (mm/s) of a segment `length` mm long that ends at `exit_vel`
+    #[inline]
+    fn max_entry_vel(&self, exit_vel: f32, length: f32) -> f32 {
+        if self.jerk > 0.0 {
+            s_curve::max_entry_velocity(exit_vel, length, self.accel, self.jerk)
+        } else {
+            max_entry_velocity(exit_vel, length, self.accel)
+        }
+    }
+
+    /// queues a segment from the end of the newest one to `(x, y)`, arcs
+    /// included it has to stay within the soft limits
+    #[inline]
+    pub fn add_pos(
+        &mut self,
+        x: i32,
+        y: i32,
+        pen: PenPosition,
+        method: Interpolation,
+        feedrate: f32,
+    ) -> Result<(), SequenceError> {
+        let sqv = self.segment_to(x, y, pen, method, feedrate);
+        if let Some(limits) = self.soft_limits {
+            let (min, max) = sqv.interpolator.bounds();
+            if !limits.contains_box(min, max) {
+                return Err(SequenceError::OutOfBounds);
+            }
+        }
+        self.push(sqv)
+    }
+
+    #[inline]
+    fn segment_to(
+        &self,
+        x: i32,
+        y: i32,
+        pen: PenPosition,
+        method: Interpolation,
+        feedrate: f32,
+    ) -> SequenceVector {
+        // chain onto the newest segment, wherever it sits in the ring
+        let (prev_x, prev_y) = match self.sequence_list.back() {
+            Some(prev) => (prev.end_x(), prev.end_y()),
+            None => (x, y),
+        };
+        let unit_length = (self.unit_length_x, self.unit_length_y);
+        let mut sqv = SequenceVector::new(x, y, pen, prev_x, prev_y, method, feedrate, unit_length);
+        if let Some(prev) = self.sequence_list.back() {
+            sqv.max_entry_vel = self.max_junction_vel(prev, &sqv);
+        }
+        sqv
+    }
+
+    #[inline]
+    fn push(&mut self, sqv: SequenceVector) -> Result<(), SequenceError> {
+        self.sequence_list
+            .push_back(sqv)
+            .map_err(|_| SequenceError::Full)?;
+        self.plan();
+        Ok(())
+    }
+
+    /// speed (mm/s) the tool can keep when going from `prev` into `next`
+    fn max_junction_vel(&self, prev: &SequenceVector, next: &SequenceVector) -> f32 {
+        // the pen only moves at standstill
+        if self.accel <= 0.0 || prev.pen() != next.pen() {
+            return 0.0;
+        }
+
+        let prev_len = prev.interpolator.get_interpolation_len();
+        let prev_dir = self.tangent_mm(prev.interpolator.tangent_at(prev_len));
+        let next_dir = self.tangent_mm(next.interpolator.tangent_at(0));
+
+        let feedrate = prev.feedrate().min(next.feedrate()) / 60.0;
+        junction_velocity(prev_dir, next_dir, self.accel, self.junction_deviation).min(feedrate)
+    }
+
+    #[inline]
+    fn tangent_mm(&self, tangent: (f32, f32)) -> (f32, f32) {
+        (
+            tangent.0 * self.unit_length_x,
+            tangent.1 * self.unit_length_y,
+        )
+    }
+
+    /// recalculates the entry speeds of the queued segments that haven't been
+    /// started yet, everything behind the front. the backward pass makes sure every segment can slow down to
+    /// the entry speed of the next one, with the last one ending at standstill,
+    /// the forward pass that every entry speed can be reached from the previous one.
+    fn plan(&mut self) {
+        let len = self.sequence_list.len();
+        if len < 2 {
+            return;
+        }
+        let last = len - 1;
+        let first = len.saturating_sub(LOOKAHEAD).max(1);
+
+        let mut exit_vel = 0.0;
+        for idx in (first..=last).rev() {
+            let sqv = self.sequence(idx);
+            let entry_vel = sqv
+                .max_entry_vel
+                .min(self.max_entry_vel(exit_vel, sqv.length()));
+            if let Some(sqv) = self.sequence_list.get_mut(idx) {
+                sqv.entry_vel = entry_vel;
+            }
+            exit_vel = entry_vel;
+        }
+
+        for idx in first..=last {
+            let prev = self.sequence(idx - 1);
+            let reachable = self.max_entry_vel(prev.entry_vel, prev.length());
+            if let Some(sqv) = self.sequence_list.get_mut(idx) {
+                sqv.entry_vel = sqv.entry_vel.min(reachable);
+            }
+        }
+    }
+
+    /// `idx` counts from the current segment, panics if it's out of range
+    #[inline]
+    fn sequence(&self, idx: usize) -> SequenceVector {
+        *self.sequence_list.get(idx).unwrap()
+    }
+
+    #[inline]
+    pub fn curr_pos(&self) -> SequenceVector {
+        self.sequence(0)
+    }
+
+    /// planned speed (mm/s) at the end of the current segment
+    #[inline]
+    pub fn exit_vel(&self) -> f32 {
+        self.sequence_list.get(1).map_or(0.0, |sqv| sqv.entry_vel())
+    }
+
+    /// index of the current segment, counted from the first one queued after
+    /// the last clear
+    #[inline]
+    pub fn curr_idx(&self) -> u32 {
+        self.curr_idx
+    }
+
+    /// number of segments that can still be added
+    #[inline]
+    pub fn free_space(&self) -> usize {
+        self.sequence_list.capacity() - self.sequence_list.len()
+    }
+
+    #[inline]
+    pub fn sequence_len(&self) -> usize {
+        self.sequence_list.len()
+    }
+
+    #[inline]
+    pub fn curr_sqv(&self) -> SequenceVector {
+        self.sequence(0)
+    }
+
+    /// drops the current segment and makes the next one current, the current
+    /// one is kept if there is nothing after it so new segments chain onto it
+    #[inline]
+    pub fn advance(&mut self) -> Option<(i32, i32)> {
+        if self.sequence_list.len() < 2 {
+            return None;
+        }
+
+        self.sequence_list.pop_front();
+        self.curr_idx += 1;
+        let sqv = self.curr_pos();
+        Some((sqv.end_x(), sqv.end_y()))
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.sequence_is_running
+    }
+
+    pub fn start_sequence(&mut self) {
+        self.sequence_is_running = true;
+    }
+
+    pub fn stop_sequence(&mut self) {
+        self.sequence_is_running = false;
+    }
+
+    #[inline]
+    pub fn has_free_space(&self) -> bool {
+        !self.sequence_list.is_full()
+    }
+
+    /// panics if `sequence_list` is of length 0
+    pub fn last_pos(&self) -> SequenceVector {
+        *self.sequence_list.back().unwrap()
+    }
+
+    /// drops every segment, new ones chain onto `initial_pos`
+    pub fn clear_sequence(&mut self, initial_pos: (i32, i32)) {
+        let last_pos = self.curr_pos();
+        self.sequence_list.clear();
+        self.curr_idx = 0;
+        // can't fail, the buffer is empty. not checked against the soft limits,
+        // it's where the machine already is
+        let sqv = self.segment_to(
+            initial_pos.0,
+            initial_pos.1,
+            PenPosition::Default,
+            Interpolation::Linear,
+            last_pos.feedrate(),
+        );
+        let _ = self.push(sqv);
+    }
+}
+
+/// struct for storing each printer headpositions
+#[derive(Clone, Copy)]
+pub struct SequenceVector {
+    start_x: i32,
+    start_y: i32,
+    end_x: i32,
+    end_y: i32,
+    pen: PenPosition,
+    /// mm/min
+    feedrate: f32,
+    /// mm
+    length: f32,
+    /// mm/s, limit given by the junction with the previous segment
+    max_entry_vel: f32,
+    /// mm/s, planned speed at the start of the segment
+    entry_vel: f32,
+    pub interpolator: Interpolator,
+}
+
+impl SequenceVector {
+    pub fn new(
+        end_x: i32,
+        end_y: i32,
+        pen: PenPosition,
+        start_x: i32,
+        start_y: i32,
+        method: Interpolation,
+        feedrate: f32,
+        unit_length: (f32, f32),
+    ) -> SequenceVector {
+        let axis_ratio = unit_length.0 / unit_length.1;
+        let interpolator =
+            Interpolator::new((start_x, start_y), (end_x, end_y), method, axis_ratio);
+        let length = interpolator.path_len(unit_length.0, unit_length.1);
+        SequenceVector {
+            start_x,
+            start_y,
+            end_x,
+            end_y,
+            pen,
+            feedrate,
+            length,
+            max_entry_vel: 0.0,
+            entry_vel: 0.0,
+            interpolator,
+        }
+    }
+
+    #[inline]
+    pub fn end_x(&self) -> i32 {
+        self.end_x
+    }
+
+    #[inline]
+    pub fn end_y(&self) -> i32 {
+        self.end_y
+    }
+
+    #[inline]
+    pub fn start(&self) -> (i32, i32) {
+        (self.start_x, self.start_y)
+    }
+
+    #[inline]
+    pub fn end(&self) -> (i32, i32) {
+        (self.end_x, self.end_y)
+    }
+
+    pub fn pen(&self) -> PenPosition {
+        self.pen
+    }
+
+    /// mm/min
+    #[inline]
+    pub fn feedrate(&self) -> f32 {
+        self.feedrate
+    }
+
+    /// mm
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    /// mm/s
+    #[inline]
+    pub fn entry_vel(&self) -> f32 {
+        self.entry_vel
+    }
+}
@@ -0,0 +1,569 @@
+use crate::interpolator::{CircularInterpolationDir, Interpolation};
+use crate::pen::PenPosition;
+use crate::sequence::SequenceVector;
+use crate::sequence::{Sequence, SequenceError};
+use crate::settings::DEFAULTS;
+
+use core::fmt;
+use micromath::F32Ext;
+
+use crate::math::sqrt;
+
+const MM_PER_INCH: f32 = 25.4;
+
+/// mm/min, used until the first F word
+pub const DEFAULT_FEEDRATE: f32 = 1000.0;
+/// mm/min, G00 moves ignore the modal feedrate
+pub const RAPID_FEEDRATE: f32 = 3000.0;
+/// share of the squared chord `4r^2 - chord^2` may fall short by and still
+/// count as a half circle, the target is hardly ever exactly a diameter away
+const ARC_RADIUS_TOLERANCE: f32 = 1e-4;
+
+/// G20/G21 modal state
+#[derive(Clone, Copy, PartialEq)]
+pub enum Units {
+    Millimetres,
+    Inches,
+}
+
+impl Units {
+    #[inline]
+    pub fn to_mm(self, value: f32) -> f32 {
+        match self {
+            Units::Millimetres => value,
+            Units::Inches => value * MM_PER_INCH,
+        }
+    }
+
+    #[inline]
+    pub fn from_mm(self, value: f32) -> f32 {
+        match self {
+            Units::Millimetres => value,
+            Units::Inches => value / MM_PER_INCH,
+        }
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Units::Millimetres => write!(f, "mm"),
+            Units::Inches => write!(f, "inch"),
+        }
+    }
+}
+
+/// G90/G91 modal state
+#[derive(Clone, Copy, PartialEq)]
+pub enum PositioningMode {
+    Absolute,
+    Relative,
+}
+
+pub struct SequenceWrapper {
+    unit_length_x: f32,
+    unit_length_y: f32,
+    pub sequence: Sequence,
+    pen_pos: PenPosition,
+    home_pos: (i32, i32),
+
+    positioning: PositioningMode,
+    units: Units,
+    /// mm/min
+    feedrate: f32,
+    /// mm/min, every move is clamped to it
+    max_feedrate: f32,
+    /// programmed position (mm, relative to home) at the end of the last queued move
+    prog_pos: (f32, f32),
+}
+
+impl SequenceWrapper {
+    /// default home position is (0, 0)
+    pub fn new() -> Self {
+        let home_pos = (0, 0);
+        let (unit_length_x, unit_length_y) = DEFAULTS.unit_length;
+
+        let mut sequence = Sequence::new();
+        sequence.set_unit_lengths(unit_length_x, unit_length_y);
+
+        Self {
+            // unit_length: 0.021,
+            unit_length_y,
+            unit_length_x,
+            sequence,
+            pen_pos: PenPosition::Default,
+            home_pos,
+
+            positioning: PositioningMode::Absolute,
+            units: Units::Millimetres,
+            feedrate: DEFAULT_FEEDRATE,
+            max_feedrate: DEFAULTS.max_speed * 60.0,
+            prog_pos: (0.0, 0.0),
+        }
+    }
+
+    #[inline]
+    pub fn set_positioning(&mut self, mode: PositioningMode) {
+        self.positioning = mode;
+    }
+
+    #[inline]
+    pub fn positioning(&self) -> PositioningMode {
+        self.positioning
+    }
+
+    /// unit that all programmed values (positions, arc offsets and radii) are given in
+    #[inline]
+    pub fn set_units(&mut self, units: Units) {
+        self.units = units;
+    }
+
+    #[inline]
+    pub fn units(&self) -> Units {
+        self.units
+    }
+
+    /// F word, in the active unit per minute
+    #[inline]
+    pub fn set_feedrate(&mut self, feedrate: f32) {
+        if feedrate > 0.0 {
+            self.feedrate = self.units.to_mm(feedrate);
+        }
+    }
+
+    /// mm/min
+    #[inline]
+    pub fn feedrate(&self) -> f32 {
+        self.feedrate
+    }
+
+    /// mm/min, moves queued from now on never go faster, rapids included
+    #[inline]
+    pub fn set_max_feedrate(&mut self, feedrate: f32) {
+        self.max_feedrate = feedrate;
+    }
+
+    /// G92, redefines the programmed position at the end of the last queued move
+    /// without moving. axes that are `None` keep their current offset.
+    pub fn set_pos(&mut self, x: Option<f32>, y: Option<f32>) {
+        let last_pos = self.sequence.last_pos();
+
+        if let Some(x) = x {
+            let x = self.units.to_mm(x);
+            self.home_pos.0 = last_pos.end_x() - self.mm_to_unit_x(x).round() as i32;
+            self.prog_pos.0 = x;
+        }
+
+        if let Some(y) = y {
+            let y = self.units.to_mm(y);
+            self.home_pos.1 = last_pos.end_y() - self.mm_to_unit_y(y).round() as i32;
+            self.prog_pos.1 = y;
+        }
+    }
+
+    /// values of X and Y words that leave the position unchanged in the current
+    /// positioning mode, for axes missing from a G-code block
+    #[inline]
+    pub fn unchanged_pos(&self) -> (f32, f32) {
+        match self.positioning {
+            PositioningMode::Absolute => (
+                self.units.from_mm(self.prog_pos.0),
+                self.units.from_mm(self.prog_pos.1),
+            ),
+            PositioningMode::Relative => (0.0, 0.0),
+        }
+    }
+
+    /// resolves a programmed x value to an absolute position in mm relative to home
+    #[inline]
+    fn abs_x(&self, x: f32) -> f32 {
+        let x = self.units.to_mm(x);
+        match self.positioning {
+            PositioningMode::Absolute => x,
+            PositioningMode::Relative => self.prog_pos.0 + x,
+        }
+    }
+
+    /// resolves a programmed y value to an absolute position in mm relative to home
+    #[inline]
+    fn abs_y(&self, y: f32) -> f32 {
+        let y = self.units.to_mm(y);
+        match self.positioning {
+            PositioningMode::Absolute => y,
+            PositioningMode::Relative => self.prog_pos.1 + y,
+        }
+    }
+
+    #[inline]
+    pub fn pen_pos(&mut self, angle: f32) {
+        let angle = angle.clamp(0.0, 255.0).ceil() as u8;
+        self.pen_pos = PenPosition::Angle(angle);
+    }
+
+    #[inline]
+    pub fn set_home(&mut self, x: f32, y: f32) {
+        let x = self.mm_to_unit_x(x).round() as i32;
+        let y = self.mm_to_unit_y(y).round() as i32;
+
+        self.home_pos = (x, y);
+    }
+
+    /// (mm relative to home) to encoder units
+    #[inline]
+    fn mm_to_pos(&self, pos: (f32, f32)) -> (i32, i32) {
+        (
+            self.mm_to_unit_x(pos.0).round() as i32 + self.home_pos.0,
+            self.mm_to_unit_y(pos.1).round() as i32 + self.home_pos.1,
+        )
+    }
+
+    /// queues a move to `(x, y)` in encoder units, the programmed position
+    /// only moves on to `prog_pos` if the sequence takes it
+    #[inline]
+    fn queue(
+        &mut self,
+        prog_pos: (f32, f32),
+        x: i32,
+        y: i32,
+        method: Interpolation,
+        feedrate: f32,
+    ) -> Result<(), SequenceError> {
+        let feedrate = feedrate.min(self.max_feedrate);
+        self.sequence
+            .add_pos(x, y, self.pen_pos, method, feedrate)?;
+        self.prog_pos = prog_pos;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn pos(&mut self, x: f32, y: f32) -> Result<(), SequenceError> {
+        let prog_pos = (self.abs_x(x), self.abs_y(y));
+        let (x, y) = self.mm_to_pos(prog_pos);
+        self.queue(prog_pos, x, y, Interpolation::Linear, self.feedrate)
+    }
+
+    #[inline]
+    pub fn pos_rapid(&mut self, x: f32, y: f32) -> Result<(), SequenceError> {
+        let prog_pos = (self.abs_x(x), self.abs_y(y));
+        let (x, y) = self.mm_to_pos(prog_pos);
+        self.queue(
+            prog_pos,
+            x,
+            y,
+            Interpolation::NoInterpolation,
+            RAPID_FEEDRATE,
+        )
+    }
+
+    #[inline]
+    pub fn pos_x(&mut self, x: f32) -> Result<(), SequenceError> {
+        let prog_pos = (self.abs_x(x), self.prog_pos.1);
+        let x = self.mm_to_pos(prog_pos).0;
+        let y = self.sequence.last_pos().end_y();
+        self.queue(prog_pos, x, y, Interpolation::Linear, self.feedrate)
+    }
+
+    #[inline]
+    pub fn pos_y(&mut self, y: f32) -> Result<(), SequenceError> {
+        let prog_pos = (self.prog_pos.0, self.abs_y(y));
+        let y = self.mm_to_pos(prog_pos).1;
+        let x = self.sequence.last_pos().end_x();
+        self.queue(prog_pos, x, y, Interpolation::Linear, self.feedrate)
+    }
+
+    #[inline]
+    pub fn pos_x_rapid(&mut self, x: f32) -> Result<(), SequenceError> {
+        let prog_pos = (self.abs_x(x), self.prog_pos.1);
+        let x = self.mm_to_pos(prog_pos).0;
+        let y = self.sequence.last_pos().end_y();
+        self.queue(
+            prog_pos,
+            x,
+            y,
+            Interpolation::NoInterpolation,
+            RAPID_FEEDRATE,
+        )
+    }
+
+    #[inline]
+    pub fn pos_y_rapid(&mut self, y: f32) -> Result<(), SequenceError> {
+        let prog_pos = (self.prog_pos.0, self.abs_y(y));
+        let y = self.mm_to_pos(prog_pos).1;
+        let x = self.sequence.last_pos().end_x();
+        self.queue(
+            prog_pos,
+            x,
+            y,
+            Interpolation::NoInterpolation,
+            RAPID_FEEDRATE,
+        )
+    }
+
+    #[inline]
+    pub fn arc_absolute_center(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+        dir: CircularInterpolationDir,
+    ) -> Result<(), SequenceError> {
+        let prog_pos = (self.abs_x(x), self.abs_y(y));
+        let (x, y) = self.mm_to_pos(prog_pos);
+
+        let i = self.mm_to_unit_x(self.units.to_mm(i));
+        let j = self.mm_to_unit_y(self.units.to_mm(j));
+        let (i, j) = (i + self.home_pos.0 as f32, j + self.home_pos.1 as f32);
+
+        let last_pos = self.sequence.last_pos();
+        let last_x = last_pos.end_x() as f32;
+        let last_y = last_pos.end_y() as f32;
+        self.queue(
+            prog_pos,
+            x,
+            y,
+            Interpolation::Circular(i - last_x, j - last_y, dir),
+            self.feedrate,
+        )
+    }
+
+    #[inline]
+    pub fn arc_clockwise_absolute_center(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+    ) -> Result<(), SequenceError> {
+        self.arc_absolute_center(x, y, i, j, CircularInterpolationDir::Clockwise)
+    }
+
+    #[inline]
+    pub fn arc_counter_clockwise_absolute_center(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+    ) -> Result<(), SequenceError> {
+        self.arc_absolute_center(x, y, i, j, CircularInterpolationDir::CounterClockwise)
+    }
+
+    #[inline]
+    pub fn arc_relative_center(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+        dir: CircularInterpolationDir,
+    ) -> Result<(), SequenceError> {
+        let (x, y) = (self.abs_x(x), self.abs_y(y));
+        let (i, j) = (self.units.to_mm(i), self.units.to_mm(j));
+        self.add_arc(x, y, i, j, dir)
+    }
+
+    /// all values in mm, `x` and `y` absolute, `i` and `j` relative to the arc's start
+    #[inline]
+    fn add_arc(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+        dir: CircularInterpolationDir,
+    ) -> Result<(), SequenceError> {
+        let prog_pos = (x, y);
+        let (x, y) = self.mm_to_pos(prog_pos);
+
+        let i = self.mm_to_unit_x(i);
+        let j = self.mm_to_unit_y(j);
+
+        self.queue(
+            prog_pos,
+            x,
+            y,
+            Interpolation::Circular(i, j, dir),
+            self.feedrate,
+        )
+    }
+
+    #[inline]
+    pub fn arc_clockwise_relative_center(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+    ) -> Result<(), SequenceError> {
+        self.arc_relative_center(x, y, i, j, CircularInterpolationDir::Clockwise)
+    }
+
+    #[inline]
+    pub fn arc_counter_clockwise_relative_center(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+    ) -> Result<(), SequenceError> {
+        self.arc_relative_center(x, y, i, j, CircularInterpolationDir::CounterClockwise)
+    }
+
+    /// arc given by its radius instead of its center (G02/G03 `R` form).
+    /// a negative radius selects the arc spanning more than 180 degrees.
+    /// `Err(InvalidTarget)` if the target is the current position or further
+    /// away than the diameter.
+    #[inline]
+    pub fn arc_radius(
+        &mut self,
+        x: f32,
+        y: f32,
+        r: f32,
+        dir: CircularInterpolationDir,
+    ) -> Result<(), SequenceError> {
+        let (x, y) = (self.abs_x(x), self.abs_y(y));
+        let r = self.units.to_mm(r);
+        let (dx, dy) = (x - self.prog_pos.0, y - self.prog_pos.1);
+        let chord = sqrt(dx * dx + dy * dy);
+
+        if chord == 0.0 {
+            // a full circle can't be described by a radius
+            return Err(SequenceError::InvalidTarget);
+        }
+
+        // distance of the center from the chord's midpoint, scaled by 2 / chord.
+        // slightly negative values are rounding errors on a half circle, more
+        // than that means the radius is too small to get there.
+        let h_squared = 4.0 * r * r - chord * chord;
+        if h_squared < -ARC_RADIUS_TOLERANCE * chord * chord {
+            return Err(SequenceError::InvalidTarget);
+        }
+        let h = sqrt(h_squared.max(0.0));
+        let mut h_x2_div_d = -h / chord;
+
+        if let CircularInterpolationDir::CounterClockwise = dir {
+            h_x2_div_d = -h_x2_div_d;
+        }
+        if r < 0.0 {
+            h_x2_div_d = -h_x2_div_d;
+        }
+
+        let i = 0.5 * (dx - (dy * h_x2_div_d));
+        let j = 0.5 * (dy + (dx * h_x2_div_d));
+
+        self.add_arc(x, y, i, j, dir)
+    }
+
+    #[inline]
+    pub fn arc_clockwise_radius(&mut self, x: f32, y: f32, r: f32) -> Result<(), SequenceError> {
+        self.arc_radius(x, y, r, CircularInterpolationDir::Clockwise)
+    }
+
+    #[inline]
+    pub fn arc_counter_clockwise_radius(
+        &mut self,
+        x: f32,
+        y: f32,
+        r: f32,
+    ) -> Result<(), SequenceError> {
+        self.arc_radius(x, y, r, CircularInterpolationDir::CounterClockwise)
+    }
+
+    // aliases --------------------------------------
+    #[inline]
+    /// alias for `arc_clockwise_relative_center`
+    pub fn arc_clockwise(&mut self, x: f32, y: f32, i: f32, j: f32) -> Result<(), SequenceError> {
+        self.arc_clockwise_relative_center(x, y, i, j)
+    }
+
+    #[inline]
+    /// alias for `arc_counter_clockwise_relative_center`
+    pub fn arc_counter_clockwise(
+        &mut self,
+        x: f32,
+        y: f32,
+        i: f32,
+        j: f32,
+    ) -> Result<(), SequenceError> {
+        self.arc_counter_clockwise_relative_center(x, y, i, j)
+    }
+    // -------------------------------------------------
+
+    /// mm per encoder unit on the x and y axis
+    #[inline]
+    pub fn unit_lengths(&self) -> (f32, f32) {
+        (self.unit_length_x, self.unit_length_y)
+    }
+
+    /// encoder positions that are already queued or stored keep their units,
+    /// so this is only meant for an empty sequence before homing
+    pub fn set_unit_lengths(&mut self, unit_length_x: f32, unit_length_y: f32) {
+        self.unit_length_x = unit_length_x;
+        self.unit_length_y = unit_length_y;
+        self.sequence.set_unit_lengths(unit_length_x, unit_length_y);
+    }
+
+    #[inline]
+    pub fn mm_to_unit_x(&self, value: f32) -> f32 {
+        value / self.unit_length_x
+    }
+
+    #[inline]
+    pub fn mm_to_unit_y(&self, value: f32) -> f32 {
+        value / self.unit_length_y
+    }
+
+    #[inline]
+    pub fn unit_to_mm_x(&self, value: f32) -> f32 {
+        value * self.unit_length_x
+    }
+
+    #[inline]
+    pub fn unit_to_mm_y(&self, value: f32) -> f32 {
+        value * self.unit_length_y
+    }
+
+    /// encoder position to mm relative to home
+    #[inline]
+    pub fn pos_to_mm(&self, pos: (i32, i32)) -> (f32, f32) {
+        (
+            self.unit_to_mm_x((pos.0 - self.home_pos.0) as f32),
+            self.unit_to_mm_y((pos.1 - self.home_pos.1) as f32),
+        )
+    }
+
+    #[inline]
+    pub fn start(&mut self) {
+        self.sequence.start_sequence()
+    }
+
+    #[inline]
+    pub fn stop(&mut self) {
+        self.sequence.stop_sequence()
+    }
+
+    /// drops every queued move and continues from `curr_pos` (encoder units)
+    /// with the pen up
+    #[inline]
+    pub fn clear(&mut self, curr_pos: (i32, i32)) {
+        self.sequence.clear_sequence(curr_pos);
+        self.pen_pos = PenPosition::Default;
+        self.prog_pos = self.pos_to_mm(curr_pos);
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.sequence.is_running()
+    }
+
+    #[inline]
+    pub fn curr_pos(&self) -> SequenceVector {
+        self.sequence.curr_pos()
+    }
+
+    #[inline]
+    pub fn advance(&mut self) -> Option<(i32, i32)> {
+        self.sequence.advance()
+    }
+}
@@ -0,0 +1,362 @@
+//! machine settings that used to be compile time constants, and the block
+//! they're stored in. a block is
+//!
+//! `[magic (4)] [version (2)] [payload len (2)] [payload..] [crc32 (4)]`
+//!
+//! all little endian, the crc covers everything in front of it. the payload is
+//! the settings in the order `Settings::fields` visits them. new settings are
+//! only ever appended, so a block written by an older firmware is just shorter
+//! and whatever it's missing keeps its default. `VERSION` only goes up when
+//! the meaning of a stored value changes, `migrate` converts older payloads.
+
+use core::fmt;
+
+use crate::homing::HomingTrigger;
+use crate::velocity_controller::PidConfig;
+
+/// "PLTS"
+pub const MAGIC: u32 = 0x5354_4C50;
+pub const VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 8;
+const CRC_LEN: usize = 4;
+/// upper bound of an encoded block
+pub const MAX_BLOCK_LEN: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Settings {
+    /// mm per encoder unit on x and y
+    pub unit_length: (f32, f32),
+    /// mm/s, an axis lagging behind the path is always driven at least this fast
+    pub min_speed: f32,
+    /// mm/s, feedrates above this are clamped
+    pub max_speed: f32,
+    /// mm/s^2, along the path
+    pub acceleration: f32,
+    /// mm, how far the path may cut a corner when the tool keeps moving through it
+    pub junction_deviation: f32,
+    /// mm/s^3, along the path when running s-curve profiles
+    pub jerk: f32,
+    pub x_pid: PidConfig,
+    pub y_pid: PidConfig,
+    /// servo angle the pen is lifted to
+    pub pen_up_angle: u8,
+    /// Hz of the motor pwm on x and y, applied on the next boot
+    pub pwm_freq: (u32, u32),
+    /// mm from the homing trigger points
+    pub soft_limits_min: (f32, f32),
+    pub soft_limits_max: (f32, f32),
+    /// mm from the homing trigger points to the program origin
+    pub home_offset: (f32, f32),
+    /// network settings, applied on the next boot. an all zero mac is
+    /// derived from the device id.
+    pub mac: [u8; 6],
+    /// used without dhcp, or when no lease arrives
+    pub ip: [u8; 4],
+    pub port: u16,
+    /// where replies go until the first command arrives
+    pub remote_ip: [u8; 4],
+    pub remote_port: u16,
+    /// ms motion waits after the pen was lifted or lowered, 0 goes on right away
+    pub pen_up_dwell: u32,
+    pub pen_down_dwell: u32,
+    pub dhcp: bool,
+    /// what ends the homing seek on x and y
+    pub homing_trigger: (HomingTrigger, HomingTrigger),
+    /// duty cycle (%) on x and y while homing looks for the trigger
+    pub homing_seek_duty: (f32, f32),
+    /// duty cycle (%) on x and y while homing backs off and approaches again
+    pub homing_locate_duty: (f32, f32),
+    /// mm x and y back off after the first trigger
+    pub homing_back_off: (f32, f32),
+}
+
+/// what the firmware used before there were stored settings
+pub const DEFAULTS: Settings = Settings {
+    unit_length: (0.042, 0.0105),
+    min_speed: 1.0,
+    max_speed: 50.0,
+    acceleration: 200.0,
+    junction_deviation: 0.05,
+    jerk: 4000.0,
+    // speeds are in mm/s
+    x_pid: PidConfig {
+        kp: 1.0,
+        ki: 4.0,
+        kd: 0.0,
+        kff: 1.0,
+        min_duty: 20.0,
+        max_duty: 100.0,
+    },
+    y_pid: PidConfig {
+        kp: 0.8,
+        ki: 3.0,
+        kd: 0.0,
+        kff: 0.8,
+        min_duty: 10.0,
+        max_duty: 100.0,
+    },
+    pen_up_angle: 60,
+    pwm_freq: (200, 60),
+    // roughly an a4 sheet with some room around it
+    soft_limits_min: (0.0, 0.0),
+    soft_limits_max: (230.0, 310.0),
+    home_offset: (0.0, 0.0),
+    mac: [0; 6],
+    ip: [192, 168, 20, 99],
+    port: 1234,
+    remote_ip: [192, 168, 20, 114],
+    remote_port: 34254,
+    pen_up_dwell: 0,
+    pen_down_dwell: 0,
+    dhcp: true,
+    // both axes used to stall against the frame
+    homing_trigger: (HomingTrigger::Stall, HomingTrigger::Stall),
+    homing_seek_duty: (80.0, 60.0),
+    homing_locate_duty: (30.0, 25.0),
+    homing_back_off: (5.0, 5.0),
+};
+
+impl Default for Settings {
+    fn default() -> Self {
+        DEFAULTS
+    }
+}
+
+/// where settings are kept across reboots
+pub trait SettingsStorage {
+    /// `Err(())` if they couldn't be written
+    fn save(&mut self, settings: &Settings) -> Result<(), ()>;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingsError {
+    /// nothing stored, erased flash
+    Blank,
+    /// the block is cut off or its crc doesn't match
+    Corrupt,
+    /// written by a newer firmware
+    Unsupported(u16),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Blank => write!(f, "no settings stored"),
+            SettingsError::Corrupt => write!(f, "settings corrupt"),
+            SettingsError::Unsupported(version) => {
+                write!(f, "settings version {} unsupported", version)
+            }
+        }
+    }
+}
+
+/// one visit per stored value, `Settings::fields` is the single place that
+/// defines the payload layout
+trait Field {
+    fn bytes(&mut self, value: &mut [u8]);
+
+    fn u8(&mut self, value: &mut u8) {
+        let mut bytes = [*value];
+        self.bytes(&mut bytes);
+        *value = bytes[0];
+    }
+
+    fn u16(&mut self, value: &mut u16) {
+        let mut bytes = value.to_le_bytes();
+        self.bytes(&mut bytes);
+        *value = u16::from_le_bytes(bytes);
+    }
+
+    fn u32(&mut self, value: &mut u32) {
+        let mut bytes = value.to_le_bytes();
+        self.bytes(&mut bytes);
+        *value = u32::from_le_bytes(bytes);
+    }
+
+    fn f32(&mut self, value: &mut f32) {
+        let mut bytes = value.to_le_bytes();
+        self.bytes(&mut bytes);
+        *value = f32::from_le_bytes(bytes);
+    }
+
+    fn flag(&mut self, value: &mut bool) {
+        let mut byte = *value as u8;
+        self.u8(&mut byte);
+        *value = byte != 0;
+    }
+
+    /// one byte, 1 for the limit switch
+    fn trigger(&mut self, value: &mut HomingTrigger) {
+        let mut switch = *value == HomingTrigger::LimitSwitch;
+        self.flag(&mut switch);
+        *value = if switch {
+            HomingTrigger::LimitSwitch
+        } else {
+            HomingTrigger::Stall
+        };
+    }
+
+    fn pair(&mut self, value: &mut (f32, f32)) {
+        self.f32(&mut value.0);
+        self.f32(&mut value.1);
+    }
+
+    fn pid(&mut self, value: &mut PidConfig) {
+        self.f32(&mut value.kp);
+        self.f32(&mut value.ki);
+        self.f32(&mut value.kd);
+        self.f32(&mut value.kff);
+        self.f32(&mut value.min_duty);
+        self.f32(&mut value.max_duty);
+    }
+}
+
+/// copies every value into the buffer
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Field for Writer<'_> {
+    fn bytes(&mut self, value: &mut [u8]) {
+        let end = self.pos + value.len();
+        self.buf[self.pos..end].copy_from_slice(value);
+        self.pos = end;
+    }
+}
+
+/// overwrites values while the payload lasts, the rest keep what they had
+struct Reader<'a> {
+    payload: &'a [u8],
+    pos: usize,
+}
+
+impl Field for Reader<'_> {
+    fn bytes(&mut self, value: &mut [u8]) {
+        let end = self.pos + value.len();
+        if end <= self.payload.len() {
+            value.copy_from_slice(&self.payload[self.pos..end]);
+        }
+        self.pos = end;
+    }
+}
+
+impl Settings {
+    /// append new values at the end, never reorder or remove them
+    fn fields(&mut self, f: &mut impl Field) {
+        f.pair(&mut self.unit_length);
+        f.f32(&mut self.min_speed);
+        f.f32(&mut self.max_speed);
+        f.f32(&mut self.acceleration);
+        f.f32(&mut self.junction_deviation);
+        f.f32(&mut self.jerk);
+        f.pid(&mut self.x_pid);
+        f.pid(&mut self.y_pid);
+        f.u8(&mut self.pen_up_angle);
+        f.u32(&mut self.pwm_freq.0);
+        f.u32(&mut self.pwm_freq.1);
+        f.pair(&mut self.soft_limits_min);
+        f.pair(&mut self.soft_limits_max);
+        f.pair(&mut self.home_offset);
+        f.bytes(&mut self.mac);
+        f.bytes(&mut self.ip);
+        f.u16(&mut self.port);
+        f.bytes(&mut self.remote_ip);
+        f.u16(&mut self.remote_port);
+        f.u32(&mut self.pen_up_dwell);
+        f.u32(&mut self.pen_down_dwell);
+        f.flag(&mut self.dhcp);
+        f.trigger(&mut self.homing_trigger.0);
+        f.trigger(&mut self.homing_trigger.1);
+        f.pair(&mut self.homing_seek_duty);
+        f.pair(&mut self.homing_locate_duty);
+        f.pair(&mut self.homing_back_off);
+    }
+
+    /// writes the block to the start of `buf`, which needs `MAX_BLOCK_LEN`
+    /// bytes. returns the length of the block.
+    pub fn encode(&self, buf: &mut [u8]) -> usize {
+        let mut settings = *self;
+        let mut writer = Writer {
+            buf: &mut buf[HEADER_LEN..],
+            pos: 0,
+        };
+        settings.fields(&mut writer);
+        let payload_len = writer.pos;
+
+        buf[0..4].copy_from_slice(&MAGIC.to_le_bytes());
+        buf[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        buf[6..8].copy_from_slice(&(payload_len as u16).to_le_bytes());
+        let crc_at = HEADER_LEN + payload_len;
+        let crc = crc32(&buf[..crc_at]);
+        buf[crc_at..crc_at + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+
+        crc_at + CRC_LEN
+    }
+
+    /// reads a block from the start of `data`, anything after it is ignored
+    pub fn decode(data: &[u8]) -> Result<Self, SettingsError> {
+        if data.len() < HEADER_LEN {
+            return Err(SettingsError::Corrupt);
+        }
+        let magic = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if magic != MAGIC {
+            return if data[..HEADER_LEN].iter().all(|b| *b == 0xFF) {
+                Err(SettingsError::Blank)
+            } else {
+                Err(SettingsError::Corrupt)
+            };
+        }
+
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        let payload_len = u16::from_le_bytes([data[6], data[7]]) as usize;
+        let crc_at = HEADER_LEN + payload_len;
+        if crc_at + CRC_LEN > data.len().min(MAX_BLOCK_LEN) {
+            return Err(SettingsError::Corrupt);
+        }
+        let crc = &data[crc_at..crc_at + CRC_LEN];
+        if crc32(&data[..crc_at]).to_le_bytes() != crc {
+            return Err(SettingsError::Corrupt);
+        }
+        if version > VERSION {
+            return Err(SettingsError::Unsupported(version));
+        }
+
+        let mut settings = DEFAULTS;
+        settings.fields(&mut Reader {
+            payload: &data[HEADER_LEN..crc_at],
+            pos: 0,
+        });
+        settings.migrate(version);
+        Ok(settings)
+    }
+
+    /// brings values stored by an older `VERSION` up to date
+    fn migrate(&mut self, version: u16) {
+        // nothing older than the first version yet, conversions go here as
+        // `if version < 2 { .. }`
+        let _ = version;
+    }
+}
+
+/// crc-32 (ieee 802.3), the one zip and ethernet use
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// locally administered unicast mac from the 96 bit unique device id, the
+/// same for every boot of the same chip
+pub fn derived_mac(uid: &[u8; 12]) -> [u8; 6] {
+    let crc = crc32(uid).to_le_bytes();
+    let fold = uid.iter().fold(0, |acc, byte| acc ^ byte);
+    [0x02, fold, crc[0], crc[1], crc[2], crc[3]]
+}
@@ -0,0 +1,53 @@
+//! the machine envelope. moves are checked against it before they're queued,
+//! and the encoders while the machine is moving.
+
+use micromath::F32Ext;
+
+/// axis aligned box in encoder units, relative to where the encoders were
+/// zeroed by homing
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SoftLimits {
+    pub min: (i32, i32),
+    pub max: (i32, i32),
+}
+
+impl SoftLimits {
+    pub fn new(min: (i32, i32), max: (i32, i32)) -> Self {
+        Self { min, max }
+    }
+
+    /// envelope given in mm from the homing trigger points, `unit_length` is
+    /// mm per encoder unit on each axis
+    pub fn from_mm(min: (f32, f32), max: (f32, f32), unit_length: (f32, f32)) -> Self {
+        // rounded towards the inside of the envelope
+        Self {
+            min: (
+                (min.0 / unit_length.0).ceil() as i32,
+                (min.1 / unit_length.1).ceil() as i32,
+            ),
+            max: (
+                (max.0 / unit_length.0).floor() as i32,
+                (max.1 / unit_length.1).floor() as i32,
+            ),
+        }
+    }
+
+    /// the same envelope grown by `margin` encoder units on every side
+    pub fn grow(&self, margin: (i32, i32)) -> Self {
+        Self {
+            min: (self.min.0 - margin.0, self.min.1 - margin.1),
+            max: (self.max.0 + margin.0, self.max.1 + margin.1),
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, pos: (i32, i32)) -> bool {
+        pos.0 >= self.min.0 && pos.0 <= self.max.0 && pos.1 >= self.min.1 && pos.1 <= self.max.1
+    }
+
+    /// whether the box from `min` to `max` lies completely inside
+    #[inline]
+    pub fn contains_box(&self, min: (i32, i32), max: (i32, i32)) -> bool {
+        self.contains(min) && self.contains(max)
+    }
+}
@@ -1,74 +1,70 @@
-use crate::opto_encoder::Encoder;
-use crate::timestamp;
+use crate::clock::Clock;
+use crate::encoder::Encoder;
 
 trait U64Time {
-    fn sec(self) -> u64;
     fn millis(self) -> u64;
-    fn micros(self) -> u64;
 }
 
 impl U64Time for u64 {
-    fn sec(self) -> Self {
-        self * 100_000
-    }
     fn millis(self) -> Self {
         self * 1000
     }
-    fn micros(self) -> Self {
-        self
-    }
 }
 
-pub struct DynamicSpeedCalculator<ENC: Encoder> {
+pub struct DynamicSpeedCalculator<ENC: Encoder, C: Clock> {
     encoder: ENC,
+    clock: C,
     speed: f32,
     sampling_interval: u64,
     last_sample_time: u64,
     last_sample_pos: i32,
 }
 
-impl<ENC: Encoder> DynamicSpeedCalculator<ENC> {
-    pub fn new(encoder: ENC) -> Self {
+impl<ENC: Encoder, C: Clock> DynamicSpeedCalculator<ENC, C> {
+    pub fn new(encoder: ENC, clock: C) -> Self {
+        let now = clock.now();
         Self {
             encoder,
+            clock,
             speed: 0.0,
             sampling_interval: 100.millis(),
-            last_sample_time: timestamp(),
+            last_sample_time: now,
             last_sample_pos: 0,
         }
     }
 
     #[inline]
     pub fn tick(&mut self) {
-        if timestamp() - self.last_sample_time >= self.sampling_interval {
+        let now = self.clock.now();
+        if now - self.last_sample_time >= self.sampling_interval {
             let speed = (self.encoder.pos() - self.last_sample_pos) as f32
-                / (timestamp() - self.last_sample_time) as f32;
+                / (now - self.last_sample_time) as f32;
 
             self.speed = speed;
-            if speed / (self.sampling_interval as f32 / 1000_000.0) < 1.0 {
+            if speed / (self.sampling_interval as f32 / 1_000_000.0) < 1.0 {
                 if self.sampling_interval < 500.millis() {
                     self.sampling_interval += 10_000;
                     if self.sampling_interval > 500.millis() {
                         self.sampling_interval = 500.millis()
                     }
                 }
-            } else if speed / (self.sampling_interval as f32 / 1000_000.0) > 1000.0 {
-                if self.sampling_interval > 1.millis() {
-                    self.sampling_interval -= 1_000;
-                    if self.sampling_interval < 1.millis() {
-                        self.sampling_interval = 1.millis()
-                    }
+            } else if speed / (self.sampling_interval as f32 / 1_000_000.0) > 1000.0
+                && self.sampling_interval > 1.millis()
+            {
+                self.sampling_interval -= 1_000;
+                if self.sampling_interval < 1.millis() {
+                    self.sampling_interval = 1.millis()
                 }
             }
 
             self.last_sample_pos = self.encoder.pos();
-            self.last_sample_time = timestamp();
+            self.last_sample_time = now;
         }
     }
 
     #[inline]
     pub fn speed(&self) -> f32 {
-        self.speed / (self.sampling_interval as f32 / 1000_000.0)
+        self.speed / (self.sampling_interval as f32 / 1_000_000.0)
     }
 
     #[inline]
@@ -77,18 +73,21 @@ impl<ENC: Encoder> DynamicSpeedCalculator<ENC> {
     }
 }
 
-pub struct PulseContedSpeedCalc<ENC: Encoder> {
+pub struct PulseContedSpeedCalc<ENC: Encoder, C: Clock> {
     encoder: ENC,
+    clock: C,
     last_sample_time: u64,
     last_pos: i32,
     speed: f32,
 }
 
-impl<ENC: Encoder> PulseContedSpeedCalc<ENC> {
-    pub fn new(encoder: ENC) -> Self {
+impl<ENC: Encoder, C: Clock> PulseContedSpeedCalc<ENC, C> {
+    pub fn new(encoder: ENC, clock: C) -> Self {
+        let now = clock.now();
         Self {
             encoder,
-            last_sample_time: timestamp(),
+            clock,
+            last_sample_time: now,
             last_pos: 0,
             speed: 0.0,
         }
@@ -96,13 +95,14 @@ impl<ENC: Encoder> PulseContedSpeedCalc<ENC> {
 
     #[inline]
     pub fn tick(&mut self, pwm_speed: f32) {
+        let now = self.clock.now();
         if self.encoder.pos() != self.last_pos {
             self.speed = (self.encoder.pos() - self.last_pos) as f32
-                / ((timestamp() - self.last_sample_time) as f32 / 1000_000.0);
-            self.last_sample_time = timestamp();
+                / ((now - self.last_sample_time) as f32 / 1_000_000.0);
+            self.last_sample_time = now;
             self.last_pos = self.encoder.pos();
-        } else if (timestamp() - self.last_sample_time > 100.millis() && pwm_speed <= 10.0)
-            || timestamp() - self.last_sample_time > 200.millis()
+        } else if (now - self.last_sample_time > 100.millis() && pwm_speed <= 10.0)
+            || now - self.last_sample_time > 200.millis()
         {
             self.speed = 0.0
         }
@@ -0,0 +1,260 @@
+use core::fmt;
+
+use crate::math::sqrt;
+use crate::s_curve::SCurveProfile;
+
+/// shape of the velocity profiles segments are planned with
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProfileKind {
+    Trapezoidal,
+    /// jerk limited, smoother but slower to change speed
+    SCurve,
+}
+
+impl fmt::Display for ProfileKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileKind::Trapezoidal => write!(f, "trapezoidal"),
+            ProfileKind::SCurve => write!(f, "s-curve"),
+        }
+    }
+}
+
+/// velocity profile of a segment of either kind
+#[derive(Clone, Copy)]
+pub enum MotionProfile {
+    Trapezoidal(SpeedProfile),
+    SCurve(SCurveProfile),
+}
+
+impl MotionProfile {
+    /// the tool isn't moving
+    pub fn standstill() -> MotionProfile {
+        MotionProfile::Trapezoidal(SpeedProfile::standstill())
+    }
+
+    #[inline]
+    pub fn length(&self) -> f32 {
+        match self {
+            MotionProfile::Trapezoidal(profile) => profile.length(),
+            MotionProfile::SCurve(profile) => profile.length(),
+        }
+    }
+
+    #[inline]
+    pub fn exit_vel(&self) -> f32 {
+        match self {
+            MotionProfile::Trapezoidal(profile) => profile.exit_vel(),
+            MotionProfile::SCurve(profile) => profile.exit_vel(),
+        }
+    }
+
+    #[inline]
+    pub fn duration(&self) -> f32 {
+        match self {
+            MotionProfile::Trapezoidal(profile) => profile.duration(),
+            MotionProfile::SCurve(profile) => profile.duration(),
+        }
+    }
+
+    /// position and velocity `t` seconds after the start of the segment
+    #[inline]
+    pub fn setpoint_at(&self, t: f32) -> (f32, f32) {
+        match self {
+            MotionProfile::Trapezoidal(profile) => profile.setpoint_at(t),
+            MotionProfile::SCurve(profile) => profile.setpoint_at(t),
+        }
+    }
+
+    /// velocity after travelling `distance` into the segment
+    #[inline]
+    pub fn velocity_at(&self, distance: f32) -> f32 {
+        match self {
+            MotionProfile::Trapezoidal(profile) => profile.velocity_at(distance),
+            MotionProfile::SCurve(profile) => profile.velocity_at(distance),
+        }
+    }
+}
+
+/// trapezoidal velocity profile over a single segment: accelerate from the entry
+/// velocity, cruise, then decelerate to the exit velocity. distances are in mm,
+/// velocities in mm/s and acceleration in mm/s^2.
+#[derive(Clone, Copy)]
+pub struct SpeedProfile {
+    length: f32,
+
+    entry_vel: f32,
+    peak_vel: f32,
+    exit_vel: f32,
+
+    accel: f32,
+
+    accel_dist: f32,
+    decel_dist: f32,
+}
+
+impl SpeedProfile {
+    /// `max_vel` is only reached if the segment is long enough, entry and exit
+    /// velocities above it are capped. if `exit_vel` can't be reached within
+    /// `length` the profile ends at the closest velocity it can reach.
+    pub fn new(
+        length: f32,
+        entry_vel: f32,
+        max_vel: f32,
+        exit_vel: f32,
+        accel: f32,
+    ) -> Result<SpeedProfile, ()> {
+        if length < 0.0 || max_vel <= 0.0 || accel <= 0.0 || entry_vel < 0.0 || exit_vel < 0.0 {
+            return Err(());
+        }
+
+        let entry_vel = entry_vel.min(max_vel);
+        let exit_vel = exit_vel.min(max_vel);
+
+        let accel_dist = (max_vel * max_vel - entry_vel * entry_vel) / (2.0 * accel);
+        let decel_dist = (max_vel * max_vel - exit_vel * exit_vel) / (2.0 * accel);
+
+        let (peak_vel, exit_vel, accel_dist, decel_dist) = if accel_dist + decel_dist <= length {
+            (max_vel, exit_vel, accel_dist, decel_dist)
+        } else {
+            // too short to reach max_vel, accel and decel ramps meet at the peak
+            let peak_sq =
+                (2.0 * accel * length + entry_vel * entry_vel + exit_vel * exit_vel) / 2.0;
+
+            if peak_sq < entry_vel * entry_vel {
+                // can't slow down to exit_vel in time, decelerate all the way
+                // and leave the segment faster than asked for
+                let exit_sq = entry_vel * entry_vel - 2.0 * accel * length;
+                (entry_vel, sqrt(exit_sq.max(0.0)), 0.0, length)
+            } else if peak_sq < exit_vel * exit_vel {
+                // can't speed up to exit_vel in time, accelerate all the way
+                let exit_sq = entry_vel * entry_vel + 2.0 * accel * length;
+                (sqrt(exit_sq), sqrt(exit_sq), length, 0.0)
+            } else {
+                let accel_dist = (peak_sq - entry_vel * entry_vel) / (2.0 * accel);
+                (sqrt(peak_sq), exit_vel, accel_dist, length - accel_dist)
+            }
+        };
+
+        Ok(SpeedProfile {
+            length,
+            entry_vel,
+            peak_vel,
+            exit_vel,
+            accel,
+            accel_dist,
+            decel_dist,
+        })
+    }
+
+    /// empty profile, the tool isn't moving
+    pub fn standstill() -> SpeedProfile {
+        SpeedProfile {
+            length: 0.0,
+            entry_vel: 0.0,
+            peak_vel: 0.0,
+            exit_vel: 0.0,
+            accel: 0.0,
+            accel_dist: 0.0,
+            decel_dist: 0.0,
+        }
+    }
+
+    /// velocity after travelling `distance` into the segment
+    pub fn velocity_at(&self, distance: f32) -> f32 {
+        let distance = distance.max(0.0).min(self.length);
+
+        if distance < self.accel_dist {
+            sqrt(self.entry_vel * self.entry_vel + 2.0 * self.accel * distance)
+        } else if distance > self.length - self.decel_dist {
+            let remaining = self.length - distance;
+            sqrt(self.exit_vel * self.exit_vel + 2.0 * self.accel * remaining)
+        } else {
+            self.peak_vel
+        }
+    }
+
+    /// time it takes to run through the whole profile
+    pub fn duration(&self) -> f32 {
+        if self.accel <= 0.0 {
+            return 0.0;
+        }
+
+        let cruise_time = if self.peak_vel > 0.0 {
+            self.cruise_dist() / self.peak_vel
+        } else {
+            0.0
+        };
+        (self.peak_vel - self.entry_vel) / self.accel
+            + cruise_time
+            + (self.peak_vel - self.exit_vel) / self.accel
+    }
+
+    /// position and velocity `t` seconds after the start of the segment
+    pub fn setpoint_at(&self, t: f32) -> (f32, f32) {
+        if self.accel <= 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let t = t.max(0.0);
+        let accel_time = (self.peak_vel - self.entry_vel) / self.accel;
+        if t < accel_time {
+            return (
+                self.entry_vel * t + self.accel * t * t / 2.0,
+                self.entry_vel + self.accel * t,
+            );
+        }
+
+        let t = t - accel_time;
+        let cruise_time = if self.peak_vel > 0.0 {
+            self.cruise_dist() / self.peak_vel
+        } else {
+            0.0
+        };
+        if t < cruise_time {
+            return (self.accel_dist + self.peak_vel * t, self.peak_vel);
+        }
+
+        let decel_time = (self.peak_vel - self.exit_vel) / self.accel;
+        let t = (t - cruise_time).min(decel_time);
+        (
+            self.accel_dist + self.cruise_dist() + self.peak_vel * t - self.accel * t * t / 2.0,
+            self.peak_vel - self.accel * t,
+        )
+    }
+
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    #[inline]
+    pub fn entry_vel(&self) -> f32 {
+        self.entry_vel
+    }
+
+    #[inline]
+    pub fn peak_vel(&self) -> f32 {
+        self.peak_vel
+    }
+
+    #[inline]
+    pub fn exit_vel(&self) -> f32 {
+        self.exit_vel
+    }
+
+    #[inline]
+    pub fn accel_dist(&self) -> f32 {
+        self.accel_dist
+    }
+
+    #[inline]
+    pub fn cruise_dist(&self) -> f32 {
+        self.length - self.accel_dist - self.decel_dist
+    }
+
+    #[inline]
+    pub fn decel_dist(&self) -> f32 {
+        self.decel_dist
+    }
+}
@@ -0,0 +1,15 @@
+//! the limit switches and the emergency stop, as far as the motion controller cares about them
+
+pub trait Switches {
+    /// the x limit switch is closed
+    fn x_limit(&self) -> bool;
+
+    /// the y limit switch is closed
+    fn y_limit(&self) -> bool;
+
+    /// the emergency stop is engaged right now
+    fn estop_engaged(&self) -> bool;
+
+    /// the emergency stop was hit since the last call
+    fn take_estop_triggered(&mut self) -> bool;
+}
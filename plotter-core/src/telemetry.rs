@@ -0,0 +1,135 @@
+//! periodic samples of both axes, batched into binary packets for a subscribed
+//! host. `tools/telemetry.py` turns them into csv.
+//!
+//! packet: `[PACKET_MAGIC] [VERSION] [seq lo] [seq hi] [count] [0]` followed by
+//! `count` records of `RECORD_LEN` bytes, all little endian:
+//!
+//! `[time_us u32]` then for x and y each
+//! `[target_pos i32] [actual_pos i32] [pwm i16] [speed f32]`
+//!
+//! positions are in encoder units, pwm is the signed duty cycle in 1/100 %
+//! and speed the measured speed in encoder units/s.
+
+use crate::transport::Peer;
+
+pub const PACKET_MAGIC: u8 = 0xA6;
+const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 6;
+const AXIS_LEN: usize = 14;
+pub const RECORD_LEN: usize = 4 + 2 * AXIS_LEN;
+const RECORDS_PER_PACKET: usize = 16;
+const PACKET_LEN: usize = HEADER_LEN + RECORDS_PER_PACKET * RECORD_LEN;
+
+/// us, a packet that isn't full yet is sent anyway once its first record is this old
+const MAX_PACKET_AGE: u64 = 50_000;
+pub const MAX_RATE_HZ: u32 = 1000;
+
+#[derive(Clone, Copy)]
+pub struct AxisSample {
+    pub target_pos: i32,
+    pub actual_pos: i32,
+    /// signed duty cycle, -100..=100
+    pub pwm: f32,
+    /// encoder units/s
+    pub speed: f32,
+}
+
+impl AxisSample {
+    fn encode(&self, buf: &mut [u8]) {
+        let pwm = (self.pwm * 100.0) as i16;
+        buf[0..4].copy_from_slice(&self.target_pos.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.actual_pos.to_le_bytes());
+        buf[8..10].copy_from_slice(&pwm.to_le_bytes());
+        buf[10..14].copy_from_slice(&self.speed.to_le_bytes());
+    }
+}
+
+pub struct Telemetry {
+    peer: Option<Peer>,
+    /// us between two samples
+    interval: u64,
+    last_sample: u64,
+
+    packet: [u8; PACKET_LEN],
+    records: usize,
+    /// time the first record of the pending packet was taken
+    packet_start: u64,
+    seq: u16,
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self {
+            peer: None,
+            interval: 0,
+            last_sample: 0,
+
+            packet: [0u8; PACKET_LEN],
+            records: 0,
+            packet_start: 0,
+            seq: 0,
+        }
+    }
+
+    /// starts sampling at `rate_hz`, capped at `MAX_RATE_HZ`, and sending the
+    /// packets to `peer`
+    pub fn subscribe(&mut self, peer: Peer, rate_hz: u32) {
+        let rate_hz = rate_hz.clamp(1, MAX_RATE_HZ);
+        self.peer = Some(peer);
+        self.interval = 1_000_000 / rate_hz as u64;
+        self.records = 0;
+    }
+
+    pub fn unsubscribe(&mut self) {
+        self.peer = None;
+        self.records = 0;
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.peer.is_some()
+    }
+
+    #[inline]
+    pub fn is_due(&self, now: u64) -> bool {
+        self.is_enabled() && now - self.last_sample >= self.interval
+    }
+
+    /// adds a sample taken at `now` (us), returns the packet and where to send
+    /// it once it's ready
+    pub fn record(&mut self, now: u64, x: AxisSample, y: AxisSample) -> Option<(&[u8], Peer)> {
+        let peer = self.peer?;
+        self.last_sample = now;
+
+        if self.records == 0 {
+            self.packet_start = now;
+        }
+
+        let offset = HEADER_LEN + self.records * RECORD_LEN;
+        let record = &mut self.packet[offset..offset + RECORD_LEN];
+        record[0..4].copy_from_slice(&(now as u32).to_le_bytes());
+        x.encode(&mut record[4..4 + AXIS_LEN]);
+        y.encode(&mut record[4 + AXIS_LEN..]);
+        self.records += 1;
+
+        if self.records < RECORDS_PER_PACKET && now - self.packet_start < MAX_PACKET_AGE {
+            return None;
+        }
+
+        let seq = self.seq.to_le_bytes();
+        self.packet[..HEADER_LEN].copy_from_slice(&[
+            PACKET_MAGIC,
+            VERSION,
+            seq[0],
+            seq[1],
+            self.records as u8,
+            0,
+        ]);
+        let len = HEADER_LEN + self.records * RECORD_LEN;
+
+        self.seq = self.seq.wrapping_add(1);
+        self.records = 0;
+        Some((&self.packet[..len], peer))
+    }
+}
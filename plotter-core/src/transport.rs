@@ -0,0 +1,199 @@
+//! how commands reach the plotter and how the answers get back. the command
+//! handler only talks to a `CommandTransport`, the firmware implements it for
+//! udp, tcp and usb, the tests use `Loopback`.
+
+use core::cell::{Cell, Ref, RefCell};
+use core::fmt::{self, Arguments};
+
+use heapless::consts::*;
+use heapless::Vec;
+
+use crate::buf_writer::BufWriter;
+use crate::com::truncate;
+
+/// who sent a message, telemetry subscriptions go back there
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Peer {
+    pub addr: [u8; 4],
+    pub port: u16,
+}
+
+impl Peer {
+    /// sender of transports without addresses, like usb
+    pub const NONE: Peer = Peer {
+        addr: [0; 4],
+        port: 0,
+    };
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d] = self.addr;
+        write!(f, "{}.{}.{}.{}:{}", a, b, c, d, self.port)
+    }
+}
+
+/// how `CommandTransport::recv` hands the data over
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Framing {
+    /// every `recv` is a whole message, plain text or a protocol frame
+    Datagram,
+    /// `recv` returns the next bytes of a stream of `\n` terminated lines.
+    /// what isn't read stays with the sender, that's the flow control.
+    Stream,
+}
+
+pub trait CommandTransport {
+    fn framing(&self) -> Framing;
+
+    /// reads what arrived into `buf`, returns how many bytes that were and
+    /// who sent them. `None` if nothing arrived
+    fn recv(&mut self, buf: &mut [u8]) -> Option<(usize, Peer)>;
+
+    /// sends `data` to whoever sent the last message, what doesn't fit may be
+    /// dropped. `Err(())` if there is nobody to send it to
+    fn send(&mut self, data: &[u8]) -> Result<(), ()>;
+
+    /// sends `data` to `peer`, no matter who sent the last message. `Err(())`
+    /// if the transport can't address anyone, or `peer` isn't reachable over it
+    fn send_to(&mut self, _data: &[u8], _peer: Peer) -> Result<(), ()> {
+        Err(())
+    }
+
+    /// true once after the other end went away
+    fn take_disconnected(&mut self) -> bool {
+        false
+    }
+}
+
+/// longest formatted message `send_fmt` sends
+pub const MAX_MESSAGE_LEN: usize = 256;
+
+/// formats `args` and sends it over `transport`, messages that don't fit
+/// into `MAX_MESSAGE_LEN` are cut short
+pub fn send_fmt<T: CommandTransport>(transport: &mut T, args: Arguments) -> Result<(), ()> {
+    let mut buf = [0u8; MAX_MESSAGE_LEN];
+    let mut buf_writer = BufWriter::new(&mut buf);
+    let _ = fmt::write(&mut buf_writer, args);
+    transport.send(buf_writer.get_bytes())
+}
+
+/// transport that only exists in memory, for driving the command handler
+/// with scripted traffic. messages `push`ed into it come out of `recv`,
+/// whatever is sent collects in `sent`.
+pub struct Loopback {
+    framing: Framing,
+    peer: Peer,
+    /// pushed messages, back to back
+    incoming: RefCell<Vec<u8, U4096>>,
+    /// length of each message in `incoming`, only used for datagrams
+    lengths: RefCell<Vec<usize, U64>>,
+    sent: RefCell<Vec<u8, U4096>>,
+    disconnected: Cell<bool>,
+}
+
+impl Loopback {
+    pub fn new(framing: Framing, peer: Peer) -> Self {
+        Self {
+            framing,
+            peer,
+            incoming: RefCell::new(Vec::new()),
+            lengths: RefCell::new(Vec::new()),
+            sent: RefCell::new(Vec::new()),
+            disconnected: Cell::new(false),
+        }
+    }
+
+    /// queues a datagram, or more bytes of the stream. `Err(())` if they
+    /// don't fit anymore
+    pub fn push(&self, data: &[u8]) -> Result<(), ()> {
+        let mut incoming = self.incoming.borrow_mut();
+        let mut lengths = self.lengths.borrow_mut();
+        let datagram = self.framing == Framing::Datagram;
+        if incoming.capacity() - incoming.len() < data.len()
+            || (datagram && lengths.len() == lengths.capacity())
+        {
+            return Err(());
+        }
+
+        let _ = incoming.extend_from_slice(data);
+        if datagram {
+            let _ = lengths.push(data.len());
+        }
+        Ok(())
+    }
+
+    /// bytes pushed that weren't read yet
+    pub fn pending(&self) -> usize {
+        self.incoming.borrow().len()
+    }
+
+    /// everything sent since the last `clear_sent`
+    pub fn sent(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.sent.borrow(), |sent| &sent[..])
+    }
+
+    pub fn clear_sent(&self) {
+        truncate(&mut self.sent.borrow_mut(), 0);
+    }
+
+    /// the other end goes away, what was pushed but not read is lost
+    pub fn disconnect(&self) {
+        truncate(&mut self.incoming.borrow_mut(), 0);
+        truncate(&mut self.lengths.borrow_mut(), 0);
+        self.disconnected.set(true);
+    }
+}
+
+impl CommandTransport for &Loopback {
+    fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> Option<(usize, Peer)> {
+        let mut incoming = self.incoming.borrow_mut();
+        let mut lengths = self.lengths.borrow_mut();
+        if incoming.is_empty() || buf.is_empty() {
+            return None;
+        }
+
+        let (len, taken) = match self.framing {
+            // a datagram that doesn't fit is cut short, like a udp socket does
+            Framing::Datagram => {
+                let message_len = lengths[0];
+                lengths.rotate_left(1);
+                lengths.pop();
+                (buf.len().min(message_len), message_len)
+            }
+            Framing::Stream => {
+                let len = buf.len().min(incoming.len());
+                (len, len)
+            }
+        };
+
+        buf[..len].copy_from_slice(&incoming[..len]);
+        incoming.rotate_left(taken);
+        let remaining = incoming.len() - taken;
+        truncate(&mut incoming, remaining);
+        Some((len, self.peer))
+    }
+
+    fn send(&mut self, data: &[u8]) -> Result<(), ()> {
+        let mut sent = self.sent.borrow_mut();
+        let free = sent.capacity() - sent.len();
+        let _ = sent.extend_from_slice(&data[..data.len().min(free)]);
+        Ok(())
+    }
+
+    /// datagrams reach anyone, what goes to `peer` collects in `sent` as well
+    fn send_to(&mut self, data: &[u8], _peer: Peer) -> Result<(), ()> {
+        match self.framing {
+            Framing::Datagram => self.send(data),
+            Framing::Stream => Err(()),
+        }
+    }
+
+    fn take_disconnected(&mut self) -> bool {
+        self.disconnected.replace(false)
+    }
+}
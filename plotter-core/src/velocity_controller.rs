@@ -0,0 +1,107 @@
+//! per axis velocity loop, turns a target speed into a motor duty cycle.
+//! doesn't touch any hardware or clock so it can be run anywhere.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PidConfig {
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    /// duty cycle per unit of target speed
+    pub kff: f32,
+    /// duty cycle the motor needs before it moves at all, added on top of the
+    /// feedforward whenever the target speed isn't zero
+    pub min_duty: f32,
+    /// upper bound of the output, at most 100
+    pub max_duty: f32,
+}
+
+pub struct VelocityController {
+    config: PidConfig,
+
+    integral: f32,
+    prev_measured: f32,
+    output: f32,
+}
+
+impl VelocityController {
+    pub fn new(config: PidConfig) -> Self {
+        Self {
+            config,
+            integral: 0.0,
+            prev_measured: 0.0,
+            output: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> PidConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: PidConfig) {
+        self.config = config;
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.output = 0.0;
+    }
+
+    /// last value returned by `update`
+    #[inline]
+    pub fn output(&self) -> f32 {
+        self.output
+    }
+
+    /// `target` and `measured` are signed speeds, `dt` is the time since the last
+    /// update in seconds. returns a signed duty cycle whose magnitude is within
+    /// 0..=`max_duty` and whose sign matches `target`, the motor is never driven
+    /// against the direction of travel.
+    pub fn update(&mut self, target: f32, measured: f32, dt: f32) -> f32 {
+        let max_duty = self.config.max_duty.clamp(0.0, 100.0);
+
+        if target == 0.0 {
+            self.integral = 0.0;
+            self.prev_measured = measured;
+            self.output = 0.0;
+            return 0.0;
+        }
+
+        let error = target - measured;
+
+        // derivative on measurement, a new target doesn't cause a kick
+        let derivative = if dt > 0.0 {
+            -(measured - self.prev_measured) / dt
+        } else {
+            0.0
+        };
+        self.prev_measured = measured;
+
+        let feedforward = self.config.kff * target + self.config.min_duty * target.signum();
+        let integral = self.integral + error * dt;
+
+        let unclamped = feedforward
+            + self.config.kp * error
+            + self.config.ki * integral
+            + self.config.kd * derivative;
+
+        let (low, high) = if target > 0.0 {
+            (0.0, max_duty)
+        } else {
+            (-max_duty, 0.0)
+        };
+        let output = unclamped.max(low).min(high);
+
+        // anti-windup: only keep integrating while the output isn't saturated,
+        // or while the error pulls it back out of saturation
+        let saturated_high = unclamped > high && error > 0.0;
+        let saturated_low = unclamped < low && error < 0.0;
+        if !saturated_high && !saturated_low {
+            self.integral = integral;
+        }
+
+        self.output = output;
+        output
+    }
+}
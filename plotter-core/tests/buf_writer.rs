@@ -0,0 +1,27 @@
+use core::fmt::Write;
+
+use plotter_core::buf_writer::BufWriter;
+
+#[test]
+fn formats_into_the_buffer() {
+    let mut buf = [0u8; 32];
+    let mut writer = BufWriter::new(&mut buf);
+
+    write!(writer, "x: {} y: {}", 12, -3).unwrap();
+    assert_eq!(writer.get_bytes(), b"x: 12 y: -3");
+
+    writer.clear_buf();
+    write!(writer, "ok").unwrap();
+    assert_eq!(writer.get_bytes(), b"ok");
+}
+
+#[test]
+fn overflow_is_an_error() {
+    let mut buf = [0u8; 8];
+    let mut writer = BufWriter::new(&mut buf);
+
+    // the last byte is always kept free
+    assert!(write!(writer, "123456").is_ok());
+    assert!(write!(writer, "7").is_err());
+    assert_eq!(writer.get_bytes(), b"123456");
+}
@@ -0,0 +1,299 @@
+//! the velocity loop and speed calculation the firmware runs, closed around a
+//! simulated axis instead of the plotter, and the whole motion controller
+//! closed around two of them
+
+use core::cell::RefCell;
+
+use plotter_core::clock::{Clock, ManualClock};
+use plotter_core::com::CommandHandler;
+use plotter_core::motion_controller::{MachineState, MotionController};
+use plotter_core::motor_pwm::MotorPwm;
+use plotter_core::pen::{Pen, PenPosition};
+use plotter_core::plant_sim::{MotorPlant, PlantConfig, SimEncoder, SimPwmPin};
+use plotter_core::settings::{Settings, SettingsStorage, DEFAULTS};
+use plotter_core::speed_calc::PulseContedSpeedCalc;
+use plotter_core::switches::Switches;
+use plotter_core::telemetry::PACKET_MAGIC;
+use plotter_core::transport::{Framing, Loopback, Peer};
+use plotter_core::velocity_controller::{PidConfig, VelocityController};
+
+/// same as the firmware's x axis
+const UNIT_LENGTH: f32 = 0.042;
+const PID: PidConfig = PidConfig {
+    kp: 1.0,
+    ki: 4.0,
+    kd: 0.0,
+    kff: 1.0,
+    min_duty: 20.0,
+    max_duty: 100.0,
+};
+/// us
+const TICK: u64 = 50;
+const CORRECTION_INTERVAL: u64 = 10_000;
+
+/// runs the loop towards `target` mm/s for `duration` us, returns the mean
+/// speed (mm/s) over the last half of it
+fn run(target: f32, duration: u64) -> f32 {
+    let clock = ManualClock::new();
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+    let mut speed = PulseContedSpeedCalc::new(SimEncoder::new(&plant), &clock);
+    let mut pid = VelocityController::new(PID);
+
+    motor.enable_pwm();
+    let mut last_correction = 0;
+    let mut duty = 0.0;
+    let mut half_way = None;
+
+    while clock.now() < duration {
+        speed.tick(duty);
+
+        if clock.now() - last_correction >= CORRECTION_INTERVAL {
+            let dt = (clock.now() - last_correction) as f32 / 1_000_000.0;
+            last_correction = clock.now();
+            duty = pid.update(target, speed.speed() * UNIT_LENGTH, dt);
+            if duty > 0.0 {
+                motor.move_positive(duty);
+            } else if duty < 0.0 {
+                motor.move_negative(-duty);
+            } else {
+                motor.active_stop();
+            }
+        }
+
+        plant.borrow_mut().step(TICK as f32 / 1_000_000.0);
+        clock.advance(TICK);
+
+        if half_way.is_none() && clock.now() >= duration / 2 {
+            half_way = Some(speed.pos());
+        }
+    }
+
+    let travelled = (speed.pos() - half_way.unwrap()) as f32 * UNIT_LENGTH;
+    travelled / ((duration / 2) as f32 / 1_000_000.0)
+}
+
+#[test]
+fn holds_cruise_speed() {
+    for &target in &[10.0, 30.0, -60.0] {
+        let speed = run(target, 3_000_000);
+        assert!(
+            (speed - target).abs() < 0.05 * target.abs(),
+            "{} mm/s instead of {}",
+            speed,
+            target
+        );
+    }
+}
+
+#[test]
+fn zero_target_stops_the_motor() {
+    assert_eq!(run(0.0, 1_000_000), 0.0);
+}
+
+#[test]
+fn speed_calc_follows_the_clock() {
+    let clock = ManualClock::new();
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+    let mut speed = PulseContedSpeedCalc::new(SimEncoder::new(&plant), &clock);
+
+    // slow enough for the pulses to be far apart compared to the tick
+    motor.enable_pwm();
+    motor.move_positive(30.0);
+    for _ in 0..50_000 {
+        plant.borrow_mut().step(0.000_01);
+        clock.advance(10);
+        speed.tick(30.0);
+    }
+    let vel = plant.borrow().motor_vel();
+    assert!(
+        (speed.speed() - vel).abs() < 0.05 * vel,
+        "{} != {}",
+        speed.speed(),
+        vel
+    );
+
+    // no new pulses for long enough counts as standing still
+    motor.disable_pwm();
+    plant.borrow_mut().step(1.0);
+    clock.advance(1000);
+    speed.tick(0.0);
+    clock.advance(300_000);
+    speed.tick(0.0);
+    assert_eq!(speed.speed(), 0.0);
+}
+
+struct SimPen {
+    pos: PenPosition,
+}
+
+impl Pen for SimPen {
+    fn pos(&self) -> PenPosition {
+        self.pos
+    }
+
+    fn move_pen(&mut self, pen_pos: PenPosition) {
+        self.pos = pen_pos;
+    }
+
+    fn move_up(&mut self) {
+        self.pos = PenPosition::Default;
+    }
+
+    fn set_up_angle(&mut self, _angle: u8) {}
+}
+
+/// no limit switches and an e-stop that's never hit
+struct NoSwitches;
+
+impl Switches for NoSwitches {
+    fn x_limit(&self) -> bool {
+        false
+    }
+
+    fn y_limit(&self) -> bool {
+        false
+    }
+
+    fn estop_engaged(&self) -> bool {
+        false
+    }
+
+    fn take_estop_triggered(&mut self) -> bool {
+        false
+    }
+}
+
+struct MemoryStore(Option<Settings>);
+
+impl SettingsStorage for MemoryStore {
+    fn save(&mut self, settings: &Settings) -> Result<(), ()> {
+        self.0 = Some(*settings);
+        Ok(())
+    }
+}
+
+type Controller<'a> = MotionController<
+    SimPwmPin<'a>,
+    SimPwmPin<'a>,
+    SimEncoder<'a>,
+    SimEncoder<'a>,
+    SimPen,
+    NoSwitches,
+    MemoryStore,
+    &'a ManualClock,
+>;
+
+const HOST: Peer = Peer {
+    addr: [192, 168, 1, 10],
+    port: 5000,
+};
+
+fn controller<'a>(
+    x: &'a RefCell<MotorPlant>,
+    y: &'a RefCell<MotorPlant>,
+    clock: &'a ManualClock,
+) -> Controller<'a> {
+    let mut x_motor = MotorPwm::new(SimPwmPin::new(x));
+    let mut y_motor = MotorPwm::new(SimPwmPin::new(y));
+    x_motor.enable_pwm();
+    y_motor.enable_pwm();
+    MotionController::new(
+        x_motor,
+        y_motor,
+        SimEncoder::new(x),
+        SimEncoder::new(y),
+        SimPen {
+            pos: PenPosition::Default,
+        },
+        NoSwitches,
+        DEFAULTS,
+        MemoryStore(None),
+        clock,
+    )
+}
+
+/// one pass of the firmware's main loop, then `TICK` of simulated time
+fn step(
+    controller: &mut Controller,
+    handler: &mut CommandHandler<&Loopback, &ManualClock>,
+    plants: (&RefCell<MotorPlant>, &RefCell<MotorPlant>),
+    clock: &ManualClock,
+) {
+    controller.tick(handler);
+    handler.tick();
+    plants.0.borrow_mut().step(TICK as f32 / 1_000_000.0);
+    plants.1.borrow_mut().step(TICK as f32 / 1_000_000.0);
+    clock.advance(TICK);
+}
+
+fn pos_mm(controller: &Controller) -> (f32, f32) {
+    let (x, y) = controller.curr_pos();
+    let (unit_x, unit_y) = DEFAULTS.unit_length;
+    (x as f32 * unit_x, y as f32 * unit_y)
+}
+
+#[test]
+fn follows_a_queued_line_and_arc() {
+    let clock = ManualClock::new();
+    let x = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let y = RefCell::new(MotorPlant::new(PlantConfig::y_axis()));
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+    let mut controller = controller(&x, &y, &clock);
+    controller.start_sequence();
+
+    udp.push(b"G21 G90\nG1 X10 Y5 F600\nG2 X20 Y5 I5 J0\n")
+        .unwrap();
+
+    let mut started = false;
+    let mut top = 0.0f32;
+    while clock.now() < 20_000_000 {
+        step(&mut controller, &mut handler, (&x, &y), &clock);
+        top = top.max(pos_mm(&controller).1);
+        started |= controller.state() == MachineState::Run;
+        if started && controller.state() == MachineState::Idle {
+            break;
+        }
+    }
+
+    assert_eq!(controller.state(), MachineState::Idle, "still running");
+    let (end_x, end_y) = pos_mm(&controller);
+    assert!(
+        (end_x - 20.0).abs() < 0.5 && (end_y - 5.0).abs() < 0.5,
+        "ended at {},{}",
+        end_x,
+        end_y
+    );
+    // clockwise around 15,5 goes over the top
+    assert!((top - 10.0).abs() < 0.5, "arc peaked at {}", top);
+}
+
+#[test]
+fn status_and_telemetry_reach_the_host_mid_move() {
+    let clock = ManualClock::new();
+    let x = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let y = RefCell::new(MotorPlant::new(PlantConfig::y_axis()));
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+    let mut controller = controller(&x, &y, &clock);
+    controller.start_sequence();
+
+    udp.push(b">telemetry 200").unwrap();
+    udp.push(b"G1 X20 F600").unwrap();
+    for _ in 0..10_000 {
+        step(&mut controller, &mut handler, (&x, &y), &clock);
+    }
+    assert!(udp.sent().contains(&PACKET_MAGIC));
+
+    udp.clear_sent();
+    udp.push(b"?").unwrap();
+    step(&mut controller, &mut handler, (&x, &y), &clock);
+    step(&mut controller, &mut handler, (&x, &y), &clock);
+    let sent = String::from_utf8_lossy(&udp.sent()).into_owned();
+    assert!(sent.contains("<Run|"), "{}", sent);
+    assert!(sent.contains("|Units:mm|"), "{}", sent);
+}
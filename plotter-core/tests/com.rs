@@ -0,0 +1,294 @@
+use plotter_core::clock::ManualClock;
+use plotter_core::com::*;
+use plotter_core::grbl_settings::SettingsCommand;
+use plotter_core::protocol::{FRAME_ACK, FRAME_DATA, FRAME_MAGIC, FRAME_NACK};
+use plotter_core::transport::{Framing, Loopback, Peer};
+
+const HOST: Peer = Peer {
+    addr: [192, 168, 1, 10],
+    port: 5000,
+};
+
+fn text(loopback: &Loopback) -> String {
+    String::from_utf8_lossy(&loopback.sent()).into_owned()
+}
+
+#[test]
+fn queues_gcode_and_picks_out_realtime_commands() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+
+    handler.tick();
+    assert!(text(&udp).contains("[0] buffer empty"));
+
+    udp.push(b"G1 X10 Y5\nG1 X0!").unwrap();
+    handler.tick();
+    assert_eq!(handler.get_gcode_buffer().len(), 2);
+    assert_eq!(handler.realtime_commands(), &[RealtimeCommand::FeedHold]);
+
+    udp.push(&[0x18]).unwrap();
+    handler.tick();
+    assert!(handler.get_gcode_buffer().is_empty());
+}
+
+#[test]
+fn an_abort_gets_through_a_burst_of_realtime_commands() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+
+    udp.push(b"??????!~!~\x18").unwrap();
+    handler.tick();
+    assert_eq!(
+        handler.realtime_commands(),
+        &[
+            RealtimeCommand::StatusQuery,
+            RealtimeCommand::Resume,
+            RealtimeCommand::Abort
+        ]
+    );
+}
+
+#[test]
+fn an_abort_takes_the_rest_of_its_message_along() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let tcp = Loopback::new(Framing::Stream, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+    handler.add_transport(&tcp).unwrap();
+
+    udp.push(b"G1 X1\n\x18G1 X2?").unwrap();
+    handler.tick();
+    assert!(handler.get_gcode_buffer().is_empty());
+    assert_eq!(
+        handler.realtime_commands(),
+        &[RealtimeCommand::Abort, RealtimeCommand::StatusQuery]
+    );
+    handler.clear_realtime_commands();
+
+    // half a line waiting in the stream is dropped as well
+    tcp.push(b"G1 X").unwrap();
+    handler.tick();
+    udp.push(&[0x18]).unwrap();
+    handler.tick();
+    tcp.push(b"3\nG1 X4\n").unwrap();
+    handler.tick();
+    assert_eq!(handler.get_gcode_buffer().len(), 1);
+
+    tcp.push(b"G1 X5\n\x18G1 X6\n").unwrap();
+    handler.tick();
+    assert!(handler.get_gcode_buffer().is_empty());
+}
+
+#[test]
+fn frames_are_acked_to_their_sender_only() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let usb = Loopback::new(Framing::Stream, Peer::NONE);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+    handler.add_transport(&usb).unwrap();
+    handler.tick();
+    udp.clear_sent();
+
+    let mut frame = vec![FRAME_MAGIC, FRAME_DATA, 0, 0];
+    frame.extend_from_slice(b"G1 X1\nG1 X2\n");
+    udp.push(&frame).unwrap();
+    handler.tick();
+    assert_eq!(&udp.sent()[..4], &[FRAME_MAGIC, FRAME_ACK, 0, 0]);
+    assert_eq!(handler.get_gcode_buffer().len(), 2);
+
+    // out of order
+    udp.clear_sent();
+    frame[2] = 5;
+    udp.push(&frame).unwrap();
+    handler.tick();
+    assert_eq!(&udp.sent()[..4], &[FRAME_MAGIC, FRAME_NACK, 1, 0]);
+    assert!(usb.sent().is_empty());
+}
+
+#[test]
+fn stream_lines_may_arrive_in_pieces() {
+    let clock = ManualClock::new();
+    let tcp = Loopback::new(Framing::Stream, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&tcp).unwrap();
+
+    tcp.push(b"G1 X1").unwrap();
+    handler.tick();
+    assert!(handler.get_gcode_buffer().is_empty());
+
+    tcp.push(b"0 Y2\nG1").unwrap();
+    handler.tick();
+    assert_eq!(handler.get_gcode_buffer().len(), 1);
+
+    tcp.push(b" X3\n").unwrap();
+    handler.tick();
+    assert_eq!(handler.get_gcode_buffer().len(), 2);
+}
+
+#[test]
+fn a_full_buffer_holds_the_stream_back() {
+    let clock = ManualClock::new();
+    let tcp = Loopback::new(Framing::Stream, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&tcp).unwrap();
+
+    for _ in 0..600 {
+        tcp.push(b"G0 X1\n").unwrap();
+    }
+    for _ in 0..20 {
+        handler.tick();
+    }
+    for _ in 0..100 {
+        tcp.push(b"G0 X1\n").unwrap();
+    }
+    handler.tick();
+    assert_eq!(handler.gcode_buffer_free(), 0);
+    assert!(tcp.pending() > 0);
+    // no buffer messages, the stream already tells the client to wait
+    assert!(!text(&tcp).contains("buffer full"));
+
+    handler.consume_gcode(512);
+    for _ in 0..20 {
+        handler.tick();
+    }
+    assert_eq!(tcp.pending(), 0);
+    assert_eq!(handler.get_gcode_buffer().len(), 188);
+}
+
+#[test]
+fn overlong_lines_are_dropped() {
+    let clock = ManualClock::new();
+    let usb = Loopback::new(Framing::Stream, Peer::NONE);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&usb).unwrap();
+
+    usb.push(&[b' '; 700]).unwrap();
+    usb.push(b"\nG1 X1\n").unwrap();
+    for _ in 0..3 {
+        handler.tick();
+    }
+    assert!(text(&usb).contains("error: line too long"));
+    assert_eq!(handler.get_gcode_buffer().len(), 1);
+}
+
+#[test]
+fn a_dropped_stream_client_is_reported_once() {
+    let clock = ManualClock::new();
+    let tcp = Loopback::new(Framing::Stream, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&tcp).unwrap();
+
+    tcp.push(b"G1 X1\nG1 X").unwrap();
+    handler.tick();
+    tcp.disconnect();
+    handler.tick();
+
+    assert!(handler.take_client_dropped());
+    assert!(!handler.take_client_dropped());
+    // the half line is gone with the client
+    tcp.push(b"1\n").unwrap();
+    handler.tick();
+    assert_eq!(handler.get_gcode_buffer().len(), 1);
+}
+
+#[test]
+fn errors_go_back_where_the_command_came_from() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let usb = Loopback::new(Framing::Stream, Peer::NONE);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+    handler.add_transport(&usb).unwrap();
+    handler.tick();
+    udp.clear_sent();
+
+    handler.set_locked(true);
+    usb.push(b"G1 X1\n").unwrap();
+    handler.tick();
+    assert!(text(&usb).contains("error: alarm"));
+    assert!(udp.sent().is_empty());
+
+    usb.push(b"$X\n").unwrap();
+    handler.tick();
+    assert!(handler.take_unlock_request());
+}
+
+#[test]
+fn replies_go_to_the_channel_that_asked() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let usb = Loopback::new(Framing::Stream, Peer::NONE);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+    handler.add_transport(&usb).unwrap();
+    handler.tick();
+    udp.clear_sent();
+
+    usb.push(b"?").unwrap();
+    handler.tick();
+    assert_eq!(handler.realtime_commands(), &[RealtimeCommand::StatusQuery]);
+    handler.send(format_args!("<Idle>\n\r"));
+    assert_eq!(&*usb.sent(), b"<Idle>\n\r");
+    assert!(udp.sent().is_empty());
+
+    udp.push(b"$$").unwrap();
+    handler.tick();
+    assert_eq!(handler.take_settings_command(), Some(SettingsCommand::List));
+    handler.send(format_args!("$11=0.010\n\r"));
+    assert!(text(&udp).contains("$11=0.010"));
+    assert_eq!(&*usb.sent(), b"<Idle>\n\r");
+}
+
+#[test]
+fn telemetry_needs_an_address() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let usb = Loopback::new(Framing::Stream, Peer::NONE);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+    handler.add_transport(&usb).unwrap();
+
+    usb.push(b">telemetry 100\n").unwrap();
+    handler.tick();
+    assert_eq!(handler.take_telemetry_request(), None);
+    assert!(text(&usb).contains("only sent over ethernet"));
+
+    udp.push(b">telemetry 100").unwrap();
+    handler.tick();
+    assert_eq!(
+        handler.take_telemetry_request(),
+        Some(TelemetryRequest::Subscribe {
+            peer: HOST,
+            rate_hz: 100
+        })
+    );
+}
+
+#[test]
+fn silent_hosts_time_out_once() {
+    let clock = ManualClock::new();
+    let udp = Loopback::new(Framing::Datagram, HOST);
+    let mut handler = CommandHandler::new(&clock);
+    handler.add_transport(&udp).unwrap();
+
+    udp.push(b">heartbeat 2").unwrap();
+    handler.tick();
+    assert!(text(&udp).contains("heartbeat: 2 s"));
+
+    clock.advance(1_500_000);
+    assert!(!handler.host_timed_out());
+    clock.advance(1_000_000);
+    assert!(handler.host_timed_out());
+    assert!(!handler.host_timed_out());
+
+    udp.push(b"G1 X1").unwrap();
+    handler.tick();
+    clock.advance(2_500_000);
+    assert!(handler.host_timed_out());
+}
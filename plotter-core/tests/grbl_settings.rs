@@ -0,0 +1,101 @@
+use plotter_core::grbl_settings::*;
+use plotter_core::settings::{Settings, DEFAULTS, MAX_BLOCK_LEN};
+
+#[test]
+fn parses_commands() {
+    assert_eq!(parse_command("$$"), Ok(SettingsCommand::List));
+    assert_eq!(parse_command("$RST\n"), Ok(SettingsCommand::Reset));
+    assert_eq!(parse_command("$110"), Ok(SettingsCommand::Show(110)));
+    assert_eq!(
+        parse_command("$110=80"),
+        Ok(SettingsCommand::Set(110, Value::Float(80.0)))
+    );
+    assert_eq!(
+        parse_command("$300=10.0.0.7"),
+        Ok(SettingsCommand::Set(300, Value::Ip([10, 0, 0, 7])))
+    );
+    assert_eq!(
+        parse_command("$302=02:ab:00:00:00:01"),
+        Ok(SettingsCommand::Set(302, Value::Mac([2, 0xab, 0, 0, 0, 1])))
+    );
+    assert_eq!(
+        parse_command("$302=0"),
+        Ok(SettingsCommand::Set(302, Value::Mac([0; 6])))
+    );
+    assert_eq!(parse_command("$J=X10"), Err(SettingError::Syntax));
+}
+
+#[test]
+fn rejects_bad_values() {
+    assert_eq!(parse_command("$999=1"), Err(SettingError::Unknown(999)));
+    assert!(matches!(
+        parse_command("$110=fast"),
+        Err(SettingError::Invalid(110, _))
+    ));
+    assert!(matches!(
+        parse_command("$110=0"),
+        Err(SettingError::OutOfRange(110, _))
+    ));
+    assert!(matches!(
+        parse_command("$30=2.5"),
+        Err(SettingError::Invalid(30, _))
+    ));
+    assert!(matches!(
+        parse_command("$300=10.0.0"),
+        Err(SettingError::Invalid(300, _))
+    ));
+    // multicast
+    assert!(matches!(
+        parse_command("$302=01:00:00:00:00:01"),
+        Err(SettingError::OutOfRange(302, _))
+    ));
+}
+
+#[test]
+fn sets_values() {
+    let mut settings = DEFAULTS;
+    settings.set(100, Value::Float(20.0)).unwrap();
+    settings.set(214, Value::Float(15.0)).unwrap();
+    settings.set(31, Value::Int(120)).unwrap();
+
+    assert_eq!(settings.unit_length.0, 0.05);
+    assert_eq!(settings.y_pid.min_duty, 15.0);
+    assert_eq!(settings.pen_up_dwell, 120);
+    assert_eq!(info(100).unwrap().get(&settings), Value::Float(20.0));
+}
+
+#[test]
+fn keeps_the_settings_consistent() {
+    let mut settings = DEFAULTS;
+    assert!(matches!(
+        settings.set(131, Value::Float(-10.0)),
+        Err(SettingError::Conflict(_))
+    ));
+    assert!(matches!(
+        settings.set(205, Value::Float(5.0)),
+        Err(SettingError::Conflict(_))
+    ));
+    assert_eq!(settings, DEFAULTS);
+}
+
+#[test]
+fn every_setting_is_listed_once_and_valid_by_default() {
+    for (idx, setting) in SETTINGS.iter().enumerate() {
+        assert!(SETTINGS[idx + 1..].iter().all(|s| s.id != setting.id));
+
+        let mut settings = DEFAULTS;
+        let value = setting.get(&DEFAULTS);
+        settings.set(setting.id, value).unwrap();
+    }
+    assert_eq!(DEFAULTS.check(), Ok(()));
+}
+
+#[test]
+fn set_values_survive_storing() {
+    let mut settings = DEFAULTS;
+    settings.set(304, Value::Int(5000)).unwrap();
+
+    let mut buf = [0xFFu8; MAX_BLOCK_LEN];
+    settings.encode(&mut buf);
+    assert_eq!(Settings::decode(&buf), Ok(settings));
+}
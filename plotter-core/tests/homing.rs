@@ -0,0 +1,153 @@
+use core::cell::RefCell;
+
+use plotter_core::encoder::Encoder;
+use plotter_core::homing::{HomingConfig, HomingCycle, HomingError, HomingStep, HomingTrigger};
+use plotter_core::motor_pwm::{MotorPwm, PwmPin};
+use plotter_core::plant_sim::{MotorPlant, PlantConfig, SimEncoder, SimPwmPin};
+
+/// us between two homing ticks
+const TICK: u64 = 1000;
+
+fn config(trigger: HomingTrigger) -> HomingConfig {
+    HomingConfig {
+        trigger,
+        dir: -1.0,
+        seek_duty: 60.0,
+        locate_duty: 25.0,
+        back_off: 200,
+        max_travel: 20_000,
+        stall_time: 50_000,
+    }
+}
+
+fn drive<T: PwmPin>(motor: &mut MotorPwm<T>, duty: f32) {
+    if duty < 0.0 {
+        motor.move_negative(-duty);
+    } else {
+        motor.move_positive(duty);
+    }
+}
+
+/// runs the cycle against the plant, `limit` says whether the switch is closed
+/// at a carriage position
+fn run(
+    plant: &RefCell<MotorPlant>,
+    config: HomingConfig,
+    limit: impl Fn(f32) -> bool,
+) -> HomingStep {
+    let mut motor = MotorPwm::new(SimPwmPin::new(plant));
+    let encoder = SimEncoder::new(plant);
+    motor.enable_pwm();
+
+    let mut homing = HomingCycle::new(config, plant.borrow().time(), encoder.pos());
+    for _ in 0..20_000 {
+        let now = plant.borrow().time();
+        let closed = limit(plant.borrow().load_pos());
+        match homing.tick(now, encoder.pos(), closed) {
+            HomingStep::Drive(duty) => drive(&mut motor, duty),
+            step => {
+                motor.active_stop();
+                return step;
+            }
+        }
+        plant.borrow_mut().step(TICK as f32 * 1e-6);
+    }
+    panic!("homing didn't finish");
+}
+
+#[test]
+fn finds_the_limit_switch() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    plant.borrow_mut().set_travel(-5000.0, 5000.0);
+
+    let step = run(&plant, config(HomingTrigger::LimitSwitch), |pos| {
+        pos <= -3000.0
+    });
+    match step {
+        HomingStep::Done(pos) => assert!((pos + 3000).abs() <= 5, "{}", pos),
+        _ => panic!("homing failed"),
+    }
+}
+
+#[test]
+fn a_switch_part_way_through_the_seek_ends_it() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    plant.borrow_mut().set_travel(-4000.0, 4000.0);
+
+    // closes while the carriage is still at seek speed, far from the stop
+    let step = run(&plant, config(HomingTrigger::LimitSwitch), |pos| {
+        pos <= -1500.0
+    });
+    match step {
+        HomingStep::Done(pos) => assert!((pos + 1500).abs() <= 5, "{}", pos),
+        _ => panic!("homing failed"),
+    }
+    assert!(plant.borrow().load_pos() > -2000.0);
+
+    // homing on a stall goes through the switch to the stop
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    plant.borrow_mut().set_travel(-4000.0, 4000.0);
+    match run(&plant, config(HomingTrigger::Stall), |pos| pos <= -1500.0) {
+        HomingStep::Done(pos) => assert!((pos + 4000).abs() <= 3, "{}", pos),
+        _ => panic!("homing failed"),
+    }
+}
+
+#[test]
+fn trigger_point_doesnt_depend_on_seek_speed() {
+    let mut found = [0; 2];
+    for (i, seek_duty) in [40.0, 100.0].iter().enumerate() {
+        let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+        let config = HomingConfig {
+            seek_duty: *seek_duty,
+            ..config(HomingTrigger::LimitSwitch)
+        };
+        match run(&plant, config, |pos| pos <= -3000.0) {
+            HomingStep::Done(pos) => found[i] = pos,
+            _ => panic!("homing failed"),
+        }
+    }
+    assert!((found[0] - found[1]).abs() <= 2, "{:?}", found);
+}
+
+#[test]
+fn finds_the_hard_stop_by_stalling() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::y_axis()));
+    plant.borrow_mut().set_travel(-4000.0, 4000.0);
+
+    match run(&plant, config(HomingTrigger::Stall), |_| false) {
+        HomingStep::Done(pos) => assert!((pos + 4000).abs() <= 3, "{}", pos),
+        _ => panic!("homing failed"),
+    }
+
+    // zeroing at the trigger point makes the stop the origin
+    SimEncoder::new(&plant).calibrate();
+    assert!(SimEncoder::new(&plant).pos().abs() <= 3);
+}
+
+#[test]
+fn gives_up_without_a_trigger() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let step = run(&plant, config(HomingTrigger::LimitSwitch), |_| false);
+    assert!(step == HomingStep::Failed(HomingError::NotFound));
+}
+
+#[test]
+fn stall_before_the_switch_is_an_error() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    plant.borrow_mut().set_travel(-1000.0, 1000.0);
+
+    let step = run(&plant, config(HomingTrigger::LimitSwitch), |pos| {
+        pos <= -3000.0
+    });
+    assert!(step == HomingStep::Failed(HomingError::Stuck));
+}
+
+#[test]
+fn switch_that_stays_closed_is_an_error() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let step = run(&plant, config(HomingTrigger::LimitSwitch), |pos| {
+        pos <= -3000.0 || pos > -2000.0
+    });
+    assert!(step == HomingStep::Failed(HomingError::SwitchStuck));
+}
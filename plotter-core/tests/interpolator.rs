@@ -0,0 +1,126 @@
+use plotter_core::interpolator::{CircularInterpolationDir, Interpolation, Interpolator};
+
+fn assert_near(a: (i32, i32), b: (i32, i32), tolerance: i32) {
+    assert!(
+        (a.0 - b.0).abs() <= tolerance && (a.1 - b.1).abs() <= tolerance,
+        "{:?} != {:?}",
+        a,
+        b
+    );
+}
+
+#[test]
+fn linear_steps_along_the_longer_axis() {
+    let interpolator = Interpolator::new((0, 0), (100, -50), Interpolation::Linear, 1.0);
+
+    assert_eq!(interpolator.get_interpolation_len(), 100);
+    assert_eq!(interpolator.get_interpolation_at(0), (0, 0));
+    assert_eq!(interpolator.get_interpolation_at(50), (50, -25));
+    assert_eq!(interpolator.get_interpolation_at(100), (100, -50));
+    // 50 mm by 100 mm
+    assert!((interpolator.path_len(0.5, 2.0) - 12_500.0f32.sqrt()).abs() < 1e-3);
+}
+
+#[test]
+fn quarter_circle_stays_on_the_radius() {
+    // counter clockwise around the origin from (100, 0) to (0, 100)
+    let interpolator = Interpolator::new(
+        (100, 0),
+        (0, 100),
+        Interpolation::Circular(-100.0, 0.0, CircularInterpolationDir::CounterClockwise),
+        1.0,
+    );
+
+    let len = interpolator.get_interpolation_len();
+    assert_eq!(len, 158);
+    for idx in 0..=len {
+        let (x, y) = interpolator.get_interpolation_at(idx);
+        let radius = ((x * x + y * y) as f32).sqrt();
+        assert!((radius - 100.0).abs() < 1.0, "radius {} at {}", radius, idx);
+    }
+    assert_near(interpolator.get_interpolation_at(len), (0, 100), 1);
+    assert_near(interpolator.get_interpolation_at(len / 2), (71, 71), 1);
+
+    let (tx, ty) = interpolator.tangent_at(0);
+    assert!(tx.abs() < 0.01 && ty > 0.99);
+}
+
+#[test]
+fn clockwise_takes_the_long_way() {
+    // through (0, -100) and (-100, 0)
+    let interpolator = Interpolator::new(
+        (100, 0),
+        (0, 100),
+        Interpolation::Circular(-100.0, 0.0, CircularInterpolationDir::Clockwise),
+        1.0,
+    );
+
+    let len = interpolator.get_interpolation_len();
+    assert_near(interpolator.get_interpolation_at(len / 3), (0, -100), 2);
+    assert_near(interpolator.get_interpolation_at(len * 2 / 3), (-100, 0), 2);
+    assert_near(interpolator.get_interpolation_at(len), (0, 100), 1);
+}
+
+#[test]
+fn circles_are_stretched_by_the_axis_ratio() {
+    // y units are 4 times finer, a circle in mm is 4 times taller in units
+    let interpolator = Interpolator::new(
+        (100, 0),
+        (-100, 0),
+        Interpolation::Circular(-100.0, 0.0, CircularInterpolationDir::CounterClockwise),
+        4.0,
+    );
+
+    let len = interpolator.get_interpolation_len();
+    assert_near(interpolator.get_interpolation_at(len / 2), (0, 400), 2);
+    assert_near(interpolator.get_interpolation_at(len), (-100, 0), 1);
+}
+
+#[test]
+fn rapid_jumps_to_the_end() {
+    let interpolator = Interpolator::new((3, 4), (300, -7), Interpolation::NoInterpolation, 1.0);
+
+    assert_eq!(interpolator.get_interpolation_len(), 1);
+    assert_eq!(interpolator.get_interpolation_at(0), (300, -7));
+}
+
+#[test]
+fn arc_bounds_include_the_bulge() {
+    // counter clockwise half circle around the origin from (100, 0) to (-100, 0)
+    let half = Interpolator::new(
+        (100, 0),
+        (-100, 0),
+        Interpolation::Circular(-100.0, 0.0, CircularInterpolationDir::CounterClockwise),
+        1.0,
+    );
+    let (min, max) = half.bounds();
+    assert_near(min, (-100, 0), 1);
+    assert_near(max, (100, 100), 1);
+
+    // the same points clockwise bulge the other way
+    let half = Interpolator::new(
+        (100, 0),
+        (-100, 0),
+        Interpolation::Circular(-100.0, 0.0, CircularInterpolationDir::Clockwise),
+        1.0,
+    );
+    let (min, max) = half.bounds();
+    assert_near(min, (-100, -100), 1);
+    assert_near(max, (100, 0), 1);
+}
+
+#[test]
+fn full_circle_bounds_are_stretched_on_y() {
+    let circle = Interpolator::new(
+        (100, 0),
+        (100, 0),
+        Interpolation::Circular(-100.0, 0.0, CircularInterpolationDir::Clockwise),
+        4.0,
+    );
+    let (min, max) = circle.bounds();
+    assert_near(min, (-100, -400), 1);
+    assert_near(max, (100, 400), 1);
+
+    let line = Interpolator::new((30, -20), (-10, 50), Interpolation::Linear, 1.0);
+    assert_eq!(line.bounds(), ((-10, -20), (30, 50)));
+}
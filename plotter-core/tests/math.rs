@@ -0,0 +1,33 @@
+//! `math::sqrt` against micromath's, which the planner, the interpolator and
+//! the speed profiles called directly before
+
+use micromath::F32Ext;
+use plotter_core::math::sqrt;
+
+/// relative error of `f` over 1e-3..1e6, the range of squared lengths in mm
+/// and squared speeds in mm/s the planner works with
+fn worst_error(f: impl Fn(f32) -> f32) -> f32 {
+    let mut worst = 0.0f32;
+    let mut x = 0.001f32;
+    while x < 1.0e6 {
+        let exact = (x as f64).sqrt() as f32;
+        worst = worst.max(((f(x) - exact) / exact).abs());
+        x *= 1.013;
+    }
+    worst
+}
+
+#[test]
+fn micromath_is_off_by_percents() {
+    // the radius of an arc through (1, 1) around the origin, 6 % too long
+    assert_eq!(F32Ext::sqrt(2.0f32), 1.5);
+    assert!(worst_error(F32Ext::sqrt) > 0.05);
+}
+
+#[test]
+fn newton_steps_bring_it_to_float_precision() {
+    assert!((sqrt(2.0) - core::f32::consts::SQRT_2).abs() < 1e-5);
+    assert!(worst_error(sqrt) < 1e-5, "{}", worst_error(sqrt));
+    assert_eq!(sqrt(0.0), 0.0);
+    assert_eq!(sqrt(-1.0), 0.0);
+}
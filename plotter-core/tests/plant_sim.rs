@@ -0,0 +1,118 @@
+use core::cell::RefCell;
+
+use plotter_core::encoder::Encoder;
+use plotter_core::motor_pwm::MotorPwm;
+use plotter_core::plant_sim::{MotorPlant, PlantConfig, SimEncoder, SimPwmPin};
+
+#[test]
+fn deadband_and_stiction_hold_the_motor() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+    let encoder = SimEncoder::new(&plant);
+
+    motor.enable_pwm();
+    motor.move_positive(10.0);
+    plant.borrow_mut().step(0.5);
+    assert_eq!(encoder.pos(), 0);
+
+    motor.move_positive(30.0);
+    plant.borrow_mut().step(0.5);
+    assert!(encoder.pos() > 0);
+    assert!(!encoder.dir());
+}
+
+#[test]
+fn full_duty_reaches_top_speed() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+
+    motor.enable_pwm();
+    motor.move_negative(100.0);
+    plant.borrow_mut().step(0.5);
+
+    let vel = plant.borrow().motor_vel();
+    assert!((vel + 4240.0).abs() < 50.0, "{}", vel);
+    assert!(SimEncoder::new(&plant).dir());
+}
+
+#[test]
+fn disabled_bridge_lets_the_motor_coast() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+
+    motor.enable_pwm();
+    motor.move_positive(100.0);
+    plant.borrow_mut().step(0.5);
+    motor.disable_pwm();
+    assert!(!plant.borrow().bridge_enabled());
+    assert_eq!(plant.borrow().duty(), 0.0);
+
+    plant.borrow_mut().step(0.01);
+    let coasting = plant.borrow().motor_vel();
+    assert!(coasting > 0.0);
+
+    plant.borrow_mut().step(1.0);
+    assert_eq!(plant.borrow().motor_vel(), 0.0);
+}
+
+#[test]
+fn braking_doesnt_enable_a_disabled_bridge() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::x_axis()));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+
+    motor.enable_pwm();
+    motor.move_positive(50.0);
+    motor.disable_pwm();
+    motor.active_stop();
+    assert!(!plant.borrow().bridge_enabled());
+
+    motor.enable_pwm();
+    assert!(plant.borrow().bridge_enabled());
+}
+
+#[test]
+fn backlash_delays_reversals() {
+    let config = PlantConfig {
+        backlash: 20.0,
+        ..PlantConfig::x_axis()
+    };
+    let plant = RefCell::new(MotorPlant::new(config));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+    let encoder = SimEncoder::new(&plant);
+
+    motor.enable_pwm();
+    motor.move_positive(30.0);
+    plant.borrow_mut().step(0.2);
+    motor.active_stop();
+    plant.borrow_mut().step(0.5);
+    let forward = encoder.pos();
+    assert!(forward > 0);
+
+    // the motor turns back through the play before the carriage follows
+    motor.move_negative(30.0);
+    let mut moved_back = false;
+    for _ in 0..10 {
+        plant.borrow_mut().step(0.001);
+        moved_back |= encoder.pos() < forward;
+    }
+    assert!(!moved_back);
+    plant.borrow_mut().step(0.3);
+    assert!(encoder.pos() < forward);
+}
+
+#[test]
+fn calibrate_zeroes_the_count() {
+    let plant = RefCell::new(MotorPlant::new(PlantConfig::y_axis()));
+    let mut motor = MotorPwm::new(SimPwmPin::new(&plant));
+    let encoder = SimEncoder::new(&plant);
+
+    motor.enable_pwm();
+    motor.move_positive(50.0);
+    plant.borrow_mut().step(0.1);
+    motor.active_stop();
+    plant.borrow_mut().step(0.5);
+
+    encoder.calibrate();
+    assert_eq!(encoder.pos(), 0);
+    assert!(plant.borrow().load_pos().abs() < 1.0);
+}
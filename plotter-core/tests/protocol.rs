@@ -0,0 +1,58 @@
+use plotter_core::protocol::*;
+
+#[test]
+fn parses_frames() {
+    match parse_frame(&[FRAME_MAGIC, FRAME_DATA, 3, 1, b'G', b'1']) {
+        Some(Ok(Frame::Data { seq, payload })) => {
+            assert_eq!(seq, 259);
+            assert_eq!(payload, b"G1");
+        }
+        _ => panic!("not a data frame"),
+    }
+
+    assert!(matches!(
+        parse_frame(&[FRAME_MAGIC, FRAME_SYNC, 7, 0]),
+        Some(Ok(Frame::Sync { seq: 7 }))
+    ));
+    assert!(parse_frame(b"G1 X1").is_none());
+    assert!(matches!(
+        parse_frame(&[FRAME_MAGIC, FRAME_DATA]),
+        Some(Err(()))
+    ));
+    assert!(matches!(
+        parse_frame(&[FRAME_MAGIC, 0x7F, 0, 0]),
+        Some(Err(()))
+    ));
+}
+
+#[test]
+fn encodes_replies() {
+    let mut buf = [0u8; REPLY_LEN];
+    assert_eq!(
+        Reply::Ack { seq: 513, free: 3 }.encode(&mut buf),
+        &[FRAME_MAGIC, FRAME_ACK, 1, 2, 3, 0, 0]
+    );
+    assert_eq!(
+        Reply::Nack {
+            seq: 2,
+            free: 300,
+            reason: NackReason::BufferFull
+        }
+        .encode(&mut buf),
+        &[FRAME_MAGIC, FRAME_NACK, 2, 0, 44, 1, 2]
+    );
+}
+
+#[test]
+fn tracker_wraps_around() {
+    let mut tracker = SequenceTracker::new();
+    tracker.sync(65534);
+
+    for seq in [65534, 65535, 0] {
+        assert!(matches!(tracker.classify(seq), Delivery::New));
+        tracker.accept();
+    }
+    assert_eq!(tracker.expected(), 1);
+    assert!(matches!(tracker.classify(65535), Delivery::Duplicate));
+    assert!(matches!(tracker.classify(5), Delivery::Gap));
+}
@@ -0,0 +1,21 @@
+use plotter_core::pwm_duty::PwmDutyCycle;
+
+#[test]
+fn percent_to_timer_value() {
+    let mut duty = PwmDutyCycle::new(12_000);
+    duty.set_duty_cycle(25.0);
+    assert_eq!(duty.get_duty_cycle_val(), 3000);
+    assert_eq!(duty.get_duty_cycle(), 25.0);
+
+    duty.set_duty_cycle_val(6000);
+    assert_eq!(duty.get_duty_cycle(), 50.0);
+
+    duty.set_duty_cycle(100.0);
+    assert_eq!(duty.get_duty_cycle_val(), 12_000);
+}
+
+#[test]
+#[should_panic]
+fn rejects_out_of_range() {
+    PwmDutyCycle::new(100).set_duty_cycle(100.5);
+}
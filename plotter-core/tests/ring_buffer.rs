@@ -0,0 +1,36 @@
+use plotter_core::ring_buffer::RingBuffer;
+
+#[test]
+fn fifo_order_across_the_wrap() {
+    let mut buffer: RingBuffer<u32, 4> = RingBuffer::new();
+
+    for round in 0..10 {
+        for i in 0..3 {
+            buffer.push_back(round * 3 + i).unwrap();
+        }
+        assert_eq!(buffer.front(), Some(&(round * 3)));
+        assert_eq!(buffer.back(), Some(&(round * 3 + 2)));
+        assert_eq!(buffer.get(1), Some(&(round * 3 + 1)));
+        for i in 0..3 {
+            assert_eq!(buffer.pop_front(), Some(round * 3 + i));
+        }
+        assert!(buffer.is_empty());
+    }
+}
+
+#[test]
+fn full_buffer_gives_the_element_back() {
+    let mut buffer: RingBuffer<u8, 2> = RingBuffer::new();
+    buffer.push_back(1).unwrap();
+    buffer.push_back(2).unwrap();
+
+    assert!(buffer.is_full());
+    assert_eq!(buffer.push_back(3), Err(3));
+    assert_eq!(buffer.get(2), None);
+
+    *buffer.get_mut(1).unwrap() = 5;
+    buffer.clear();
+    assert_eq!(buffer.len(), 0);
+    assert_eq!(buffer.pop_front(), None);
+    assert_eq!(buffer.capacity(), 2);
+}
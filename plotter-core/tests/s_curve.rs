@@ -0,0 +1,56 @@
+use plotter_core::s_curve::{max_entry_velocity, transition_dist, SCurveProfile};
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-2, "{} != {}", a, b);
+}
+
+#[test]
+fn full_profile_from_rest() {
+    // 0 -> 20 mm/s with a = 200, j = 4000: jerk phases of 0.05 s, constant
+    // accel for 0.05 s, 0.15 s and 1.5 mm per ramp
+    let profile = SCurveProfile::new(100.0, 0.0, 20.0, 0.0, 200.0, 4000.0).unwrap();
+
+    assert_close(profile.peak_vel(), 20.0);
+    assert_close(profile.duration(), 0.15 + 97.0 / 20.0 + 0.15);
+
+    assert_close(
+        profile.setpoint_at(0.05).0,
+        4000.0 * 0.05 * 0.05 * 0.05 / 6.0,
+    );
+    assert_close(profile.setpoint_at(0.05).1, 5.0);
+    assert_close(profile.setpoint_at(0.1).1, 15.0);
+    assert_close(profile.setpoint_at(0.15).0, 1.5);
+    assert_close(profile.setpoint_at(0.15).1, 20.0);
+
+    let (end_pos, end_vel) = profile.setpoint_at(profile.duration());
+    assert_close(end_pos, 100.0);
+    assert_close(end_vel, 0.0);
+}
+
+#[test]
+fn short_segment_never_reaches_max_vel() {
+    let profile = SCurveProfile::new(1.0, 0.0, 50.0, 0.0, 200.0, 4000.0).unwrap();
+
+    assert!(profile.peak_vel() < 50.0);
+    assert_close(profile.setpoint_at(profile.duration()).0, 1.0);
+    assert_close(profile.velocity_at(0.0), 0.0);
+    assert_close(profile.velocity_at(0.5), profile.peak_vel());
+}
+
+#[test]
+fn velocity_at_distance_matches_time_setpoints() {
+    let profile = SCurveProfile::new(20.0, 5.0, 30.0, 10.0, 200.0, 4000.0).unwrap();
+
+    let mut t = 0.0;
+    while t < profile.duration() {
+        let (pos, vel) = profile.setpoint_at(t);
+        assert_close(profile.velocity_at(pos), vel);
+        t += 0.01;
+    }
+}
+
+#[test]
+fn entry_velocity_allows_stopping_in_time() {
+    let entry = max_entry_velocity(0.0, 5.0, 200.0, 4000.0);
+    assert_close(transition_dist(entry, 0.0, 200.0, 4000.0), 5.0);
+}
@@ -0,0 +1,151 @@
+use plotter_core::interpolator::{CircularInterpolationDir, Interpolation};
+use plotter_core::pen::PenPosition;
+use plotter_core::sequence::{Sequence, SequenceError, SEQUENCE_CAPACITY};
+use plotter_core::soft_limits::SoftLimits;
+
+const DOWN: PenPosition = PenPosition::Angle(100);
+
+fn sequence() -> Sequence {
+    let mut sequence = Sequence::new();
+    sequence.set_unit_lengths(0.042, 0.0105);
+    sequence.set_planner_limits(200.0, 0.05);
+    sequence
+}
+
+fn line(sequence: &mut Sequence, x: i32, y: i32) {
+    sequence
+        .add_pos(x, y, DOWN, Interpolation::Linear, 1200.0)
+        .unwrap();
+}
+
+#[test]
+fn streams_more_segments_than_it_holds() {
+    let mut sequence = sequence();
+    let mut added = 0;
+    let mut executed = 0;
+    let mut prev_end = (0, 0);
+
+    while executed < 3 * SEQUENCE_CAPACITY as i32 {
+        while sequence.has_free_space() {
+            added += 1;
+            line(&mut sequence, added * 10, (added % 7) * 100);
+        }
+        assert!(sequence
+            .add_pos(0, 0, DOWN, Interpolation::Linear, 1200.0)
+            .is_err());
+
+        for _ in 0..300 {
+            sequence.advance().unwrap();
+            executed += 1;
+            let sqv = sequence.curr_pos();
+            assert_eq!(sqv.start(), prev_end, "chain broken at {}", executed);
+            prev_end = sqv.end();
+        }
+    }
+    assert_eq!(sequence.curr_idx(), executed as u32);
+}
+
+#[test]
+fn last_segment_ends_at_standstill() {
+    let mut sequence = sequence();
+    for i in 1..=10 {
+        line(&mut sequence, i * 1000, 0);
+    }
+
+    // nothing after the newest segment, it has to be able to stop
+    let last = sequence.last_pos();
+    assert!(last.entry_vel() <= (2.0f32 * 200.0 * last.length()).sqrt() + 1e-3);
+    // the first one starts from standstill
+    sequence.advance().unwrap();
+    assert_eq!(sequence.curr_pos().entry_vel(), 0.0);
+    for _ in 0..9 {
+        sequence.advance().unwrap();
+        assert!(sequence.curr_pos().entry_vel() > 0.0);
+    }
+    assert_eq!(sequence.exit_vel(), 0.0);
+    assert!(sequence.advance().is_none());
+}
+
+#[test]
+fn corners_are_slower_than_straights() {
+    let mut straight = sequence();
+    line(&mut straight, 1000, 0);
+    line(&mut straight, 2000, 0);
+    line(&mut straight, 3000, 0);
+
+    let mut corner = sequence();
+    line(&mut corner, 1000, 0);
+    line(&mut corner, 1000, 4000);
+    line(&mut corner, 1000, 8000);
+
+    let mut reversal = sequence();
+    line(&mut reversal, 1000, 0);
+    line(&mut reversal, 0, 0);
+    line(&mut reversal, 1000, 0);
+
+    for sequence in [&mut straight, &mut corner, &mut reversal] {
+        sequence.advance().unwrap();
+    }
+    let (straight, corner, reversal) =
+        (straight.exit_vel(), corner.exit_vel(), reversal.exit_vel());
+    // capped by the feedrate, 1200 mm/min
+    assert!((straight - 20.0).abs() < 1e-3, "{}", straight);
+    assert!(corner > 0.0 && corner < straight, "{}", corner);
+    assert_eq!(reversal, 0.0);
+}
+
+#[test]
+fn pen_changes_stop_the_tool() {
+    let mut sequence = sequence();
+    line(&mut sequence, 1000, 0);
+    sequence
+        .add_pos(2000, 0, PenPosition::Default, Interpolation::Linear, 1200.0)
+        .unwrap();
+    line(&mut sequence, 3000, 0);
+
+    sequence.advance().unwrap();
+    assert_eq!(sequence.exit_vel(), 0.0);
+}
+
+#[test]
+fn clear_restarts_from_the_given_position() {
+    let mut sequence = sequence();
+    line(&mut sequence, 1000, 0);
+    line(&mut sequence, 2000, 0);
+    sequence.advance().unwrap();
+
+    sequence.clear_sequence((1234, 56));
+    assert_eq!(sequence.sequence_len(), 1);
+    assert_eq!(sequence.curr_idx(), 0);
+    assert_eq!(sequence.curr_pos().end(), (1234, 56));
+
+    line(&mut sequence, 0, 0);
+    assert_eq!(sequence.last_pos().start(), (1234, 56));
+}
+
+#[test]
+fn moves_leaving_the_soft_limits_are_rejected() {
+    let mut sequence = sequence();
+    sequence.set_soft_limits(Some(SoftLimits::new((0, 0), (1000, 2200))));
+
+    line(&mut sequence, 1000, 500);
+    assert_eq!(
+        sequence.add_pos(1001, 500, DOWN, Interpolation::Linear, 1200.0),
+        Err(SequenceError::OutOfBounds)
+    );
+    // both ends inside, but the arc swings out to y = 2500, arcs are circles
+    // in mm and a y unit is a quarter of an x unit
+    let arc = Interpolation::Circular(-500.0, 0.0, CircularInterpolationDir::CounterClockwise);
+    assert_eq!(
+        sequence.add_pos(0, 500, DOWN, arc, 1200.0),
+        Err(SequenceError::OutOfBounds)
+    );
+    assert_eq!(sequence.last_pos().end(), (1000, 500));
+
+    let arc = Interpolation::Circular(-400.0, 0.0, CircularInterpolationDir::CounterClockwise);
+    sequence.add_pos(200, 500, DOWN, arc, 1200.0).unwrap();
+
+    // clearing outside the envelope still works, it's where the machine is
+    sequence.clear_sequence((-50, 0));
+    assert_eq!(sequence.curr_pos().end(), (-50, 0));
+}
@@ -0,0 +1,166 @@
+use plotter_core::interpolator::CircularInterpolationDir;
+use plotter_core::sequence::SequenceError;
+use plotter_core::sequence_wrapper::{PositioningMode, SequenceWrapper, Units};
+use plotter_core::soft_limits::SoftLimits;
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 0.05, "{} != {}", a, b);
+}
+
+fn end_mm(wrapper: &SequenceWrapper) -> (f32, f32) {
+    wrapper.pos_to_mm(wrapper.sequence.last_pos().end())
+}
+
+#[test]
+fn positions_are_converted_to_encoder_units() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.pos(42.0, 10.5).unwrap();
+
+    assert_eq!(wrapper.sequence.last_pos().end(), (1000, 1000));
+    assert_close(
+        wrapper.sequence.last_pos().length(),
+        (42.0f32 * 42.0 + 10.5 * 10.5).sqrt(),
+    );
+}
+
+#[test]
+fn inches_and_relative_moves() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.set_units(Units::Inches);
+    wrapper.pos(1.0, 1.0).unwrap();
+    assert_close(end_mm(&wrapper).0, 25.4);
+    assert_close(end_mm(&wrapper).1, 25.4);
+
+    wrapper.set_units(Units::Millimetres);
+    wrapper.set_positioning(PositioningMode::Relative);
+    wrapper.pos_x(10.0).unwrap();
+    wrapper.pos_y(-5.0).unwrap();
+    assert_close(end_mm(&wrapper).0, 35.4);
+    assert_close(end_mm(&wrapper).1, 20.4);
+    assert_eq!(wrapper.unchanged_pos(), (0.0, 0.0));
+}
+
+#[test]
+fn set_pos_moves_the_origin() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.pos(10.0, 10.0).unwrap();
+    let end = wrapper.sequence.last_pos().end();
+
+    // G92 X0 Y0, the tool stays where it is
+    wrapper.set_pos(Some(0.0), Some(0.0));
+    assert_eq!(wrapper.unchanged_pos(), (0.0, 0.0));
+    wrapper.pos(0.0, 0.0).unwrap();
+    assert_eq!(wrapper.sequence.last_pos().end(), end);
+}
+
+#[test]
+fn arc_by_radius_ends_on_target() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.pos(10.0, 0.0).unwrap();
+    wrapper
+        .arc_radius(0.0, 10.0, 10.0, CircularInterpolationDir::CounterClockwise)
+        .unwrap();
+
+    let sqv = wrapper.sequence.last_pos();
+    let interpolator = sqv.interpolator;
+    let len = interpolator.get_interpolation_len();
+    let (x, y) = wrapper.pos_to_mm(interpolator.get_interpolation_at(len / 2));
+    assert_close(x, 10.0 * core::f32::consts::FRAC_1_SQRT_2);
+    assert_close(y, 10.0 * core::f32::consts::FRAC_1_SQRT_2);
+    let (x, y) = wrapper.pos_to_mm(interpolator.get_interpolation_at(len));
+    assert_close(x, 0.0);
+    assert_close(y, 10.0);
+    assert_close(sqv.length(), 10.0 * core::f32::consts::FRAC_PI_2);
+
+    // a negative radius picks the longer arc back to the start
+    wrapper
+        .arc_radius(10.0, 0.0, -10.0, CircularInterpolationDir::Clockwise)
+        .unwrap();
+    assert_close(
+        wrapper.sequence.last_pos().length(),
+        10.0 * 3.0 * core::f32::consts::FRAC_PI_2,
+    );
+}
+
+#[test]
+fn arc_by_radius_to_the_current_position_is_rejected() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.pos(10.0, 0.0).unwrap();
+    let len = wrapper.sequence.sequence_len();
+
+    assert_eq!(
+        wrapper.arc_radius(10.0, 0.0, 5.0, CircularInterpolationDir::Clockwise),
+        Err(SequenceError::InvalidTarget)
+    );
+    assert_eq!(wrapper.sequence.sequence_len(), len);
+}
+
+#[test]
+fn arc_by_radius_shorter_than_half_the_chord_is_rejected() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.pos(10.0, 0.0).unwrap();
+    let len = wrapper.sequence.sequence_len();
+
+    assert_eq!(
+        wrapper.arc_radius(30.0, 0.0, 5.0, CircularInterpolationDir::Clockwise),
+        Err(SequenceError::InvalidTarget)
+    );
+    assert_eq!(wrapper.sequence.sequence_len(), len);
+    assert_close(end_mm(&wrapper).0, 10.0);
+
+    // exactly half of it is a half circle
+    wrapper
+        .arc_radius(30.0, 0.0, 10.0, CircularInterpolationDir::Clockwise)
+        .unwrap();
+    assert_close(
+        wrapper.sequence.last_pos().length(),
+        10.0 * core::f32::consts::PI,
+    );
+}
+
+#[test]
+fn clear_lifts_the_pen_and_keeps_the_position() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.pen_pos(100.0);
+    wrapper.pos(20.0, 20.0).unwrap();
+
+    wrapper.clear((238, 952));
+    assert_eq!(wrapper.curr_pos().end(), (238, 952));
+    assert_close(wrapper.unchanged_pos().0, 238.0 * 0.042);
+    assert_close(wrapper.unchanged_pos().1, 952.0 * 0.0105);
+    assert!(wrapper.curr_pos().pen() == plotter_core::pen::PenPosition::Default);
+}
+
+#[test]
+fn rejected_moves_keep_the_programmed_position() {
+    let mut wrapper = SequenceWrapper::new();
+    let limits = SoftLimits::from_mm((0.0, 0.0), (100.0, 50.0), wrapper.unit_lengths());
+    assert_eq!(limits.max, (2380, 4761));
+    wrapper.sequence.set_soft_limits(Some(limits));
+
+    wrapper.set_positioning(PositioningMode::Relative);
+    wrapper.pos(60.0, 10.0).unwrap();
+    assert_eq!(wrapper.pos(60.0, 10.0), Err(SequenceError::OutOfBounds));
+    assert_eq!(wrapper.pos_y(-20.0), Err(SequenceError::OutOfBounds));
+
+    // relative moves continue from the last accepted one
+    wrapper.pos(30.0, 10.0).unwrap();
+    assert_close(end_mm(&wrapper).0, 90.0);
+    assert_close(end_mm(&wrapper).1, 20.0);
+}
+
+#[test]
+fn feedrates_are_clamped_to_the_maximum() {
+    let mut wrapper = SequenceWrapper::new();
+    wrapper.set_max_feedrate(2000.0);
+    wrapper.set_feedrate(5000.0);
+    wrapper.pos(10.0, 0.0).unwrap();
+    assert_eq!(wrapper.sequence.last_pos().feedrate(), 2000.0);
+
+    wrapper.pos_rapid(0.0, 0.0).unwrap();
+    assert_eq!(wrapper.sequence.last_pos().feedrate(), 2000.0);
+
+    wrapper.set_feedrate(500.0);
+    wrapper.pos(10.0, 0.0).unwrap();
+    assert_eq!(wrapper.sequence.last_pos().feedrate(), 500.0);
+}
@@ -0,0 +1,105 @@
+use plotter_core::settings::*;
+
+fn changed() -> Settings {
+    let mut settings = DEFAULTS;
+    settings.unit_length = (0.04, 0.01);
+    settings.max_speed = 80.0;
+    settings.x_pid.ki = 2.5;
+    settings.pen_up_angle = 75;
+    settings.pwm_freq = (400, 90);
+    settings.mac = [0x02, 1, 2, 3, 4, 5];
+    settings.remote_port = 4000;
+    settings
+}
+
+#[test]
+fn blocks_round_trip() {
+    let mut buf = [0xFFu8; MAX_BLOCK_LEN];
+    let len = changed().encode(&mut buf);
+    assert!(len <= MAX_BLOCK_LEN);
+
+    assert_eq!(Settings::decode(&buf), Ok(changed()));
+    assert_eq!(Settings::decode(&buf[..len]), Ok(changed()));
+}
+
+#[test]
+fn erased_flash_is_blank() {
+    assert_eq!(
+        Settings::decode(&[0xFF; MAX_BLOCK_LEN]),
+        Err(SettingsError::Blank)
+    );
+    assert_eq!(
+        Settings::decode(&[0u8; MAX_BLOCK_LEN]),
+        Err(SettingsError::Corrupt)
+    );
+}
+
+#[test]
+fn damaged_blocks_are_rejected() {
+    let mut buf = [0xFFu8; MAX_BLOCK_LEN];
+    let len = DEFAULTS.encode(&mut buf);
+
+    let mut flipped = buf;
+    flipped[20] ^= 0x10;
+    assert_eq!(Settings::decode(&flipped), Err(SettingsError::Corrupt));
+
+    assert_eq!(
+        Settings::decode(&buf[..len - 1]),
+        Err(SettingsError::Corrupt)
+    );
+}
+
+/// recomputes the header and crc after the block was edited
+fn reseal(buf: &mut [u8], version: u16, payload_len: usize) -> usize {
+    buf[4..6].copy_from_slice(&version.to_le_bytes());
+    buf[6..8].copy_from_slice(&(payload_len as u16).to_le_bytes());
+    let crc_at = 8 + payload_len;
+    let crc = crc32(&buf[..crc_at]);
+    buf[crc_at..crc_at + 4].copy_from_slice(&crc.to_le_bytes());
+    crc_at + 4
+}
+
+#[test]
+fn shorter_blocks_keep_defaults_for_what_they_miss() {
+    let mut buf = [0xFFu8; MAX_BLOCK_LEN];
+    changed().encode(&mut buf);
+    // an older firmware that only stored the unit lengths
+    let len = reseal(&mut buf, VERSION, 8);
+
+    let settings = Settings::decode(&buf[..len]).unwrap();
+    assert_eq!(settings.unit_length, (0.04, 0.01));
+    assert_eq!(settings.max_speed, DEFAULTS.max_speed);
+    assert_eq!(settings.pen_up_angle, DEFAULTS.pen_up_angle);
+    assert_eq!(settings.mac, DEFAULTS.mac);
+}
+
+#[test]
+fn newer_versions_are_not_guessed_at() {
+    let mut buf = [0xFFu8; MAX_BLOCK_LEN];
+    let len = DEFAULTS.encode(&mut buf);
+    let payload_len = len - 12;
+    reseal(&mut buf, VERSION + 1, payload_len);
+
+    assert_eq!(
+        Settings::decode(&buf),
+        Err(SettingsError::Unsupported(VERSION + 1))
+    );
+}
+
+#[test]
+fn crc_matches_the_usual_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn derived_macs_are_local_unicast_and_differ_per_chip() {
+    let a = derived_mac(&[1, 0, 2, 0, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x01]);
+    let b = derived_mac(&[2, 0, 2, 0, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x01]);
+
+    assert_eq!(a[0] & 0b11, 0b10);
+    assert_ne!(a, b);
+    assert_eq!(
+        a,
+        derived_mac(&[1, 0, 2, 0, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x01])
+    );
+}
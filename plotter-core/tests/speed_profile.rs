@@ -0,0 +1,72 @@
+use plotter_core::speed_profile::{MotionProfile, SpeedProfile};
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-2, "{} != {}", a, b);
+}
+
+#[test]
+fn trapezoid_from_rest() {
+    // 0 -> 20 mm/s at 200 mm/s^2 takes 0.1 s and 1 mm
+    let profile = SpeedProfile::new(100.0, 0.0, 20.0, 0.0, 200.0).unwrap();
+
+    assert_close(profile.accel_dist(), 1.0);
+    assert_close(profile.decel_dist(), 1.0);
+    assert_close(profile.cruise_dist(), 98.0);
+    assert_close(profile.duration(), 0.1 + 98.0 / 20.0 + 0.1);
+
+    assert_close(profile.velocity_at(0.0), 0.0);
+    assert_close(profile.velocity_at(0.5), 200.0f32.sqrt());
+    assert_close(profile.velocity_at(50.0), 20.0);
+    assert_close(profile.velocity_at(100.0), 0.0);
+
+    assert_close(profile.setpoint_at(0.05).0, 0.25);
+    assert_close(profile.setpoint_at(0.05).1, 10.0);
+    let (end_pos, end_vel) = profile.setpoint_at(profile.duration());
+    assert_close(end_pos, 100.0);
+    assert_close(end_vel, 0.0);
+}
+
+#[test]
+fn short_segment_is_a_triangle() {
+    let profile = SpeedProfile::new(1.0, 0.0, 50.0, 0.0, 200.0).unwrap();
+
+    assert_close(profile.peak_vel(), 200.0f32.sqrt());
+    assert_close(profile.accel_dist(), 0.5);
+    assert_close(profile.cruise_dist(), 0.0);
+    assert_close(profile.velocity_at(0.5), profile.peak_vel());
+}
+
+#[test]
+fn unreachable_exit_is_clamped() {
+    // 1 mm is only enough to get to 20 mm/s
+    let profile = SpeedProfile::new(1.0, 0.0, 50.0, 30.0, 200.0).unwrap();
+    assert_close(profile.exit_vel(), 20.0);
+
+    // and only enough to slow down from 30 to 10*sqrt(5) mm/s
+    let profile = SpeedProfile::new(1.0, 30.0, 50.0, 0.0, 200.0).unwrap();
+    assert_close(profile.exit_vel(), 500.0f32.sqrt());
+}
+
+#[test]
+fn entry_and_exit_are_capped_at_max_vel() {
+    let profile = SpeedProfile::new(10.0, 40.0, 20.0, 40.0, 200.0).unwrap();
+    assert_close(profile.entry_vel(), 20.0);
+    assert_close(profile.exit_vel(), 20.0);
+    assert_close(profile.duration(), 0.5);
+}
+
+#[test]
+fn invalid_arguments() {
+    assert!(SpeedProfile::new(-1.0, 0.0, 20.0, 0.0, 200.0).is_err());
+    assert!(SpeedProfile::new(1.0, 0.0, 0.0, 0.0, 200.0).is_err());
+    assert!(SpeedProfile::new(1.0, 0.0, 20.0, 0.0, 0.0).is_err());
+    assert!(SpeedProfile::new(1.0, -1.0, 20.0, 0.0, 200.0).is_err());
+}
+
+#[test]
+fn standstill_never_moves() {
+    let profile = MotionProfile::standstill();
+    assert_eq!(profile.duration(), 0.0);
+    assert_eq!(profile.setpoint_at(1.0), (0.0, 0.0));
+    assert_eq!(profile.exit_vel(), 0.0);
+}
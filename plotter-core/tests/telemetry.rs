@@ -0,0 +1,108 @@
+//! the packets have to stay readable by `tools/telemetry.py`, which unpacks
+//! the header as `<BBHBx` and every record as `<I` followed by `iihf` for x
+//! and y
+
+use plotter_core::telemetry::{AxisSample, Telemetry, PACKET_MAGIC, RECORD_LEN};
+use plotter_core::transport::Peer;
+
+const HOST: Peer = Peer {
+    addr: [192, 168, 1, 10],
+    port: 5000,
+};
+/// `struct.calcsize("<BBHBx")`
+const HEADER_LEN: usize = 6;
+/// what telemetry.py reads at most from one packet
+const RECORDS_PER_PACKET: usize = 16;
+
+/// `<BBHBx`: magic, version, seq, count
+fn header(packet: &[u8]) -> (u8, u8, u16, u8) {
+    (
+        packet[0],
+        packet[1],
+        u16::from_le_bytes([packet[2], packet[3]]),
+        packet[4],
+    )
+}
+
+/// `iihf`: target, actual, pwm, speed
+fn axis(buf: &[u8]) -> (i32, i32, i16, f32) {
+    (
+        i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]),
+        i32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]),
+        i16::from_le_bytes([buf[8], buf[9]]),
+        f32::from_le_bytes([buf[10], buf[11], buf[12], buf[13]]),
+    )
+}
+
+fn sample(pos: i32) -> AxisSample {
+    AxisSample {
+        target_pos: pos,
+        actual_pos: pos - 3,
+        pwm: -42.5,
+        speed: 120.25,
+    }
+}
+
+#[test]
+fn packets_match_the_python_layout() {
+    assert_eq!(RECORD_LEN, 4 + 2 * (4 + 4 + 2 + 4));
+
+    let mut telemetry = Telemetry::new();
+    telemetry.subscribe(HOST, 1000);
+    for idx in 0..RECORDS_PER_PACKET as u64 - 1 {
+        assert!(telemetry
+            .record(1000 * idx, sample(idx as i32), sample(-(idx as i32)))
+            .is_none());
+    }
+
+    let last = RECORDS_PER_PACKET as u64 - 1;
+    let (packet, peer) = telemetry
+        .record(1000 * last, sample(last as i32), sample(-(last as i32)))
+        .unwrap();
+    assert_eq!(peer, HOST);
+    assert_eq!(packet.len(), HEADER_LEN + RECORDS_PER_PACKET * RECORD_LEN);
+    let (magic, version, seq, count) = header(packet);
+    assert_eq!(magic, PACKET_MAGIC);
+    assert_eq!(version, 1);
+    assert_eq!(seq, 0);
+    assert_eq!(count as usize, RECORDS_PER_PACKET);
+    assert_eq!(packet[5], 0);
+
+    // `<I` then `iihf` for x and y
+    let record = &packet[HEADER_LEN + 2 * RECORD_LEN..HEADER_LEN + 3 * RECORD_LEN];
+    assert_eq!(
+        u32::from_le_bytes([record[0], record[1], record[2], record[3]]),
+        2000
+    );
+    assert_eq!(axis(&record[4..18]), (2, -1, -4250, 120.25));
+    assert_eq!(axis(&record[18..32]), (-2, -5, -4250, 120.25));
+}
+
+#[test]
+fn old_records_go_out_before_the_packet_is_full() {
+    let mut telemetry = Telemetry::new();
+    telemetry.subscribe(HOST, 10);
+    assert!(telemetry.record(0, sample(0), sample(0)).is_none());
+
+    let (packet, _) = telemetry.record(100_000, sample(1), sample(1)).unwrap();
+    assert_eq!(header(packet).3, 2);
+    assert_eq!(packet.len(), HEADER_LEN + 2 * RECORD_LEN);
+}
+
+#[test]
+fn sequence_numbers_wrap() {
+    let mut telemetry = Telemetry::new();
+    telemetry.subscribe(HOST, 10);
+
+    let mut now = 0;
+    let mut last_seq = None;
+    for _ in 0..=u16::MAX as u32 + 1 {
+        assert!(telemetry.record(now, sample(0), sample(0)).is_none());
+        now += 100_000;
+        let (packet, _) = telemetry.record(now, sample(0), sample(0)).unwrap();
+        now += 100_000;
+        last_seq = Some(header(packet).2);
+    }
+    // 65537 packets, the last one starts over at 0
+    assert_eq!(last_seq, Some(0));
+}
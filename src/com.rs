@@ -1,100 +1,94 @@
-//command_handler.rs v2
+//! the firmware's command transports, the handler itself is
+//! `plotter_core::com`
 
-use heapless::consts::*;
-use heapless::Vec;
-
-use crate::ethernet::ethernet_wrapper::EthernetWrapper;
-use crate::BufWriter;
-use cortex_m_semihosting::hprintln;
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
 
 use crate::ethernet::global_ethernet;
-use global_ethernet::eth_send;
-
-pub struct CommandHandler {
-    recv_buffer: [u8; 576],
-    gcode_buffer: Vec<gcode::GCode, U512>,
-
-    buffer_read_offset: usize,
-
-    page_count: usize,
-
-    buffer_empty_sent: bool,
-    buffer_full_sent: bool,
-
-    calibration_request: bool,
+use crate::transport::{CommandTransport, Framing, Peer};
+use crate::usb_com;
+use crate::SystemClock;
+
+pub use plotter_core::com::{RealtimeCommand, TelemetryRequest};
+
+pub type CommandHandler = plotter_core::com::CommandHandler<Transport, SystemClock>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Transport {
+    /// datagrams on the command port
+    Udp,
+    /// the one client connected to the command port
+    Tcp,
+    /// the cdc serial port
+    Usb,
 }
 
-impl CommandHandler {
-    pub fn new() -> Self {
-        Self {
-            recv_buffer: [0u8; 576],
-            gcode_buffer: Vec::new(),
-
-            page_count: 0,
-
-            buffer_read_offset: 0,
-
-            buffer_empty_sent: false,
-            buffer_full_sent: false,
-
-            calibration_request: false,
+impl CommandTransport for Transport {
+    fn framing(&self) -> Framing {
+        match self {
+            Transport::Udp => Framing::Datagram,
+            Transport::Tcp | Transport::Usb => Framing::Stream,
         }
     }
 
-    #[inline]
-    pub fn tick(&mut self) {
-        if !self.buffer_empty_sent && self.gcode_buffer.len() == 0 {
-            eth_send!("[{}] buffer empty\n\r", self.page_count);
-            // eth.send(buf_writer);
-            self.buffer_empty_sent = true;
-            self.buffer_full_sent = false;
+    fn recv(&mut self, buf: &mut [u8]) -> Option<(usize, Peer)> {
+        match self {
+            Transport::Udp => {
+                global_ethernet::recv_from(buf).map(|(data, sender)| (data.len(), peer(sender)))
+            }
+            Transport::Tcp => {
+                global_ethernet::recv_tcp(buf).map(|(data, sender)| (data.len(), peer(sender)))
+            }
+            Transport::Usb => match usb_com::read(buf) {
+                0 => None,
+                len => Some((len, Peer::NONE)),
+            },
         }
+    }
 
-        let data = global_ethernet::recv(&mut self.recv_buffer);
-
-        if let None = data {
-            return;
+    fn send(&mut self, data: &[u8]) -> Result<(), ()> {
+        match self {
+            Transport::Udp => global_ethernet::send(data),
+            Transport::Tcp => global_ethernet::send_tcp(data),
+            Transport::Usb => usb_com::send(data),
         }
-        let data = data.unwrap();
+    }
 
-        if let Ok(code_str) = core::str::from_utf8(data) {
-            let codes = gcode::parse(&code_str);
-            for code in codes {
-                if let Err(_) = self.gcode_buffer.push(code) {
-                    eth_send!("write failed, buffer full\n\r");
-                    break;
-                }
-            }
-            if self.gcode_buffer.len() >= 511 {
-                eth_send!("[{}] buffer full", self.page_count);
-                if !self.buffer_full_sent {
-                    self.page_count += 1;
-                }
-                self.buffer_full_sent = true;
-                self.buffer_empty_sent = false;
-            }
+    /// only udp can reach someone other than who sent the last message
+    fn send_to(&mut self, data: &[u8], peer: Peer) -> Result<(), ()> {
+        match self {
+            Transport::Udp => global_ethernet::send_to(data, endpoint(peer)),
+            Transport::Tcp | Transport::Usb => Err(()),
         }
     }
 
-    #[inline]
-    pub fn needs_calibration(&mut self) -> bool {
-        let calib_rqst = self.calibration_request;
-        self.calibration_request = false;
-        calib_rqst
+    fn take_disconnected(&mut self) -> bool {
+        match self {
+            Transport::Udp => false,
+            Transport::Tcp => global_ethernet::take_tcp_dropped(),
+            Transport::Usb => usb_com::take_disconnected(),
+        }
     }
+}
 
-    #[inline]
-    pub fn clear_gcode_buffer(&mut self) {
-        // if self.gcode_buffer.len() >= 500 { //dont wanna fill the entire thing up or else it will send `buffer full` message
-        self.gcode_buffer.clear();
-        // self.buffer_read_offset = 0;
-        // } else if self.gcode_buffer.len() != 0 {
-        //     self.buffer_read_offset = self.gcode_buffer.len() - 1;
-        // }
+/// messages nobody asked for, like `eth_send`'s, go to the tcp client if one
+/// is connected, to the udp host otherwise, and to the usb host
+pub fn broadcast(data: &[u8]) {
+    if Transport::Tcp.send(data).is_err() {
+        let _ = Transport::Udp.send(data);
     }
+    let _ = Transport::Usb.send(data);
+}
 
-    #[inline]
-    pub fn get_gcode_buffer(&self) -> &[gcode::GCode] {
-        &self.gcode_buffer
+pub fn peer(endpoint: IpEndpoint) -> Peer {
+    match endpoint.addr {
+        IpAddress::Ipv4(addr) => Peer {
+            addr: addr.0,
+            port: endpoint.port,
+        },
+        _ => Peer::NONE,
     }
 }
+
+pub fn endpoint(peer: Peer) -> IpEndpoint {
+    IpEndpoint::new(Ipv4Address(peer.addr).into(), peer.port)
+}
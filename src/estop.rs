@@ -0,0 +1,66 @@
+//! emergency stop. the button opens a normally closed contact to ground, so a
+//! broken wire stops the machine as well. the interrupt cuts both h-bridges
+//! right away, the motion controller picks up the latch on its next tick and
+//! stays in alarm until it's unlocked.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use stm32h7xx_hal::gpio::gpiod::PD2;
+use stm32h7xx_hal::gpio::{Edge, ExtiPin, Input, PullUp};
+use stm32h7xx_hal::hal::digital::v2::InputPin;
+use stm32h7xx_hal::pac;
+
+/// high while the stop is engaged, on EXTI2
+type EstopPin = PD2<Input<PullUp>>;
+
+static mut ESTOP_PIN: Option<EstopPin> = None;
+/// set by the interrupt, cleared by `take_triggered`
+static TRIGGERED: AtomicBool = AtomicBool::new(false);
+
+pub fn init(mut pin: EstopPin, syscfg: &mut pac::SYSCFG, exti: &mut pac::EXTI) {
+    pin.make_interrupt_source(syscfg);
+    pin.trigger_on_edge(exti, Edge::Rising);
+    pin.enable_interrupt(exti);
+
+    // engaged before the interrupt could see an edge
+    if pin.is_high().unwrap_or(true) {
+        cut_bridges();
+        TRIGGERED.store(true, Ordering::SeqCst);
+    }
+    unsafe { ESTOP_PIN = Some(pin) };
+}
+
+/// drives the h-bridge enables of both axes (PG14, PE8) low straight through
+/// the registers, safe to call from any context, a fault handler included
+#[inline]
+pub fn cut_bridges() {
+    unsafe {
+        (*pac::GPIOG::ptr()).bsrr.write(|w| w.bits(1 << (14 + 16)));
+        (*pac::GPIOE::ptr()).bsrr.write(|w| w.bits(1 << (8 + 16)));
+    }
+}
+
+/// whether the stop is engaged right now, also true before `init`
+#[inline]
+pub fn is_engaged() -> bool {
+    match unsafe { ESTOP_PIN.as_ref() } {
+        Some(pin) => pin.is_high().unwrap_or(true),
+        None => true,
+    }
+}
+
+/// whether the stop was hit since the last call
+#[inline]
+pub fn take_triggered() -> bool {
+    TRIGGERED.swap(false, Ordering::SeqCst)
+}
+
+/// body of the EXTI2 interrupt
+#[inline]
+pub fn on_interrupt() {
+    cut_bridges();
+    TRIGGERED.store(true, Ordering::SeqCst);
+    if let Some(pin) = unsafe { ESTOP_PIN.as_mut() } {
+        pin.clear_interrupt_pending_bit();
+    }
+}
@@ -21,14 +21,19 @@ use pac::interrupt;
 use embedded_timeout_macros::{block_timeout, repeat_timeout, TimeoutError};
 
 use smoltcp;
+use smoltcp::dhcp::Dhcpv4Client;
 use smoltcp::iface::{
     EthernetInterface, EthernetInterfaceBuilder, Neighbor, NeighborCache, Route, Routes,
 };
+use smoltcp::socket::{RawPacketMetadata, RawSocketBuffer};
 use smoltcp::socket::{SocketHandle, SocketSet, SocketSetItem};
+use smoltcp::socket::{TcpSocket, TcpSocketBuffer};
 use smoltcp::socket::{UdpPacketMetadata, UdpSocket, UdpSocketBuffer};
 use smoltcp::storage::PacketMetadata;
 use smoltcp::time::{Duration, Instant};
-use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address, Ipv6Cidr};
+use smoltcp::wire::{
+    EthernetAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address, Ipv4Cidr, Ipv6Cidr,
+};
 
 use heapless::Vec;
 
@@ -39,6 +44,12 @@ use super::timer::CountDownTimer as Timer;
 
 // pub const MAX_UDP_PACKET_SIZE: usize = 576;
 pub const MAX_UDP_PACKET_SIZE: usize = 4096;
+/// a dhcp message with all options fits
+const DHCP_BUFFER_LEN: usize = 900;
+/// ms to wait for a lease before the static address is used
+const DHCP_FALLBACK_MS: i64 = 5_000;
+/// size of each tcp window, a full receive buffer stops the client
+pub const TCP_BUFFER_LEN: usize = 2048;
 
 // - global static state ------------------------------------------------------
 
@@ -59,6 +70,12 @@ pub struct Storage<'a> {
     socket_set_entries: [Option<SocketSetItem<'a>>; 8],
     neighbor_cache_storage: [Option<(IpAddress, Neighbor)>; 8],
     routes_storage: [Option<(IpCidr, Route)>; 1],
+    dhcp_rx_metadata: [RawPacketMetadata; 1],
+    dhcp_tx_metadata: [RawPacketMetadata; 1],
+    dhcp_rx_buffer: [u8; DHCP_BUFFER_LEN],
+    dhcp_tx_buffer: [u8; DHCP_BUFFER_LEN],
+    tcp_rx_buffer: [u8; TCP_BUFFER_LEN],
+    tcp_tx_buffer: [u8; TCP_BUFFER_LEN],
 }
 
 impl<'a> Storage<'a> {
@@ -68,6 +85,12 @@ impl<'a> Storage<'a> {
             socket_set_entries: [None, None, None, None, None, None, None, None],
             neighbor_cache_storage: [None; 8],
             routes_storage: [None; 1],
+            dhcp_rx_metadata: [RawPacketMetadata::EMPTY],
+            dhcp_tx_metadata: [RawPacketMetadata::EMPTY],
+            dhcp_rx_buffer: [0u8; DHCP_BUFFER_LEN],
+            dhcp_tx_buffer: [0u8; DHCP_BUFFER_LEN],
+            tcp_rx_buffer: [0u8; TCP_BUFFER_LEN],
+            tcp_tx_buffer: [0u8; TCP_BUFFER_LEN],
         }
     }
 }
@@ -111,6 +134,12 @@ pub struct Interface<'a> {
     lan8742a: Option<hal::ethernet::phy::LAN8742A<hal::ethernet::EthernetMAC>>,
     interface: Option<EthernetInterface<'a, ethernet::EthernetDMA<'a, 4, 4>>>,
     pub sockets: Option<SocketSet<'static>>,
+    dhcp: Option<Dhcpv4Client>,
+    /// used without dhcp, or once `dhcp_fallback_at` (ms) passed without a lease
+    static_ip: Ipv4Address,
+    dhcp_fallback_at: Option<i64>,
+    /// set whenever the address changes, until it's taken
+    new_ip: Option<Ipv4Cidr>,
     _marker: core::marker::PhantomData<&'a ()>,
 }
 
@@ -121,14 +150,21 @@ impl<'a> Interface<'a> {
             lan8742a: None,
             interface: None,
             sockets: None,
+            dhcp: None,
+            static_ip: Ipv4Address::UNSPECIFIED,
+            dhcp_fallback_at: None,
+            new_ip: None,
             _marker: core::marker::PhantomData,
         }
     }
 
+    /// with `dhcp` the interface asks for a lease and falls back to
+    /// `ip_address` if none arrives
     pub fn start(
         pins: Pins,
         mac_address: &[u8; 6],
         ip_address: &[u8; 4],
+        dhcp: bool,
         eth1mac: hal::rcc::rec::Eth1Mac,
         ccdr_clocks: &hal::rcc::CoreClocks,
         timeout_timer: Timer<pac::TIM17>,
@@ -146,13 +182,19 @@ impl<'a> Interface<'a> {
         };
 
         let mut interface = Interface::new(pins);
-        let timeout_timer =
-            match interface.up(mac_address, ip_address, eth1mac, ccdr_clocks, timeout_timer) {
-                Ok(timeout_timer) => timeout_timer,
-                Err(e) => {
-                    return Err(e);
-                }
-            };
+        let timeout_timer = match interface.up(
+            mac_address,
+            ip_address,
+            dhcp,
+            eth1mac,
+            ccdr_clocks,
+            timeout_timer,
+        ) {
+            Ok(timeout_timer) => timeout_timer,
+            Err(e) => {
+                return Err(e);
+            }
+        };
 
         // wrap ethernet interface in mutex
         // cortex_m::interrupt::free(|cs| unsafe {
@@ -203,6 +245,7 @@ impl<'a> Interface<'a> {
         &mut self,
         mac_address: &[u8; 6],
         ip_address: &[u8; 4],
+        dhcp: bool,
         eth1mac: hal::rcc::rec::Eth1Mac,
         ccdr_clocks: &hal::rcc::CoreClocks,
         mut timeout_timer: Timer<pac::TIM17>,
@@ -253,9 +296,15 @@ impl<'a> Interface<'a> {
 
         // --------------------------------------------------------------------
 
+        self.static_ip = Ipv4Address::from_bytes(ip_address);
+        // without an address until the lease, dhcp works on broadcasts
+        let ip_address = if dhcp {
+            Ipv4Address::UNSPECIFIED
+        } else {
+            self.static_ip
+        };
         unsafe {
-            ETHERNET_STORAGE.ip_addrs =
-                [IpCidr::new(Ipv4Address::from_bytes(ip_address).into(), 0)];
+            ETHERNET_STORAGE.ip_addrs = [IpCidr::new(ip_address.into(), 0)];
         }
 
         let neighbor_cache =
@@ -267,7 +316,27 @@ impl<'a> Interface<'a> {
             .ip_addrs(unsafe { &mut ETHERNET_STORAGE.ip_addrs[..] })
             .routes(routes)
             .finalize();
-        let sockets = SocketSet::new(unsafe { &mut ETHERNET_STORAGE.socket_set_entries[..] });
+        let mut sockets = SocketSet::new(unsafe { &mut ETHERNET_STORAGE.socket_set_entries[..] });
+
+        if dhcp {
+            let (rx_buffer, tx_buffer) = unsafe {
+                (
+                    RawSocketBuffer::new(
+                        &mut ETHERNET_STORAGE.dhcp_rx_metadata[..],
+                        &mut ETHERNET_STORAGE.dhcp_rx_buffer[..],
+                    ),
+                    RawSocketBuffer::new(
+                        &mut ETHERNET_STORAGE.dhcp_tx_metadata[..],
+                        &mut ETHERNET_STORAGE.dhcp_tx_buffer[..],
+                    ),
+                )
+            };
+            let now = Instant::from_millis(self.now());
+            self.dhcp = Some(Dhcpv4Client::new(&mut sockets, rx_buffer, tx_buffer, now));
+            self.dhcp_fallback_at = Some(self.now() + DHCP_FALLBACK_MS);
+        } else {
+            self.new_ip = Some(Ipv4Cidr::new(self.static_ip, 0));
+        }
 
         self.lan8742a = Some(lan8742a);
         self.interface = Some(interface);
@@ -283,10 +352,56 @@ impl<'a> Interface<'a> {
     // poll ethernet interface
     pub fn poll(&mut self) -> Result<bool, smoltcp::Error> {
         let timestamp = Instant::from_millis(self.now());
-        self.interface
+        let result = self
+            .interface
             .as_mut()
             .unwrap()
-            .poll(&mut self.sockets.as_mut().unwrap(), timestamp)
+            .poll(&mut self.sockets.as_mut().unwrap(), timestamp);
+        self.poll_dhcp(timestamp);
+        result
+    }
+
+    /// takes over a new lease, or the static address once waiting for one
+    /// took too long. a lease that arrives later still replaces it.
+    fn poll_dhcp(&mut self, timestamp: Instant) {
+        let dhcp = match self.dhcp.as_mut() {
+            Some(dhcp) => dhcp,
+            None => return,
+        };
+        let interface = self.interface.as_mut().unwrap();
+        let sockets = self.sockets.as_mut().unwrap();
+
+        // a malformed reply only means the client tries again
+        let config = dhcp.poll(interface, sockets, timestamp).unwrap_or(None);
+        let lease = config.as_ref().and_then(|config| config.address);
+
+        let cidr = match (lease, self.dhcp_fallback_at) {
+            (Some(cidr), _) => {
+                if let Some(router) = config.as_ref().and_then(|config| config.router) {
+                    let _ = interface.routes_mut().add_default_ipv4_route(router);
+                }
+                self.dhcp_fallback_at = None;
+                cidr
+            }
+            (None, Some(at)) if timestamp.total_millis() >= at => {
+                self.dhcp_fallback_at = None;
+                Ipv4Cidr::new(self.static_ip, 0)
+            }
+            _ => return,
+        };
+
+        interface.update_ip_addrs(|addrs| {
+            if let Some(addr) = addrs.iter_mut().next() {
+                *addr = IpCidr::Ipv4(cidr);
+            }
+        });
+        self.new_ip = Some(cidr);
+    }
+
+    /// the address the interface switched to since the last call
+    #[inline]
+    pub fn take_new_ip(&mut self) -> Option<Ipv4Cidr> {
+        self.new_ip.take()
     }
 
     pub fn poll_delay(&mut self) -> Option<Duration> {
@@ -325,6 +440,26 @@ impl<'a> Interface<'a> {
         let socket_handle = self.sockets.as_mut().unwrap().add(udp_socket);
         socket_handle
     }
+
+    /// there is storage for a single tcp socket, `None` once it's taken
+    pub fn new_tcp_socket(&mut self) -> Option<SocketHandle> {
+        static mut TAKEN: bool = false;
+        unsafe {
+            if TAKEN {
+                return None;
+            }
+            TAKEN = true;
+        }
+
+        let tcp_socket = unsafe {
+            TcpSocket::new(
+                TcpSocketBuffer::new(&mut ETHERNET_STORAGE.tcp_rx_buffer[..]),
+                TcpSocketBuffer::new(&mut ETHERNET_STORAGE.tcp_tx_buffer[..]),
+            )
+        };
+
+        Some(self.sockets.as_mut().unwrap().add(tcp_socket))
+    }
 }
 
 // - Pins ---------------------------------------------------------------------
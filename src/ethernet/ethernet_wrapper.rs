@@ -1,10 +1,12 @@
 use core::fmt::{write, Arguments};
 
 use crate::buf_writer::BufWriter;
+use crate::settings::{self, Settings};
 use embedded_timeout_macros::embedded_hal::digital::v2::OutputPin;
 use smoltcp::{
-    socket::{SocketHandle, UdpSocket},
-    wire::{IpEndpoint, Ipv4Address},
+    socket::{SocketHandle, TcpSocket, UdpSocket},
+    time::Duration,
+    wire::{IpEndpoint, Ipv4Address, Ipv4Cidr},
 };
 use stm32h7xx_hal::{
     device::TIM17,
@@ -26,24 +28,48 @@ use super::timer;
 
 use cortex_m_semihosting::hprintln;
 
-const MAC_LOCAL: [u8; 6] = [0x02, 0x00, 0x11, 0x22, 0x33, 0x44];
-const IP_LOCAL: [u8; 4] = [192, 168, 20, 99];
-const IP_REMOTE: [u8; 4] = [192, 168, 20, 114];
-const IP_REMOTE_PORT: u16 = 34254;
+/// 96 bit unique device id, RM0433 section 61.1
+const UID_ADDR: usize = 0x1FF1_E800;
+/// ms between keep-alives while a tcp client is connected
+const TCP_KEEP_ALIVE_MS: u64 = 1_000;
+/// ms without an answer from the tcp client before it counts as gone
+const TCP_TIMEOUT_MS: u64 = 5_000;
 
 pub struct EthernetWrapper {
     fmt_buf: [u8; 256],
+    /// peer that sent the last command, replies go there
     remote_ep: IpEndpoint,
-    local_ep: IpEndpoint,
+    port: u16,
+    mac: [u8; 6],
+    ip: [u8; 4],
+    dhcp: bool,
     socket_handle: Option<SocketHandle>,
+    /// listens on the same port as the udp socket, for one client at a time
+    tcp_handle: Option<SocketHandle>,
+    /// the connected tcp client
+    tcp_client: Option<IpEndpoint>,
+    /// the tcp client went away, until it's taken
+    tcp_dropped: bool,
     link_led_low: PB14<Output<PushPull>>,
     link_led_high: PE1<Output<PushPull>>,
 }
 
 impl EthernetWrapper {
-    pub fn new(link_led_low: PB14<Output<PushPull>>, link_led_high: PE1<Output<PushPull>>) -> Self {
-        let local_ep = IpEndpoint::new(Ipv4Address::from_bytes(&IP_LOCAL).into(), 1234);
-        let remote_ep = IpEndpoint::new(Ipv4Address::from_bytes(&IP_REMOTE).into(), IP_REMOTE_PORT);
+    /// addresses and ports are taken from `settings`
+    pub fn new(
+        link_led_low: PB14<Output<PushPull>>,
+        link_led_high: PE1<Output<PushPull>>,
+        settings: &Settings,
+    ) -> Self {
+        let mac = if settings.mac == [0; 6] {
+            settings::derived_mac(&device_uid())
+        } else {
+            settings.mac
+        };
+        let remote_ep = IpEndpoint::new(
+            Ipv4Address::from_bytes(&settings.remote_ip).into(),
+            settings.remote_port,
+        );
 
         let mut link_led_low = link_led_low;
         let mut link_led_high = link_led_high;
@@ -54,13 +80,21 @@ impl EthernetWrapper {
         Self {
             fmt_buf: [0u8; 256],
             remote_ep,
-            local_ep,
+            port: settings.port,
+            mac,
+            ip: settings.ip,
+            dhcp: settings.dhcp,
             socket_handle: None,
+            tcp_handle: None,
+            tcp_client: None,
+            tcp_dropped: false,
             link_led_high,
             link_led_low,
         }
     }
 
+    /// `Err` if the command port can't be bound, nothing is sent or received
+    /// over ethernet then
     pub fn init(
         &mut self,
         ref_clk: PA1<Alternate<AF11>>,
@@ -75,7 +109,7 @@ impl EthernetWrapper {
         timeout_timer: Timer<TIM17>,
         core_clocks: &CoreClocks,
         eth1_mac: Eth1Mac,
-    ) {
+    ) -> Result<(), smoltcp::Error> {
         let pins = ethernet::Pins {
             ref_clk,
             md_io,
@@ -91,8 +125,9 @@ impl EthernetWrapper {
         let timeout_timer = timer::CountDownTimer::new(timeout_timer);
         let _timeout_timer = match ethernet::Interface::start(
             pins,
-            &MAC_LOCAL,
-            &IP_LOCAL,
+            &self.mac,
+            &self.ip,
+            self.dhcp,
             eth1_mac,
             core_clocks,
             timeout_timer,
@@ -118,15 +153,14 @@ impl EthernetWrapper {
                 .as_mut()
                 .unwrap()
                 .get::<UdpSocket>(socket_handle);
-            match socket.bind(self.local_ep) {
-                Ok(()) => return socket_handle,
-                Err(e) => {
-                    hprintln!("Failed to bind socket to endpoint: {:?}", self.local_ep).unwrap();
-                    loop {}
-                }
-            }
+            // any address, it may still change with a dhcp lease
+            socket.bind(self.port).map(|()| socket_handle)
+        })?;
+        self.socket_handle = Some(socket_handle);
+        self.tcp_handle = ethernet::Interface::interrupt_free(|ethernet_interface| {
+            ethernet_interface.new_tcp_socket()
         });
-        self.socket_handle = Some(socket_handle)
+        Ok(())
     }
 
     #[inline]
@@ -134,11 +168,13 @@ impl EthernetWrapper {
         ethernet::Interface::interrupt_free(|ethernet_interface| {
             match ethernet_interface.poll() {
                 Ok(result) => {} // packets were processed or emitted
-                Err(smoltcp::Error::Exhausted) => (),
-                Err(smoltcp::Error::Unrecognized) => (),
-                Err(e) => hprintln!("ethernet::Interface.poll() -> {:?}", e).unwrap(),
+                // a packet that couldn't be handled is dropped, the next poll
+                // goes on with the rest
+                Err(_) => (),
             }
 
+            self.poll_tcp(ethernet_interface);
+
             if ethernet_interface.poll_link() {
                 self.link_led_high.set_high();
                 self.link_led_low.set_low();
@@ -151,38 +187,153 @@ impl EthernetWrapper {
         })
     }
 
+    /// keeps the tcp socket listening and notices when the client comes and
+    /// goes
+    fn poll_tcp(&mut self, ethernet_interface: &mut ethernet::Interface) {
+        let tcp_handle = match self.tcp_handle {
+            Some(handle) => handle,
+            None => return,
+        };
+        let mut socket = ethernet_interface
+            .sockets
+            .as_mut()
+            .unwrap()
+            .get::<TcpSocket>(tcp_handle);
+
+        if self.tcp_client.is_some() {
+            // a client that only closed its sending half is still there, its
+            // lines are read to the end before our half is closed as well
+            if !socket.may_recv() && socket.recv_queue() == 0 {
+                socket.close();
+            }
+            // closed, reset, timed out or in time-wait, the client is gone
+            if !socket.is_open() {
+                self.tcp_client = None;
+                self.tcp_dropped = true;
+            }
+        }
+        if self.tcp_client.is_none() && socket.may_recv() && socket.may_send() {
+            self.tcp_client = Some(socket.remote_endpoint());
+        }
+
+        if !socket.is_open() {
+            socket.set_keep_alive(Some(Duration::from_millis(TCP_KEEP_ALIVE_MS)));
+            socket.set_timeout(Some(Duration::from_millis(TCP_TIMEOUT_MS)));
+            // tried again on the next poll if it fails
+            let _ = socket.listen(self.port);
+        }
+    }
+
+    /// true once after the tcp client disconnected
+    #[inline]
+    pub fn take_tcp_dropped(&mut self) -> bool {
+        let dropped = self.tcp_dropped;
+        self.tcp_dropped = false;
+        dropped
+    }
+
+    /// the address the interface switched to since the last call, after a
+    /// dhcp lease or the fallback to the static one
+    #[inline]
+    pub fn take_new_ip(&mut self) -> Option<Ipv4Cidr> {
+        ethernet::Interface::interrupt_free(|ethernet_interface| ethernet_interface.take_new_ip())
+    }
+
     #[inline]
     /// returns `Err(())` when `self.socket_handle` is `None`
     pub fn send(&mut self, buf_writer: &mut BufWriter) -> Result<(), ()> {
+        let result = self.send_bytes(buf_writer.get_bytes());
+        buf_writer.clear_buf();
+        result
+    }
+
+    #[inline]
+    /// returns `Err(())` when `self.socket_handle` is `None`
+    pub fn send_bytes(&mut self, data: &[u8]) -> Result<(), ()> {
+        let remote_ep = self.remote_ep;
+        self.send_bytes_to(data, remote_ep)
+    }
+
+    #[inline]
+    /// returns `Err(())` when `self.socket_handle` is `None` or `remote_ep`
+    /// can't be sent to. a full send buffer drops `data` silently
+    pub fn send_bytes_to(&mut self, data: &[u8], remote_ep: IpEndpoint) -> Result<(), ()> {
         let socket_handle = if let Some(handle) = self.socket_handle {
             handle
         } else {
             return Err(());
         };
 
-        let data = buf_writer.get_bytes();
-
         ethernet::Interface::interrupt_free(|ethernet_interface| {
             let mut socket = ethernet_interface
                 .sockets
                 .as_mut()
                 .unwrap()
                 .get::<UdpSocket>(socket_handle);
-            match socket.send_slice(data, self.remote_ep) {
-                Ok(()) => (),
-                Err(smoltcp::Error::Exhausted) => (),
-                Err(e) => hprintln!("UdpSocket::send error: {:?}", e).unwrap(),
-            };
-        });
+            match socket.send_slice(data, remote_ep) {
+                Ok(()) | Err(smoltcp::Error::Exhausted) => Ok(()),
+                Err(_) => Err(()),
+            }
+        })
+    }
 
-        buf_writer.clear_buf();
+    /// queues `data` for the tcp client, whatever doesn't fit into the window
+    /// is dropped. returns `Err(())` when no client is connected, or it went
+    /// away since the last poll
+    pub fn send_tcp(&mut self, data: &[u8]) -> Result<(), ()> {
+        let tcp_handle = match (self.tcp_handle, self.tcp_client) {
+            (Some(handle), Some(_)) => handle,
+            _ => return Err(()),
+        };
 
-        Ok(())
+        ethernet::Interface::interrupt_free(|ethernet_interface| {
+            let mut socket = ethernet_interface
+                .sockets
+                .as_mut()
+                .unwrap()
+                .get::<TcpSocket>(tcp_handle);
+            // the next poll notices the client is gone
+            socket.send_slice(data).map(|_| ()).map_err(|_| ())
+        })
+    }
+
+    /// reads at most `data.len()` bytes from the tcp client, what isn't read
+    /// stays in the socket and closes the window once it's full
+    pub fn recv_tcp<'a>(&mut self, data: &'a mut [u8]) -> Option<(&'a [u8], IpEndpoint)> {
+        let (tcp_handle, client) = match (self.tcp_handle, self.tcp_client) {
+            (Some(handle), Some(client)) => (handle, client),
+            _ => return None,
+        };
+
+        let len = ethernet::Interface::interrupt_free(|ethernet_interface| {
+            let mut socket = ethernet_interface
+                .sockets
+                .as_mut()
+                .unwrap()
+                .get::<TcpSocket>(tcp_handle);
+            if !socket.can_recv() {
+                return 0;
+            }
+            // an error means the client went away, the next poll notices
+            socket.recv_slice(data).unwrap_or(0)
+        });
+
+        if len > 0 {
+            Some((&data[..len], client))
+        } else {
+            None
+        }
     }
 
     #[inline]
     /// returns `None` when `self.socket_handle` is `None` or nothing is read
     pub fn recv<'a>(&mut self, data: &'a mut [u8]) -> Option<&'a [u8]> {
+        self.recv_from(data).map(|(data, _)| data)
+    }
+
+    #[inline]
+    /// like `recv`, also returns who sent the data
+    pub fn recv_from<'a>(&mut self, data: &'a mut [u8]) -> Option<(&'a [u8], IpEndpoint)> {
         let socket_handle = if let Some(handle) = self.socket_handle {
             handle
         } else {
@@ -190,6 +341,7 @@ impl EthernetWrapper {
         };
 
         let mut write_len = 0;
+        let mut sender = self.remote_ep;
 
         ethernet::Interface::interrupt_free(|ethernet_interface| {
             let mut socket = ethernet_interface
@@ -198,16 +350,28 @@ impl EthernetWrapper {
                 .unwrap()
                 .get::<UdpSocket>(socket_handle);
             match socket.recv_slice(data) {
-                Ok((len, _)) => write_len = len,
-                Err(smoltcp::Error::Exhausted) => (),
-                Err(e) => hprintln!("UdpSocket::recv error: {:?}", e).unwrap(),
+                Ok((len, endpoint)) => {
+                    write_len = len;
+                    sender = endpoint;
+                }
+                // nothing received
+                Err(_) => (),
             };
         });
 
         if write_len > 0 {
-            Some(&data[..write_len])
+            self.remote_ep = sender;
+            Some((&data[..write_len], sender))
         } else {
             None
         }
     }
 }
+
+fn device_uid() -> [u8; 12] {
+    let mut uid = [0u8; 12];
+    for (i, byte) in uid.iter_mut().enumerate() {
+        *byte = unsafe { core::ptr::read_volatile((UID_ADDR + i) as *const u8) };
+    }
+    uid
+}
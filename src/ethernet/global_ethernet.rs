@@ -17,6 +17,8 @@ use stm32h7xx_hal::{
 };
 
 use crate::buf_writer::BufWriter;
+use crate::settings::Settings;
+use smoltcp::wire::{IpEndpoint, Ipv4Cidr};
 
 use super::ethernet_wrapper::EthernetWrapper;
 
@@ -25,6 +27,7 @@ pub static GLOBAL_ETHERNET: Mutex<RefCell<Option<EthernetWrapper>>> =
 static mut FORMATTER_BUFF: [u8; 512] = [0u8; 512];
 pub static FORMATTER: Mutex<RefCell<Option<BufWriter>>> = Mutex::new(RefCell::new(None));
 
+/// see `EthernetWrapper::init`
 pub fn init(
     link_led_low: PB14<Output<PushPull>>,
     link_led_high: PE1<Output<PushPull>>,
@@ -40,10 +43,11 @@ pub fn init(
     timeout_timer: Timer<TIM17>,
     core_clocks: &CoreClocks,
     eth1_mac: Eth1Mac,
-) {
+    settings: &Settings,
+) -> Result<(), smoltcp::Error> {
     cortex_m::interrupt::free(|cs| {
-        let mut eth = EthernetWrapper::new(link_led_low, link_led_high);
-        eth.init(
+        let mut eth = EthernetWrapper::new(link_led_low, link_led_high, settings);
+        let result = eth.init(
             ref_clk,
             md_io,
             md_clk,
@@ -61,7 +65,8 @@ pub fn init(
         FORMATTER
             .borrow(cs)
             .replace(Some(BufWriter::new(unsafe { &mut FORMATTER_BUFF })));
-    });
+        result
+    })
 }
 
 pub fn poll() -> i64 {
@@ -72,6 +77,15 @@ pub fn poll() -> i64 {
     })
 }
 
+/// see `EthernetWrapper::take_new_ip`
+pub fn take_new_ip() -> Option<Ipv4Cidr> {
+    cortex_m::interrupt::free(|cs| {
+        let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
+        let eth = eth.as_mut().unwrap();
+        eth.take_new_ip()
+    })
+}
+
 pub fn recv<'a>(buf: &'a mut [u8]) -> Option<&'a [u8]> {
     cortex_m::interrupt::free(move |cs| {
         let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
@@ -80,19 +94,74 @@ pub fn recv<'a>(buf: &'a mut [u8]) -> Option<&'a [u8]> {
     })
 }
 
+pub fn recv_from<'a>(buf: &'a mut [u8]) -> Option<(&'a [u8], IpEndpoint)> {
+    cortex_m::interrupt::free(move |cs| {
+        let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
+        let eth = eth.as_mut().unwrap();
+        eth.recv_from(buf)
+    })
+}
+
+/// see `EthernetWrapper::recv_tcp`
+pub fn recv_tcp<'a>(buf: &'a mut [u8]) -> Option<(&'a [u8], IpEndpoint)> {
+    cortex_m::interrupt::free(move |cs| {
+        let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
+        let eth = eth.as_mut().unwrap();
+        eth.recv_tcp(buf)
+    })
+}
+
+/// see `EthernetWrapper::send_tcp`
+pub fn send_tcp(data: &[u8]) -> Result<(), ()> {
+    cortex_m::interrupt::free(|cs| {
+        let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
+        let eth = eth.as_mut().unwrap();
+        eth.send_tcp(data)
+    })
+}
+
+/// see `EthernetWrapper::take_tcp_dropped`
+pub fn take_tcp_dropped() -> bool {
+    cortex_m::interrupt::free(|cs| {
+        let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
+        let eth = eth.as_mut().unwrap();
+        eth.take_tcp_dropped()
+    })
+}
+
+/// sends `data` as is, for binary replies that can't go through `eth_send`
+pub fn send(data: &[u8]) -> Result<(), ()> {
+    cortex_m::interrupt::free(|cs| {
+        let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
+        let eth = eth.as_mut().unwrap();
+        eth.send_bytes(data)
+    })
+}
+
+/// like `send`, to `remote_ep` instead of the default remote
+pub fn send_to(data: &[u8], remote_ep: IpEndpoint) -> Result<(), ()> {
+    cortex_m::interrupt::free(|cs| {
+        let mut eth = GLOBAL_ETHERNET.borrow(cs).borrow_mut();
+        let eth = eth.as_mut().unwrap();
+        eth.send_bytes_to(data, remote_ep)
+    })
+}
+
+/// formats the text and hands it to `com::broadcast`
+///
 /// # Panics
 /// Panics if called before calling `init`
 #[macro_export]
 macro_rules! eth_send {
     ($($arg:tt)*) => {{
         use core::fmt::Write;
-        use crate::ethernet::global_ethernet::GLOBAL_ETHERNET;
         use crate::ethernet::global_ethernet::FORMATTER;
         cortex_m::interrupt::free(|cs| {
             let mut formatter = FORMATTER.borrow(cs).borrow_mut();
             let mut formatter = formatter.as_mut().unwrap();
             let _ = write!(formatter, $($arg)*);
-            GLOBAL_ETHERNET.borrow(cs).borrow_mut().as_mut().unwrap().send(&mut formatter)
+            crate::com::broadcast(formatter.get_bytes());
+            formatter.clear_buf();
         })
     }};
 }
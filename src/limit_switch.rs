@@ -0,0 +1,54 @@
+use plotter_core::switches::Switches;
+use stm32h7xx_hal::gpio;
+use stm32h7xx_hal::gpio::{Input, PullUp};
+use stm32h7xx_hal::hal::digital::v2::InputPin;
+
+use crate::estop;
+
+/// normally open switches to ground at the homing end of each axis
+type LimitX = gpio::gpiod::PD0<Input<PullUp>>;
+type LimitY = gpio::gpiod::PD1<Input<PullUp>>;
+
+pub struct LimitSwitches {
+    x: LimitX,
+    y: LimitY,
+}
+
+impl LimitSwitches {
+    pub fn new(x: LimitX, y: LimitY) -> Self {
+        Self { x, y }
+    }
+
+    #[inline]
+    pub fn x_closed(&self) -> bool {
+        self.x.is_low().unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn y_closed(&self) -> bool {
+        self.y.is_low().unwrap_or(false)
+    }
+}
+
+/// the e-stop has its own interrupt, it's only read through here
+impl Switches for LimitSwitches {
+    #[inline]
+    fn x_limit(&self) -> bool {
+        self.x_closed()
+    }
+
+    #[inline]
+    fn y_limit(&self) -> bool {
+        self.y_closed()
+    }
+
+    #[inline]
+    fn estop_engaged(&self) -> bool {
+        estop::is_engaged()
+    }
+
+    #[inline]
+    fn take_estop_triggered(&mut self) -> bool {
+        estop::take_triggered()
+    }
+}
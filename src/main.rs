@@ -1,31 +1,38 @@
 #![no_std]
 #![no_main]
 
-mod buf_writer;
 mod com;
 mod command_handler;
+pub mod estop;
 pub mod ethernet;
-pub mod interpolator;
+pub mod limit_switch;
 mod motion_controller_2;
-mod motion_controller_advanced;
 pub mod opto;
 pub mod opto_encoder;
 pub mod pen;
 pub mod pwm;
-pub mod pwm_duty;
-pub mod sequence;
 mod sequence_data;
-pub mod sequence_wrapper;
-pub mod speed_calc;
+pub mod settings_store;
 pub mod stop_timer;
 mod usb_com;
+pub mod watchdog;
 pub mod x_axis;
 pub mod y_axis;
 
+pub use plotter_core::{
+    buf_writer, grbl_settings, interpolator, motion_controller, planner, plant_sim, protocol,
+    pwm_duty, ring_buffer, s_curve, sequence, sequence_wrapper, settings, soft_limits, speed_calc,
+    speed_profile, telemetry, transport, velocity_controller,
+};
+
 use buf_writer::BufWriter;
 use ethernet::ethernet_wrapper::EthernetWrapper;
+use limit_switch::LimitSwitches;
 use opto::{Opto1Gpio, OptoDecoder};
-use pwm::{MotorPwmX, MotorPwmY};
+use plotter_core::clock::Clock;
+use settings::SettingsError;
+use settings_store::SettingsStore;
+use watchdog::Watchdog;
 use x_axis::opto::Opto2Gpio;
 use x_axis::x_driver::XDriver;
 use y_axis::y_driver::YDriver;
@@ -40,18 +47,12 @@ use interpolator::CircularInterpolationDir::*;
 use interpolator::Interpolation;
 use sequence_wrapper::SequenceWrapper;
 // use motion_controller::MotionController;
-use motion_controller_advanced::MotionController;
 
 // use command_handler::CommandHandler;
-use com::CommandHandler;
+use com::{CommandHandler, Transport};
 use command_handler::HandlerState;
-use ethernet::global_ethernet;
+use ethernet::global_ethernet::{self, eth_send};
 
-// pick a panicking behavior
-use panic_semihosting as _; // you can put a breakpoint on `rust_begin_unwind` to catch panics
-                            // use panic_abort as _; // requires nightly
-                            // use panic_itm as _; // logs messages over ITM; requires ITM support
-                            // use panic_semihosting as _; // logs messages to the host stderr; requires a debugge
 use micromath::{F32Ext, F32};
 
 use cortex_m_rt::entry;
@@ -70,7 +71,8 @@ use stm32h7xx_hal::gpio::{Edge, ExtiPin, Floating, Input, Output, PullUp, PushPu
 use stm32h7xx_hal::hal::digital::v2::OutputPin;
 use stm32h7xx_hal::hal::digital::v2::ToggleableOutputPin;
 use stm32h7xx_hal::prelude::*;
-use stm32h7xx_hal::rcc::rec::ResetEnable;
+use stm32h7xx_hal::rcc::rec::{ResetEnable, UsbClkSel};
+use stm32h7xx_hal::usb_hs::USB1;
 use stm32h7xx_hal::{interrupt, pac, prelude::*, timer};
 // LED pin
 use stm32h7xx_hal::gpio::gpiob::PB0;
@@ -82,6 +84,17 @@ use crate::pwm::PwmPinY;
 
 use opto_encoder::*;
 
+type MotionController = motion_controller::MotionController<
+    PwmPinX,
+    PwmPinY,
+    EncoderX,
+    EncoderY,
+    PenDriver,
+    LimitSwitches,
+    SettingsStore,
+    SystemClock,
+>;
+
 static mut TICK_TIMER: Option<timer::Timer<pac::TIM5>> = None;
 static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
 
@@ -90,6 +103,9 @@ static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
 // static SYNCHRONIZER: Mutex<RefCell<Option<MotionController>>> = Mutex::new(RefCell::new(None));
 static mut SYNCHRONIZER: Option<MotionController> = None;
 
+/// ms the main loop may stall before the watchdog resets the mcu
+const WATCHDOG_TIMEOUT_MS: u32 = 250;
+
 #[entry]
 fn main() -> ! {
     let mut cp = cortex_m::Peripherals::take().unwrap();
@@ -105,10 +121,18 @@ fn main() -> ! {
         .pll1_r_ck(100.mhz())
         .freeze(pwrcfg, &dp.SYSCFG);
 
+    // usb runs from the internal 48 MHz oscillator
+    let _ = ccdr.clocks.hsi48_ck().expect("HSI48 must run");
+    ccdr.peripheral.kernel_usb_clk_mux(UsbClkSel::HSI48);
+
     cp.SCB.invalidate_icache();
     cp.SCB.enable_icache();
     cp.DWT.enable_cycle_counter();
 
+    let settings_store = SettingsStore::new(dp.FLASH);
+    let stored_settings = settings_store.load();
+    let settings = stored_settings.unwrap_or_default();
+
     let gpiob = dp.GPIOB.split(ccdr.peripheral.GPIOB);
     let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
     let gpioe = dp.GPIOE.split(ccdr.peripheral.GPIOE);
@@ -147,7 +171,7 @@ fn main() -> ! {
         dp.TIM4,
         ccdr.peripheral.TIM4,
         &ccdr.clocks,
-        60.hz(), //45Hz
+        settings.pwm_freq.1.hz(),
     ));
 
     motor_pwm_y.enable_pwm();
@@ -169,28 +193,46 @@ fn main() -> ! {
         ),
     );
 
-    let mut delay = cp.SYST.delay(ccdr.clocks);
-
-    let motor_pwm_x = MotorPwm::new(PwmPinX::new(
+    let mut motor_pwm_x = MotorPwm::new(PwmPinX::new(
         gpioe.pe13.into_alternate_af1(),
         gpioe.pe14.into_alternate_af1(),
         gpiog.pg14.into_push_pull_output(),
         dp.TIM1,
         ccdr.peripheral.TIM1,
         &ccdr.clocks,
-        200.hz(), //45Hz
+        settings.pwm_freq.0.hz(),
     ));
 
+    motor_pwm_x.enable_pwm();
+
     let scl = gpiob.pb8.into_alternate_af4().set_open_drain();
     let sda = gpiob.pb9.into_alternate_af4().set_open_drain();
-    let mut pen_driver = PenDriver::new(dp.I2C1, scl, sda, ccdr.peripheral.I2C1, &ccdr.clocks);
+    let mut pen_driver = PenDriver::new(
+        dp.I2C1,
+        scl,
+        sda,
+        ccdr.peripheral.I2C1,
+        &ccdr.clocks,
+        settings.pen_up_angle,
+    );
+
+    estop::init(gpiod.pd2.into_pull_up_input(), &mut syscfg, &mut exti);
+
+    let limit_switches = LimitSwitches::new(
+        gpiod.pd0.into_pull_up_input(),
+        gpiod.pd1.into_pull_up_input(),
+    );
 
     let synchronizer = MotionController::new(
-        MotorPwmX(motor_pwm_x),
-        MotorPwmY(motor_pwm_y),
+        motor_pwm_x,
+        motor_pwm_y,
         encoder_x,
         encoder_y,
         pen_driver,
+        limit_switches,
+        settings,
+        settings_store,
+        SystemClock,
     );
 
     free(|_cs| {
@@ -210,9 +252,8 @@ fn main() -> ! {
 
     let synchronizer = unsafe { &mut SYNCHRONIZER };
     let synchronizer = synchronizer.as_mut().unwrap();
-    synchronizer.calibrate(&mut delay);
 
-    global_ethernet::init(
+    let ethernet = global_ethernet::init(
         link_led_low,
         link_led_high,
         gpioa.pa1.into_alternate_af11().set_speed(VeryHigh),
@@ -227,22 +268,55 @@ fn main() -> ! {
         timeout_timer,
         &ccdr.clocks,
         ccdr.peripheral.ETH1MAC,
+        &settings,
     );
 
+    usb_com::init(USB1::new(
+        dp.OTG1_HS_GLOBAL,
+        dp.OTG1_HS_DEVICE,
+        dp.OTG1_HS_PWRCLK,
+        gpioa.pa11.into_alternate_af10(),
+        gpioa.pa12.into_alternate_af10(),
+        ccdr.peripheral.USB1OTG,
+        &ccdr.clocks,
+    ));
+
+    match stored_settings {
+        Ok(_) | Err(SettingsError::Blank) => {}
+        Err(e) => eth_send!("error: {}, using defaults\n\r", e),
+    }
+
     synchronizer.start_sequence();
 
     unsafe {
         cp.NVIC.set_priority(interrupt::TIM2, 2);
         NVIC::unmask(interrupt::TIM2);
+        cp.NVIC.set_priority(interrupt::EXTI2, 1);
+        NVIC::unmask(interrupt::EXTI2);
     }
 
     let mut fmt_buf = [0u8; 64];
     let mut buf_writer = BufWriter::new(&mut fmt_buf);
 
     // let mut cmd_handler = CommandHandler::new(HandlerState::Busy);
-    let mut cmd_handler = CommandHandler::new();
+    let mut cmd_handler = CommandHandler::new(SystemClock);
+    for transport in [Transport::Udp, Transport::Tcp, Transport::Usb].iter() {
+        let _ = cmd_handler.add_transport(*transport);
+    }
+    match ethernet {
+        Ok(()) => synchronizer.start_homing(&mut cmd_handler),
+        // usb still works, the machine stays locked until $X
+        Err(e) => synchronizer.alarm(
+            format_args!("command port {} unavailable, {}", settings.port, e),
+            &mut cmd_handler,
+        ),
+    }
+
+    let mut watchdog = Watchdog::start(dp.IWDG, WATCHDOG_TIMEOUT_MS);
 
     loop {
+        watchdog.feed();
+
         // if let Err(e) = i2c.write(0x08, &[angle]) {
         //     eth_send!("i2c error: {:?}\n", e);
         // }
@@ -261,6 +335,10 @@ fn main() -> ! {
         synchronizer.tick(&mut cmd_handler);
 
         let _now = global_ethernet::poll();
+        if let Some(ip) = global_ethernet::take_new_ip() {
+            eth_send!("ip: {}\n\r", ip.address());
+        }
+        usb_com::poll();
 
         cmd_handler.tick();
     }
@@ -274,6 +352,13 @@ fn TIM2() {
     timer.clear_irq();
 }
 
+#[interrupt]
+fn EXTI2() {
+    // cuts the bridges through the registers, the controller finishes the
+    // stop on its next tick
+    estop::on_interrupt();
+}
+
 pub fn timestamp() -> u64 {
     let overflows = OVERFLOWS.load(Ordering::SeqCst) as u64;
     let mut rc = unsafe { &mut TICK_TIMER };
@@ -283,16 +368,51 @@ pub fn timestamp() -> u64 {
     (overflows << 32) + ctr
 }
 
+/// `timestamp` as a `Clock` for the hardware independent code
+#[derive(Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> u64 {
+        timestamp()
+    }
+}
+
 use core::fmt::Write;
+use core::panic::PanicInfo;
 use core::ptr;
+use cortex_m::peripheral::{DCB, SCB};
 use cortex_m_rt::{exception, ExceptionFrame};
 use cortex_m_semihosting::hio;
 
+/// a crash must never leave a motor running, so both handlers cut the
+/// h-bridges before anything else and then reset the mcu. semihosting halts
+/// the core without a debugger attached, so the message only goes out with one
+#[inline(never)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    estop::cut_bridges();
+
+    if DCB::is_debugger_attached() {
+        if let Ok(mut hstdout) = hio::hstdout() {
+            writeln!(hstdout, "{}", info).ok();
+        }
+    }
+
+    SCB::sys_reset()
+}
+
 #[exception]
 fn HardFault(ef: &ExceptionFrame) -> ! {
-    if let Ok(mut hstdout) = hio::hstdout() {
-        writeln!(hstdout, "{:#?}", ef).ok();
+    estop::cut_bridges();
+
+    if DCB::is_debugger_attached() {
+        if let Ok(mut hstdout) = hio::hstdout() {
+            writeln!(hstdout, "{:#?}", ef).ok();
+        }
     }
 
-    loop {}
+    SCB::sys_reset()
 }
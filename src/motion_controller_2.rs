@@ -2,7 +2,7 @@ use crate::opto_encoder::Encoder;
 use crate::timestamp;
 use crate::{
     com::CommandHandler, ethernet::ethernet_wrapper::EthernetWrapper, interpolator::Interpolation,
-    pen::pen_driver, pen::pen_driver::PenDriver, pen::PenPosition, sequence::Sequence,
+    pen::pen_driver::PenDriver, pen::PenPosition, sequence::Sequence,
     sequence_wrapper::SequenceWrapper, BufWriter, XDriver, YDriver,
 };
 
@@ -69,17 +69,17 @@ impl MotionController {
                 if let Some(pen_pos) = code.value_for('Z') {
                     self.sequence.pen_pos(pen_pos)
                 }
-                match (code.value_for('X'), code.value_for('Y')) {
+                let _ = match (code.value_for('X'), code.value_for('Y')) {
                     (Some(x), Some(y)) => self.sequence.pos_rapid(x, y),
                     (Some(x), None) => self.sequence.pos_x_rapid(x),
                     (None, Some(y)) => self.sequence.pos_y_rapid(y),
-                    (None, None) => (),
-                }
+                    (None, None) => Ok(()),
+                };
             }
             1 => {
                 //G01 linear interpolation
                 if let (Some(x), Some(y)) = (code.value_for('X'), code.value_for('Y')) {
-                    self.sequence.pos(x, y)
+                    let _ = self.sequence.pos(x, y);
                 }
             }
             _ => (),
@@ -131,12 +131,12 @@ impl MotionController {
                         self.pen_driver.move_pen(sqv.pen());
 
                         let old_angle = match target_pos.pen() {
-                            PenPosition::Default => pen_driver::UP_ANGLE,
+                            PenPosition::Default => self.pen_driver.up_angle(),
                             PenPosition::Angle(a) => a,
                         };
 
                         let new_angle = match sqv.pen() {
-                            PenPosition::Default => pen_driver::UP_ANGLE,
+                            PenPosition::Default => self.pen_driver.up_angle(),
                             PenPosition::Angle(a) => a,
                         };
 
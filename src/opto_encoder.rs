@@ -1,3 +1,5 @@
+pub use plotter_core::encoder::Encoder;
+
 use crate::timestamp;
 use stm32h7::stm32h743v::{TIM2, TIM8};
 use stm32h7xx_hal::rcc::rec::ResetEnable;
@@ -88,12 +90,6 @@ impl EncoderX {
     }
 }
 
-pub trait Encoder {
-    fn pos(&self) -> i32;
-    fn dir(&self) -> bool;
-    fn calibrate(&self);
-}
-
 impl Encoder for EncoderX {
     fn pos(&self) -> i32 {
         let real_count = self.tim8.cnt.read().cnt().bits();
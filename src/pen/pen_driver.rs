@@ -1,6 +1,8 @@
 use super::servo_pwm::ServoPwm;
 
 use crate::ethernet::global_ethernet::eth_send;
+pub use plotter_core::pen::PenPosition;
+use plotter_core::pen::Pen;
 
 use core::cmp::PartialEq;
 use core::marker::Copy;
@@ -15,18 +17,12 @@ use stm32h7xx_hal::rcc::rec::{I2c1, Tim3};
 use stm32h7xx_hal::rcc::{CoreClocks, PeripheralREC};
 
 const PEN_DRIVER_ADDR: u8 = 0x8;
-pub const UP_ANGLE: u8 = 60;
-
-#[derive(Copy, Clone, PartialEq)]
-pub enum PenPosition {
-    Default,
-    Angle(u8),
-}
 
 // #[repr(transparent)]
 pub struct PenDriver {
     i2c: I2c<I2C1>,
     pos: PenPosition,
+    up_angle: u8,
 }
 
 impl PenDriver {
@@ -36,16 +32,36 @@ impl PenDriver {
         sda: gpio::gpiob::PB9<Alternate<AF4>>,
         prec: I2c1,
         clocks: &CoreClocks,
+        up_angle: u8,
     ) -> Self {
         Self {
             i2c: i2c.i2c((scl, sda), 100.khz(), prec, clocks),
             pos: PenPosition::Default,
+            up_angle,
         }
     }
 
+    /// angle the pen is lifted to
+    #[inline]
+    pub fn up_angle(&self) -> u8 {
+        self.up_angle
+    }
+
+    /// takes effect with the next lift
+    #[inline]
+    pub fn set_up_angle(&mut self, angle: u8) {
+        self.up_angle = angle;
+    }
+
+    /// last position the pen was sent to
+    #[inline]
+    pub fn pos(&self) -> PenPosition {
+        self.pos
+    }
+
     #[inline]
     pub fn move_up(&mut self) {
-        self.set_angle(UP_ANGLE);
+        self.set_angle(self.up_angle);
     }
 
     #[inline]
@@ -72,3 +88,25 @@ impl PenDriver {
         }
     }
 }
+
+impl Pen for PenDriver {
+    #[inline]
+    fn pos(&self) -> PenPosition {
+        PenDriver::pos(self)
+    }
+
+    #[inline]
+    fn move_pen(&mut self, pen_pos: PenPosition) {
+        PenDriver::move_pen(self, pen_pos)
+    }
+
+    #[inline]
+    fn move_up(&mut self) {
+        PenDriver::move_up(self)
+    }
+
+    #[inline]
+    fn set_up_angle(&mut self, angle: u8) {
+        PenDriver::set_up_angle(self, angle)
+    }
+}
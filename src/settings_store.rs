@@ -0,0 +1,191 @@
+//! keeps the settings block in the last sector of flash bank 2, outside of
+//! the `FLASH` region in memory.x. every save programs the block into the
+//! next free slot of the sector, the sector is only erased once all slots
+//! are used. the newest slot that decodes wins, so a save cut short by a reset
+//! falls back to the one before it.
+//!
+//! the flash registers are written directly, offsets and bits are from
+//! RM0433 section 4.9.
+
+use core::ptr;
+
+use crate::settings::{Settings, SettingsError, SettingsStorage, MAX_BLOCK_LEN};
+use crate::watchdog;
+use stm32h7xx_hal::pac;
+
+/// bank 2, sector 7
+const SECTOR_ADDR: usize = 0x081E_0000;
+const SECTOR_NUMBER: u32 = 7;
+const SECTOR_LEN: usize = 128 * 1024;
+/// one block per slot, a whole number of 32 byte flash words
+const SLOT_LEN: usize = MAX_BLOCK_LEN;
+const SLOTS: usize = SECTOR_LEN / SLOT_LEN;
+/// bytes programmed at once
+const FLASH_WORD: usize = 32;
+
+const FLASH_BASE: usize = 0x5200_2000;
+const KEYR2: usize = FLASH_BASE + 0x104;
+const CR2: usize = FLASH_BASE + 0x10C;
+const SR2: usize = FLASH_BASE + 0x110;
+const CCR2: usize = FLASH_BASE + 0x114;
+
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+const CR_LOCK: u32 = 1 << 0;
+const CR_PG: u32 = 1 << 1;
+const CR_SER: u32 = 1 << 2;
+/// 64 bit parallelism, fine from 2.7 V up
+const CR_PSIZE_X64: u32 = 0b11 << 4;
+const CR_START: u32 = 1 << 7;
+const CR_SNB_SHIFT: u32 = 8;
+
+const SR_BSY: u32 = 1 << 0;
+const SR_WBNE: u32 = 1 << 1;
+const SR_QW: u32 = 1 << 2;
+/// WRPERR up to DBECCERR
+const SR_ERRORS: u32 = 0x07EE_0000;
+/// EOP and every error flag
+const CCR_ALL: u32 = 0x07EF_0000;
+
+pub struct SettingsStore {
+    _flash: pac::FLASH,
+    /// slot the next save goes to, `SLOTS` once the sector is full
+    next_slot: usize,
+}
+
+impl SettingsStore {
+    pub fn new(flash: pac::FLASH) -> Self {
+        let next_slot = (0..SLOTS)
+            .find(|slot| Self::slot_is_blank(*slot))
+            .unwrap_or(SLOTS);
+
+        Self {
+            _flash: flash,
+            next_slot,
+        }
+    }
+
+    /// newest stored settings that are intact
+    pub fn load(&self) -> Result<Settings, SettingsError> {
+        let mut error = SettingsError::Blank;
+        for slot in (0..self.next_slot).rev() {
+            match Settings::decode(Self::slot(slot)) {
+                Ok(settings) => return Ok(settings),
+                // an older slot may still be intact
+                Err(SettingsError::Corrupt) => error = SettingsError::Corrupt,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(error)
+    }
+
+    /// programs `settings` into the next slot. blocks for a few ms, or for
+    /// seconds when the sector has to be erased first, so only save while
+    /// nothing moves
+    pub fn save(&mut self, settings: &Settings) -> Result<(), ()> {
+        let mut block = [0xFFu8; SLOT_LEN];
+        settings.encode(&mut block);
+
+        Self::unlock();
+        let result = self.program(&block);
+        Self::lock();
+        result
+    }
+
+    fn program(&mut self, block: &[u8; SLOT_LEN]) -> Result<(), ()> {
+        if self.next_slot >= SLOTS {
+            Self::erase()?;
+            self.next_slot = 0;
+        }
+
+        let addr = SECTOR_ADDR + self.next_slot * SLOT_LEN;
+        // whatever happens from here on, this slot can't be programmed again
+        self.next_slot += 1;
+
+        for (i, word) in block.chunks(FLASH_WORD).enumerate() {
+            Self::program_word(addr + i * FLASH_WORD, word)?;
+        }
+
+        if Settings::decode(Self::slot(self.next_slot - 1)).is_ok() {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    fn erase() -> Result<(), ()> {
+        unsafe {
+            ptr::write_volatile(
+                CR2 as *mut u32,
+                CR_SER | CR_PSIZE_X64 | (SECTOR_NUMBER << CR_SNB_SHIFT),
+            );
+            let cr = ptr::read_volatile(CR2 as *const u32);
+            ptr::write_volatile(CR2 as *mut u32, cr | CR_START);
+        }
+        // takes up to a few seconds
+        let result = Self::wait(watchdog::feed_now);
+        unsafe { ptr::write_volatile(CR2 as *mut u32, 0) };
+        result
+    }
+
+    fn program_word(addr: usize, word: &[u8]) -> Result<(), ()> {
+        unsafe {
+            ptr::write_volatile(CR2 as *mut u32, CR_PG | CR_PSIZE_X64);
+            for (i, bytes) in word.chunks(4).enumerate() {
+                let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                ptr::write_volatile((addr + i * 4) as *mut u32, value);
+            }
+        }
+        let result = Self::wait(|| {});
+        unsafe { ptr::write_volatile(CR2 as *mut u32, 0) };
+        result
+    }
+
+    /// until the bank is done, calling `idle` in between
+    fn wait(mut idle: impl FnMut()) -> Result<(), ()> {
+        loop {
+            let sr = unsafe { ptr::read_volatile(SR2 as *const u32) };
+            if sr & SR_ERRORS != 0 {
+                unsafe { ptr::write_volatile(CCR2 as *mut u32, CCR_ALL) };
+                return Err(());
+            }
+            if sr & (SR_BSY | SR_WBNE | SR_QW) == 0 {
+                unsafe { ptr::write_volatile(CCR2 as *mut u32, CCR_ALL) };
+                return Ok(());
+            }
+            idle();
+        }
+    }
+
+    fn unlock() {
+        unsafe {
+            if ptr::read_volatile(CR2 as *const u32) & CR_LOCK != 0 {
+                ptr::write_volatile(KEYR2 as *mut u32, KEY1);
+                ptr::write_volatile(KEYR2 as *mut u32, KEY2);
+            }
+        }
+    }
+
+    fn lock() {
+        unsafe { ptr::write_volatile(CR2 as *mut u32, CR_LOCK) };
+    }
+
+    fn slot(slot: usize) -> &'static [u8] {
+        unsafe {
+            core::slice::from_raw_parts((SECTOR_ADDR + slot * SLOT_LEN) as *const u8, SLOT_LEN)
+        }
+    }
+
+    #[inline]
+    fn slot_is_blank(slot: usize) -> bool {
+        Self::slot(slot).iter().all(|b| *b == 0xFF)
+    }
+}
+
+impl SettingsStorage for SettingsStore {
+    #[inline]
+    fn save(&mut self, settings: &Settings) -> Result<(), ()> {
+        SettingsStore::save(self, settings)
+    }
+}
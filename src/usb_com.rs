@@ -1,9 +1,13 @@
+//! cdc serial port on USB1, a command channel next to ethernet. the port is
+//! kept in a global like the ethernet interface, `poll` has to run from the
+//! main loop.
+
 use stm32h7xx_hal::usb_hs::USB1;
 use synopsys_usb_otg::bus::UsbBus;
 use usbd_serial::SerialPort;
 
 use usb_device::bus::UsbBusAllocator;
-use usb_device::device::{UsbDevice, UsbDeviceBuilder, UsbVidPid};
+use usb_device::device::{UsbDevice, UsbDeviceBuilder, UsbDeviceState, UsbVidPid};
 use usb_device::UsbError;
 
 use core::fmt::Debug;
@@ -11,6 +15,84 @@ use core::fmt::{self, Arguments};
 
 use super::BufWriter;
 
+/// packet buffers of the usb core
+static mut EP_MEMORY: [u32; 1024] = [0; 1024];
+static mut USB_BUS: Option<UsbBusAllocator<UsbBus<USB1>>> = None;
+static mut USB_SERIAL: Option<UsbSerial<'static>> = None;
+/// the host had the port open at the last `poll`
+static mut OPEN: bool = false;
+/// the host closed the port, until it's taken
+static mut CLOSED: bool = false;
+
+/// brings up the serial port, the host sees it once `poll` runs
+pub fn init(usb: USB1) {
+    unsafe {
+        USB_BUS = Some(UsbBus::new(usb, &mut EP_MEMORY));
+        USB_SERIAL = Some(UsbSerial::new(USB_BUS.as_ref().unwrap()));
+    }
+}
+
+/// services the usb device, returns true if the port may have data
+pub fn poll() -> bool {
+    let ready = with_serial(|serial| serial.poll()).unwrap_or(false);
+
+    let open = connected();
+    unsafe {
+        if OPEN && !open {
+            CLOSED = true;
+        }
+        OPEN = open;
+    }
+    ready
+}
+
+/// true once after the host closed the port or the cable was pulled
+pub fn take_disconnected() -> bool {
+    cortex_m::interrupt::free(|_cs| unsafe {
+        let closed = CLOSED;
+        CLOSED = false;
+        closed
+    })
+}
+
+/// reads what the host sent, at most `buf.len()` bytes. what isn't read
+/// stays with the host until there's room.
+pub fn read(buf: &mut [u8]) -> usize {
+    if buf.is_empty() {
+        return 0;
+    }
+    with_serial(|serial| serial.read(buf).unwrap_or(0)).unwrap_or(0)
+}
+
+/// sends `data` once the host has configured the device, without waiting for
+/// it. what doesn't fit is dropped. dtr isn't needed, plenty of terminals never
+/// set it. `Err(())` while the device isn't configured
+pub fn send(data: &[u8]) -> Result<(), ()> {
+    with_serial(|serial| {
+        if serial.configured() {
+            serial.write_available(data);
+            Ok(())
+        } else {
+            Err(())
+        }
+    })
+    .unwrap_or(Err(()))
+}
+
+/// true while a host has the port open, dtr falling is how a closed port or a
+/// pulled cable shows
+pub fn connected() -> bool {
+    with_serial(|serial| serial.dtr()).unwrap_or(false)
+}
+
+/// `None` before `init`
+fn with_serial<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce(&mut UsbSerial<'static>) -> R,
+{
+    cortex_m::interrupt::free(|_cs| unsafe { USB_SERIAL.as_mut() }.map(f))
+}
+
 #[derive(Debug)]
 pub enum UsbWriteError {
     DeviceBusy,
@@ -100,6 +182,22 @@ impl<'a> UsbSerial<'a> {
         Ok(())
     }
 
+    /// writes as much of `buf` as fits right now, returns how much that was
+    pub fn write_available(&mut self, buf: &[u8]) -> usize {
+        let mut write_offset = 0;
+
+        while write_offset < buf.len() {
+            match self.serial_port.write(&buf[write_offset..]) {
+                Ok(len) if len > 0 => {
+                    write_offset += len;
+                }
+                _ => break,
+            }
+        }
+
+        write_offset
+    }
+
     pub fn wait_for_write(&self) {
         while !self.serial_port.rts() {}
     }
@@ -108,6 +206,16 @@ impl<'a> UsbSerial<'a> {
         self.serial_port.rts()
     }
 
+    /// true once the host enumerated the device, with or without the port open
+    pub fn configured(&self) -> bool {
+        self.usb_dev.state() == UsbDeviceState::Configured
+    }
+
+    /// true while a host has the port open
+    pub fn dtr(&self) -> bool {
+        self.serial_port.dtr()
+    }
+
     pub fn poll(&mut self) -> bool {
         self.usb_dev.poll(&mut [&mut self.serial_port])
     }
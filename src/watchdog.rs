@@ -0,0 +1,50 @@
+//! independent watchdog. it runs off the 32 kHz LSI and resets the mcu unless
+//! the main loop feeds it, a reset puts the h-bridge enables back to low.
+//! once started nothing stops it again.
+
+use stm32h7xx_hal::pac::IWDG;
+
+/// Hz of the LSI after the /64 prescaler
+const TICK_HZ: u32 = 32_000 / 64;
+/// the reload register is 12 bits wide
+const MAX_RELOAD: u32 = 0xFFF;
+
+const KEY_FEED: u32 = 0xAAAA;
+const KEY_UNLOCK: u32 = 0x5555;
+const KEY_START: u32 = 0xCCCC;
+/// prescaler register value for /64
+const PRESCALER_64: u32 = 0b100;
+
+pub struct Watchdog {
+    iwdg: IWDG,
+}
+
+impl Watchdog {
+    /// starts the watchdog, the mcu resets if `feed` isn't called within
+    /// `timeout_ms`, at most ~8 s
+    pub fn start(iwdg: IWDG, timeout_ms: u32) -> Self {
+        let reload = (timeout_ms * TICK_HZ / 1000).max(1).min(MAX_RELOAD);
+
+        iwdg.kr.write(|w| unsafe { w.bits(KEY_START) });
+        iwdg.kr.write(|w| unsafe { w.bits(KEY_UNLOCK) });
+        iwdg.pr.write(|w| unsafe { w.bits(PRESCALER_64) });
+        iwdg.rlr.write(|w| unsafe { w.bits(reload) });
+        // the new values only take effect once they're synced to the lsi domain
+        while iwdg.sr.read().bits() != 0 {}
+        iwdg.kr.write(|w| unsafe { w.bits(KEY_FEED) });
+
+        Self { iwdg }
+    }
+
+    #[inline]
+    pub fn feed(&mut self) {
+        self.iwdg.kr.write(|w| unsafe { w.bits(KEY_FEED) });
+    }
+}
+
+/// feeds the watchdog without owning it, for the few operations that block
+/// the main loop for longer than the timeout on purpose (erasing flash)
+#[inline]
+pub fn feed_now() {
+    unsafe { (*IWDG::ptr()).kr.write(|w| w.bits(KEY_FEED)) };
+}
@@ -2,8 +2,8 @@ pub use super::opto;
 pub use super::pwm;
 pub use super::pwm_duty;
 pub use super::sequence;
+pub use super::speed_profile;
 pub use super::timestamp;
 
-pub mod speed_profile;
 pub mod x_driver;
 pub mod x_pwm;
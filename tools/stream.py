@@ -0,0 +1,143 @@
+#!/usr/bin/env python3
+"""Streams a g-code file to the plotter using the framed protocol in
+plotter-core/src/protocol.rs: every frame is acknowledged, lost frames are
+resent and the plotter drops duplicates, so each line runs exactly once and in
+order.
+
+usage: stream.py FILE [--host 192.168.20.99] [--port 1234] [--heartbeat SECONDS]
+
+with --heartbeat the plotter goes into feed hold if it doesn't hear from this
+script for that long while the job is still being streamed.
+"""
+
+import argparse
+import socket
+import struct
+import sys
+import time
+
+FRAME_MAGIC = 0xA5
+FRAME_DATA = 0x01
+FRAME_SYNC = 0x02
+FRAME_ACK = 0x81
+FRAME_NACK = 0x82
+
+NACK_REASONS = {1: "out of order", 2: "buffer full", 3: "malformed", 4: "alarm"}
+NACK_ALARM = 4
+
+# the plotter receives into a 576 byte buffer
+MAX_PAYLOAD = 512
+# small frames are cheap to resend and fit into a nearly full line buffer
+MAX_LINES_PER_FRAME = 16
+WINDOW = 4
+TIMEOUT = 0.3
+
+
+def frame(kind, seq, payload=b""):
+    return struct.pack("<BBH", FRAME_MAGIC, kind, seq & 0xFFFF) + payload
+
+
+def chunk(lines):
+    frames, current = [], []
+    for line in lines:
+        candidate = current + [line]
+        if current and (
+            len("\n".join(candidate)) > MAX_PAYLOAD
+            or len(candidate) > MAX_LINES_PER_FRAME
+        ):
+            frames.append("\n".join(current).encode())
+            candidate = [line]
+        current = candidate
+    if current:
+        frames.append("\n".join(current).encode())
+    return frames
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("file")
+    parser.add_argument("--host", default="192.168.20.99")
+    parser.add_argument("--port", type=int, default=1234)
+    parser.add_argument("--heartbeat", type=float)
+    args = parser.parse_args()
+
+    with open(args.file) as f:
+        lines = [l.split(";")[0].strip() for l in f]
+    payloads = chunk([l for l in lines if l])
+
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    sock.settimeout(TIMEOUT)
+    plotter = (args.host, args.port)
+
+    # payload index i is sent with sequence number i, without a confirmed sync
+    # the first frames could be taken for duplicates of an earlier job
+    while True:
+        sock.sendto(frame(FRAME_SYNC, 0), plotter)
+        try:
+            data, _ = sock.recvfrom(1024)
+        except socket.timeout:
+            continue
+        if len(data) >= 7 and data[0] == FRAME_MAGIC and data[1] == FRAME_ACK:
+            break
+
+    if args.heartbeat:
+        sock.sendto(">heartbeat {}".format(args.heartbeat).encode(), plotter)
+
+    acked = 0  # everything below has been acknowledged
+    next_to_send = 0
+    backoff_until = 0.0
+
+    while acked < len(payloads):
+        now = time.monotonic()
+        while (
+            next_to_send < len(payloads)
+            and next_to_send < acked + WINDOW
+            and now >= backoff_until
+        ):
+            sock.sendto(frame(FRAME_DATA, next_to_send, payloads[next_to_send]), plotter)
+            next_to_send += 1
+
+        try:
+            data, _ = sock.recvfrom(1024)
+        except socket.timeout:
+            # nothing came back, resend what's still outstanding
+            next_to_send = acked
+            continue
+
+        if len(data) < 7 or data[0] != FRAME_MAGIC:
+            sys.stdout.write(data.decode(errors="replace"))
+            continue
+
+        _, kind, seq, free, reason = struct.unpack("<BBHHB", data[:7])
+        # widen the 16 bit sequence number around the acked position
+        delta = (seq - acked) & 0xFFFF
+        if delta >= 0x8000:
+            delta -= 0x10000
+        seq = acked + delta
+
+        if kind == FRAME_ACK:
+            acked = max(acked, seq + 1)
+            next_to_send = max(next_to_send, acked)
+        elif kind == FRAME_NACK:
+            if reason == NACK_ALARM:
+                print("\nplotter is in alarm, unlock it with $X and restart the job")
+                sys.exit(1)
+            if reason == 2:
+                # the plotter is busy, give it some time before resending
+                backoff_until = time.monotonic() + 0.1
+            else:
+                print("nack {}: {}".format(seq, NACK_REASONS.get(reason, reason)))
+            # everything before seq made it
+            acked = max(acked, seq)
+            next_to_send = acked
+
+        print("\r{}/{} frames, {} lines free".format(acked, len(payloads), free), end="")
+
+    # the whole job is on the plotter now, it can finish without us
+    if args.heartbeat:
+        sock.sendto(b">heartbeat off", plotter)
+    print()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,83 @@
+#!/usr/bin/env python3
+"""Subscribes to the plotter's telemetry stream (plotter-core/src/telemetry.rs)
+and writes the samples as csv.
+
+usage: telemetry.py [--host 192.168.20.99] [--port 1234] [--rate 200]
+                    [--listen 34255] [-o out.csv]
+
+stops and unsubscribes on ctrl-c. lost packets are reported on stderr.
+"""
+
+import argparse
+import csv
+import socket
+import struct
+import sys
+
+PACKET_MAGIC = 0xA6
+VERSION = 1
+HEADER = struct.Struct("<BBHBx")
+RECORD = struct.Struct("<I" + "iihf" * 2)
+
+COLUMNS = [
+    "time_us",
+    "x_target", "x_pos", "x_pwm", "x_speed",
+    "y_target", "y_pos", "y_pwm", "y_speed",
+]
+
+
+def decode(packet):
+    """yields a csv row for every record in a packet"""
+    magic, version, _, count = HEADER.unpack_from(packet)
+    if magic != PACKET_MAGIC or version != VERSION:
+        return
+    for i in range(count):
+        t, xt, xp, xpwm, xs, yt, yp, ypwm, ys = RECORD.unpack_from(
+            packet, HEADER.size + i * RECORD.size
+        )
+        yield [t, xt, xp, xpwm / 100.0, xs, yt, yp, ypwm / 100.0, ys]
+
+
+def main():
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--host", default="192.168.20.99")
+    parser.add_argument("--port", type=int, default=1234)
+    parser.add_argument("--rate", type=int, default=200, help="samples per second")
+    parser.add_argument("--listen", type=int, default=34255, help="local port")
+    parser.add_argument("-o", "--output", help="csv file, stdout if omitted")
+    args = parser.parse_args()
+
+    sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+    sock.bind(("", args.listen))
+    plotter = (args.host, args.port)
+    # the plotter sends to whoever subscribed, so subscribe from the listening socket
+    sock.sendto(">telemetry {}".format(args.rate).encode(), plotter)
+
+    out = open(args.output, "w", newline="") if args.output else sys.stdout
+    writer = csv.writer(out)
+    writer.writerow(COLUMNS)
+
+    expected = None
+    try:
+        while True:
+            packet, _ = sock.recvfrom(2048)
+            if len(packet) < HEADER.size or packet[0] != PACKET_MAGIC:
+                # text replies from the plotter
+                sys.stderr.write(packet.decode(errors="replace"))
+                continue
+            seq = HEADER.unpack_from(packet)[2]
+            if expected is not None and seq != expected:
+                lost = (seq - expected) & 0xFFFF
+                print("lost {} packet(s)".format(lost), file=sys.stderr)
+            expected = (seq + 1) & 0xFFFF
+            writer.writerows(decode(packet))
+    except KeyboardInterrupt:
+        pass
+    finally:
+        sock.sendto(b">telemetry off", plotter)
+        if out is not sys.stdout:
+            out.close()
+
+
+if __name__ == "__main__":
+    main()